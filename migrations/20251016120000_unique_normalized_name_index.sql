@@ -0,0 +1,2 @@
+-- Names are stored trimmed and lowercase, make sure differently cased duplicates can never coexist
+CREATE UNIQUE INDEX IF NOT EXISTS unique_normalized_name_index ON names (LOWER(TRIM(name)));
@@ -0,0 +1,3 @@
+-- Optional structured metadata, kept next to the string metadata for Krist compatibility
+ALTER TABLE transactions ADD COLUMN metadata_json JSONB;
+CREATE INDEX transactions_metadata_json_idx ON transactions USING GIN (metadata_json jsonb_path_ops);
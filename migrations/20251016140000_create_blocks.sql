@@ -0,0 +1,12 @@
+-- ------------------------------
+-- TABLE: blocks
+-- ------------------------------
+-- Kromer does not mine, this holds imported Krist blocks so clients can show the chain tip.
+CREATE TABLE blocks (
+    height BIGINT PRIMARY KEY,
+    address CHAR(10) NOT NULL,
+    hash CHAR(64) NULL,
+    value NUMERIC(16, 2) NOT NULL,
+    time TIMESTAMPTZ NOT NULL,
+    difficulty BIGINT NOT NULL
+);
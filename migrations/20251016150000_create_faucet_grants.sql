@@ -0,0 +1,9 @@
+-- ------------------------------
+-- TABLE: faucet_grants
+-- ------------------------------
+-- One row per address that received the one-time websocket faucet grant.
+CREATE TABLE faucet_grants (
+    address CHAR(10) PRIMARY KEY,
+    amount NUMERIC(16, 2) NOT NULL,
+    granted_at TIMESTAMPTZ NOT NULL
+);
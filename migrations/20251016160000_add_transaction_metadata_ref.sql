@@ -0,0 +1,4 @@
+-- The CommonMeta `ref` value shops use to match payments to orders, kept indexed so lookups don't scan every transaction
+ALTER TABLE transactions
+    ADD COLUMN metadata_ref TEXT GENERATED ALWAYS AS (substring(metadata from '(?:^|;)ref=([^;]+)')) STORED;
+CREATE INDEX transactions_metadata_ref_idx ON transactions (metadata_ref);
@@ -0,0 +1,12 @@
+-- ------------------------------
+-- TABLE: webhooks
+-- ------------------------------
+-- URLs that get transaction and name events POSTed to them, signed with their secret.
+CREATE TABLE webhooks (
+    id SERIAL PRIMARY KEY,
+    url TEXT NOT NULL,
+    events TEXT[] NOT NULL,
+    secret TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX webhooks_events_idx ON webhooks USING GIN (events);
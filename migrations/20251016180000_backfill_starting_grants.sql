@@ -0,0 +1,12 @@
+-- Wallets used to get their starting 100 KRO through a plain balance update, which left no
+-- transaction behind and total_in at zero. Record those grants the way new wallets get them now,
+-- so the ledger reconciliation only flags wallets that are actually off.
+WITH legacy AS (
+    SELECT address, created_at FROM wallets
+    WHERE address <> 'serverwelf' AND balance - (total_in - total_out) = 100.00
+), granted AS (
+    INSERT INTO transactions(amount, "from", "to", transaction_type, date)
+    SELECT 100.00, 'serverwelf', address, 'mined', created_at FROM legacy
+)
+UPDATE wallets SET total_in = total_in + 100.00
+WHERE address IN (SELECT address FROM legacy);
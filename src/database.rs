@@ -1,7 +1,11 @@
+pub mod block;
+pub mod faucet;
+pub mod ledger;
 pub mod name;
 pub mod player;
 pub mod transaction;
 pub mod wallet;
+pub mod webhook;
 
 use sqlx::{Encode, Executor, Postgres, prelude::Type};
 
@@ -24,7 +28,7 @@ pub trait ModelExt<'q> {
         T: 'q + Encode<'q, Postgres> + Type<Postgres> + Send,
         E: 'q + Executor<'q, Database = Postgres>;
 
-    /// Fetches all records from a table and returns them
+    /// Fetches all records from a table and returns them, ordered by id so pages never overlap
     async fn fetch_all<E>(pool: E, limit: i64, offset: i64) -> Result<Vec<Self>>
     where
         Self: Sized,
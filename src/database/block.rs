@@ -0,0 +1,85 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::{Decimal, prelude::ToPrimitive};
+use sqlx::{Executor, Postgres};
+
+use crate::database::{DatabaseError, Result};
+use crate::models::krist::blocks::BlockJson;
+
+/// Length of the hash prefix Krist shows as `short_hash`
+const SHORT_HASH_LENGTH: usize = 12;
+
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct Model {
+    pub height: i64,
+    pub address: String,
+    pub hash: Option<String>,
+    pub value: Decimal,
+    pub time: DateTime<Utc>,
+    pub difficulty: i64,
+}
+
+impl<'q> Model {
+    /// Fetch the block with the greatest height, if there are any blocks at all.
+    pub async fn fetch_latest<E>(pool: E) -> Result<Option<Model>>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT * FROM blocks ORDER BY height DESC LIMIT 1";
+
+        sqlx::query_as(q)
+            .fetch_optional(pool)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+}
+
+impl From<Model> for BlockJson {
+    fn from(block: Model) -> Self {
+        let short_hash = block
+            .hash
+            .as_ref()
+            .map(|hash| hash.chars().take(SHORT_HASH_LENGTH).collect());
+
+        Self {
+            height: block.height as f64,
+            address: block.address,
+            hash: block.hash,
+            short_hash,
+            value: block.value.to_f64().unwrap_or_default(),
+            time: block.time.to_rfc3339(),
+            difficulty: block.difficulty as f64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::dec;
+
+    use super::*;
+
+    #[test]
+    fn test_block_json() {
+        let block = Model {
+            height: 2121616,
+            address: "kristdeath".into(),
+            hash: Some("00000000009f16ac5ded918793310016ea2d61a29d5a328e244cd8478da6924c".into()),
+            value: dec!(1),
+            time: DateTime::from_timestamp(1658263389, 0).unwrap(),
+            difficulty: 551,
+        };
+
+        let json = BlockJson::from(block.clone());
+        assert_eq!(json.height, 2121616.0);
+        assert_eq!(json.short_hash.as_deref(), Some("00000000009f"));
+        assert_eq!(json.value, 1.0);
+        assert_eq!(json.time, "2022-07-19T20:43:09+00:00");
+
+        // Blocks imported without a hash have no short hash either
+        let json = BlockJson::from(Model {
+            hash: None,
+            ..block
+        });
+        assert_eq!(json.short_hash, None);
+    }
+}
@@ -0,0 +1,83 @@
+use rust_decimal::Decimal;
+use sqlx::{Pool, Postgres};
+
+use crate::database::ledger::WELFARE_ADDRESS;
+use crate::database::transaction::{Model as Transaction, TransactionCreateData, TransactionType};
+use crate::database::wallet::Model as Wallet;
+use crate::database::{DatabaseError, Result};
+
+/// Credit `amount` to `address` unless it already received a faucet grant. Returns the updated
+/// wallet and the grant's transaction when the grant was made.
+pub async fn grant_once(
+    pool: &Pool<Postgres>,
+    address: &str,
+    amount: Decimal,
+) -> Result<Option<(Wallet, Transaction)>> {
+    let mut tx = pool.begin().await?;
+
+    // The primary key makes this the once-per-address check, concurrent connects can't both win.
+    let q = "INSERT INTO faucet_grants(address, amount, granted_at) VALUES ($1, $2, NOW()) ON CONFLICT DO NOTHING";
+    let inserted = sqlx::query(q)
+        .bind(address)
+        .bind(amount)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    if inserted == 0 {
+        return Ok(None);
+    }
+
+    let Some(wallet) = Wallet::fetch_by_address(&mut *tx, address).await? else {
+        return Ok(None);
+    };
+    let updated_wallet = wallet.update_balance(&mut *tx, amount).await?;
+
+    let creation_data = TransactionCreateData {
+        from: WELFARE_ADDRESS.clone(),
+        to: address.to_owned(),
+        amount,
+        metadata: Some("faucet".into()),
+        transaction_type: TransactionType::Transfer,
+        ..Default::default()
+    };
+    let transaction = Transaction::create_no_update(&mut *tx, creation_data).await?;
+
+    tx.commit().await.map_err(DatabaseError::Sqlx)?;
+
+    Ok(Some((updated_wallet, transaction)))
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::dec;
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn test_grant_once_per_address(pool: sqlx::PgPool) {
+        Wallet::create_wallet(&pool, "kalice0000", "alice", None)
+            .await
+            .unwrap();
+
+        let (wallet, transaction) = grant_once(&pool, "kalice0000", dec!(5))
+            .await
+            .unwrap()
+            .expect("the first connect should get the grant");
+        assert_eq!(wallet.balance, dec!(5));
+        assert_eq!(transaction.to, "kalice0000");
+        assert_eq!(transaction.amount, dec!(5));
+
+        // Later connects, even asking for a different amount, get nothing
+        assert!(
+            grant_once(&pool, "kalice0000", dec!(10))
+                .await
+                .unwrap()
+                .is_none()
+        );
+        let wallet = Wallet::fetch_by_address(&pool, "kalice0000")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(wallet.balance, dec!(5));
+    }
+}
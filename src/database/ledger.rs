@@ -0,0 +1,282 @@
+use once_cell::sync::Lazy;
+use rust_decimal::Decimal;
+use sqlx::{Pool, Postgres};
+
+use crate::database::{DatabaseError, Result};
+use crate::errors::wallet::WalletError;
+
+/// The default [`WELFARE_ADDRESS`], created by the initial migrations
+const DEFAULT_WELFARE_ADDRESS: &str = "serverwelf";
+
+/// The system wallet welfare, mints, name purchases and faucet grants are paid from and burned
+/// money goes to. Read from `WELFARE_ADDRESS`, defaults to `serverwelf`.
+pub static WELFARE_ADDRESS: Lazy<String> =
+    Lazy::new(|| welfare_address(std::env::var("WELFARE_ADDRESS").ok()));
+
+/// Addresses that mint and burn money, left out of the supply. Read from the comma separated
+/// `LEDGER_SYSTEM_ADDRESSES`, defaults to the [`WELFARE_ADDRESS`].
+pub static SYSTEM_ADDRESSES: Lazy<Vec<String>> = Lazy::new(|| {
+    std::env::var("LEDGER_SYSTEM_ADDRESSES")
+        .unwrap_or_else(|_| WELFARE_ADDRESS.clone())
+        .split(',')
+        .map(str::trim)
+        .filter(|address| !address.is_empty())
+        .map(str::to_owned)
+        .collect()
+});
+
+fn welfare_address(value: Option<String>) -> String {
+    value
+        .map(|address| address.trim().to_owned())
+        .filter(|address| !address.is_empty())
+        .unwrap_or_else(|| DEFAULT_WELFARE_ADDRESS.to_owned())
+}
+
+/// Create the wallet for `address` if it does not exist yet, meant for the [`WELFARE_ADDRESS`]
+/// at startup.
+pub async fn ensure_system_wallet(pool: &Pool<Postgres>, address: &str) -> Result<()> {
+    if !is_valid_system_address(address) {
+        return Err(DatabaseError::Wallet(WalletError::InvalidAddress(
+            address.to_owned(),
+        )));
+    }
+
+    let q = "INSERT INTO wallets(address, balance, created_at) VALUES ($1, 0.00, NOW()) ON CONFLICT (address) DO NOTHING";
+    let inserted = sqlx::query(q)
+        .bind(address)
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+    if inserted > 0 {
+        tracing::info!("Created missing system wallet {address}");
+    }
+
+    Ok(())
+}
+
+/// System addresses don't have to look like `k` addresses, but they still have to be plain
+/// lowercase letters and digits that fit the address column, like `serverwelf`.
+fn is_valid_system_address(address: &str) -> bool {
+    (1..=10).contains(&address.len())
+        && address
+            .bytes()
+            .all(|byte| byte.is_ascii_lowercase() || byte.is_ascii_digit())
+}
+
+/// A wallet whose balance does not match its own `total_in - total_out`
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow, serde::Serialize)]
+pub struct WalletMismatch {
+    pub address: String,
+    pub balance: Decimal,
+    pub expected_balance: Decimal,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, sqlx::FromRow)]
+pub struct LedgerTotals {
+    /// Everything system addresses sent to other wallets
+    pub minted: Decimal,
+    /// Everything other wallets sent to system addresses
+    pub burned: Decimal,
+    /// The sum of all balances outside of the system addresses
+    pub supply: Decimal,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ReconciliationReport {
+    /// Whether every invariant holds
+    pub ok: bool,
+    pub minted: Decimal,
+    pub burned: Decimal,
+    pub expected_supply: Decimal,
+    pub actual_supply: Decimal,
+    /// `actual_supply - expected_supply`, anything but zero means money appeared or vanished
+    pub discrepancy: Decimal,
+    pub mismatched_wallets: Vec<WalletMismatch>,
+}
+
+impl ReconciliationReport {
+    pub fn new(totals: LedgerTotals, mismatched_wallets: Vec<WalletMismatch>) -> Self {
+        let expected_supply = totals.minted - totals.burned;
+        let discrepancy = totals.supply - expected_supply;
+
+        Self {
+            ok: discrepancy.is_zero() && mismatched_wallets.is_empty(),
+            minted: totals.minted,
+            burned: totals.burned,
+            expected_supply,
+            actual_supply: totals.supply,
+            discrepancy,
+            mismatched_wallets,
+        }
+    }
+}
+
+/// Check that the money supply equals everything minted minus everything burned, and that
+/// every wallet's balance agrees with its totals.
+pub async fn reconcile(
+    pool: &Pool<Postgres>,
+    system_addresses: &[String],
+) -> Result<ReconciliationReport> {
+    let mut tx = pool.begin().await?;
+
+    let q = r#"
+    SELECT
+        (SELECT COALESCE(SUM(amount), 0) FROM transactions
+            WHERE "from" = ANY($1) AND NOT ("to" = ANY($1))) AS minted,
+        (SELECT COALESCE(SUM(amount), 0) FROM transactions
+            WHERE "to" = ANY($1) AND NOT (COALESCE("from", '') = ANY($1))) AS burned,
+        (SELECT COALESCE(SUM(balance), 0) FROM wallets
+            WHERE NOT (address = ANY($1))) AS supply
+    "#;
+    let totals: LedgerTotals = sqlx::query_as(q)
+        .bind(system_addresses)
+        .fetch_one(&mut *tx)
+        .await?;
+
+    let q = r#"
+    SELECT address, balance, total_in - total_out AS expected_balance
+    FROM wallets
+    WHERE balance != total_in - total_out AND NOT (address = ANY($1))
+    ORDER BY address ASC
+    "#;
+    let mismatched_wallets = sqlx::query_as(q)
+        .bind(system_addresses)
+        .fetch_all(&mut *tx)
+        .await?;
+
+    tx.commit().await.map_err(DatabaseError::Sqlx)?;
+
+    Ok(ReconciliationReport::new(totals, mismatched_wallets))
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::dec;
+
+    use super::*;
+    use crate::database::transaction::{
+        Model as Transaction, TransactionCreateData, TransactionType,
+    };
+    use crate::database::wallet::Model as Wallet;
+
+    #[test]
+    fn test_welfare_address() {
+        assert_eq!(welfare_address(None), "serverwelf");
+        assert_eq!(welfare_address(Some("  ".into())), "serverwelf");
+        assert_eq!(welfare_address(Some(" testwelf \n".into())), "testwelf");
+    }
+
+    #[sqlx::test]
+    async fn test_supply_excludes_welfare(pool: sqlx::PgPool) {
+        ensure_system_wallet(&pool, "testwelf").await.unwrap();
+        // Creating it again leaves it alone
+        ensure_system_wallet(&pool, "testwelf").await.unwrap();
+        sqlx::query(
+            "UPDATE wallets SET balance = 1000 WHERE address IN ('testwelf', 'serverwelf')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        Wallet::create_wallet(&pool, "kalice0000", "alice", Some(dec!(25)))
+            .await
+            .unwrap();
+
+        // The welfare wallet that isn't configured is counted like any other
+        let supply = Wallet::money_supply(&pool, "testwelf").await.unwrap();
+        assert_eq!(supply, dec!(1025));
+        let supply = Wallet::money_supply(&pool, "serverwelf").await.unwrap();
+        assert_eq!(supply, dec!(1025));
+
+        for address in ["TestWelf", "test welf", "testwelf000", ""] {
+            assert!(
+                matches!(
+                    ensure_system_wallet(&pool, address).await,
+                    Err(DatabaseError::Wallet(WalletError::InvalidAddress(_)))
+                ),
+                "{address:?} should be rejected"
+            );
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_reconcile(pool: sqlx::PgPool) {
+        let system = vec!["serverwelf".to_owned()];
+
+        // A wallet from before starting grants were recorded, its balance has no totals behind it
+        Wallet::create_wallet(&pool, "klegacy000", "legacy", Some(dec!(100)))
+            .await
+            .unwrap();
+        let report = reconcile(&pool, &system).await.unwrap();
+        assert!(!report.ok);
+        assert_eq!(report.discrepancy, dec!(100));
+        assert_eq!(report.mismatched_wallets.len(), 1);
+
+        sqlx::raw_sql(include_str!(
+            "../../migrations/20251016180000_backfill_starting_grants.sql"
+        ))
+        .execute(&pool)
+        .await
+        .unwrap();
+        let report = reconcile(&pool, &system).await.unwrap();
+        assert!(report.ok, "{report:?}");
+        assert_eq!(report.minted, dec!(100));
+
+        // A new wallet gets its grant, pays someone, and that someone burns part of it
+        let alice = Wallet::create_wallet(&pool, "kalice0000", "alice", None)
+            .await
+            .unwrap();
+        alice.update_balance(&pool, dec!(100)).await.unwrap();
+        let grant = TransactionCreateData {
+            from: "serverwelf".into(),
+            to: "kalice0000".into(),
+            amount: dec!(100),
+            transaction_type: TransactionType::Mined,
+            ..Default::default()
+        };
+        Transaction::create_no_update(&pool, grant).await.unwrap();
+        let bob = Wallet::create_wallet(&pool, "kbob000000", "bob", None)
+            .await
+            .unwrap();
+        let transfer = TransactionCreateData {
+            from: "kalice0000".into(),
+            to: "kbob000000".into(),
+            amount: dec!(30),
+            transaction_type: TransactionType::Transfer,
+            ..Default::default()
+        };
+        Transaction::create(&pool, transfer).await.unwrap();
+        bob.update_balance(&pool, dec!(-5)).await.unwrap();
+        let burn = TransactionCreateData {
+            from: "kbob000000".into(),
+            to: "serverwelf".into(),
+            amount: dec!(5),
+            transaction_type: TransactionType::Transfer,
+            ..Default::default()
+        };
+        Transaction::create_no_update(&pool, burn).await.unwrap();
+
+        let report = reconcile(&pool, &system).await.unwrap();
+        assert!(report.ok, "{report:?}");
+        assert_eq!(report.minted, dec!(200));
+        assert_eq!(report.burned, dec!(5));
+        assert_eq!(report.actual_supply, dec!(195));
+
+        // 1 KRO that appeared out of nowhere
+        sqlx::query("UPDATE wallets SET balance = balance + 1 WHERE address = 'kbob000000'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let report = reconcile(&pool, &system).await.unwrap();
+        assert!(!report.ok);
+        assert_eq!(report.discrepancy, dec!(1));
+        assert_eq!(
+            report.mismatched_wallets,
+            vec![WalletMismatch {
+                address: "kbob000000".into(),
+                balance: dec!(26),
+                expected_balance: dec!(25),
+            }]
+        );
+    }
+}
@@ -1,5 +1,5 @@
 use async_trait::async_trait;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use rust_decimal::{Decimal, dec};
 use sqlx::{Acquire, Encode, Executor, Pool, Postgres, Type};
 
@@ -13,8 +13,11 @@ use crate::errors::wallet::WalletError;
 use crate::models::krist::websockets::{WebSocketEvent, WebSocketMessage};
 use crate::websockets::WebSocketServer;
 use crate::{
-    database::ModelExt, errors::krist::generic::GenericError,
-    models::krist::names::NameDataUpdateBody, routes::PaginationParams, utils::validation,
+    database::ModelExt,
+    errors::krist::generic::GenericError,
+    models::krist::names::NameDataUpdateBody,
+    routes::{DEFAULT_PAGE_LIMIT, PaginationParams, page_limit},
+    utils::validation,
 };
 
 #[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
@@ -30,6 +33,13 @@ pub struct Model {
     pub metadata: Option<String>,
 }
 
+/// How many names were registered on a single UTC day
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow, serde::Serialize, utoipa::ToSchema)]
+pub struct DailyRegistrations {
+    pub day: NaiveDate,
+    pub count: i64,
+}
+
 #[async_trait]
 impl<'q> ModelExt<'q> for Model {
     async fn fetch_by_id<T, E>(pool: E, id: T) -> Result<Option<Self>>
@@ -52,8 +62,12 @@ impl<'q> ModelExt<'q> for Model {
         Self: Sized,
         E: 'q + Executor<'q, Database = Postgres>,
     {
-        let limit = limit.clamp(1, 1000);
-        let q = "SELECT * from names LIMIT $1 OFFSET $2";
+        let limit = page_limit(limit);
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+
+        let q = "SELECT * from names ORDER BY id LIMIT $1 OFFSET $2";
 
         sqlx::query_as(q)
             .bind(limit)
@@ -91,15 +105,32 @@ impl<'q> Model {
             .map_err(DatabaseError::Sqlx)
     }
 
+    /// Get every name in `names` that exists, in no particular order
+    pub async fn fetch_by_names<E>(pool: E, names: &[String]) -> Result<Vec<Model>>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT * FROM names WHERE name = ANY($1);";
+
+        sqlx::query_as(q)
+            .bind(names)
+            .fetch_all(pool)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
     pub async fn all_unpaid<E>(pool: E, pagination: &PaginationParams) -> Result<Vec<Model>>
     where
         E: 'q + Executor<'q, Database = Postgres>,
     {
-        let limit = pagination.limit.unwrap_or(50);
+        let limit = pagination.limit.unwrap_or(*DEFAULT_PAGE_LIMIT);
         let offset = pagination.offset.unwrap_or(0);
-        let limit = limit.clamp(1, 1000);
+        let limit = page_limit(limit);
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
 
-        let q = "SELECT * FROM names WHERE unpaid > 0 LIMIT $1 OFFSET $2";
+        let q = "SELECT * FROM names WHERE unpaid > 0 ORDER BY id LIMIT $1 OFFSET $2";
 
         sqlx::query_as(q)
             .bind(limit)
@@ -118,18 +149,62 @@ impl<'q> Model {
         sqlx::query_scalar(q).fetch_one(pool).await
     }
 
+    /// Count registrations per UTC day, newest day first. Days without registrations are left out.
+    pub async fn registrations_per_day<E>(
+        pool: E,
+        pagination: &PaginationParams,
+    ) -> Result<Vec<DailyRegistrations>>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let limit = page_limit(pagination.limit.unwrap_or(*DEFAULT_PAGE_LIMIT));
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+
+        let offset = pagination.offset.unwrap_or(0);
+
+        let q = r#"
+        SELECT (time_registered AT TIME ZONE 'UTC')::date AS day, COUNT(*) AS count
+        FROM names
+        GROUP BY day
+        ORDER BY day DESC
+        LIMIT $1 OFFSET $2
+        "#;
+
+        sqlx::query_as(q)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(pool)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    /// Register a new name. The name is stored in its normalized (trimmed, lowercase) form.
     pub async fn create<E>(pool: E, name: String, owner: String) -> Result<Model>
     where
         E: 'q + Executor<'q, Database = Postgres>,
     {
+        let name = validation::normalize_name(&name);
+        if !validation::is_valid_name(&name, false) {
+            return Err(DatabaseError::Generic(GenericError::InvalidParameter(
+                "name".to_owned(),
+            )));
+        }
+
         let q = "INSERT INTO names(name, owner, original_owner, time_registered) VALUES ($1, $2, $2, NOW()) RETURNING *";
 
         sqlx::query_as(q)
-            .bind(name)
+            .bind(&name)
             .bind(owner)
             .fetch_one(pool)
             .await
-            .map_err(DatabaseError::Sqlx)
+            .map_err(|err| match err.as_database_error() {
+                Some(db_err) if db_err.is_unique_violation() => {
+                    DatabaseError::Name(NameError::NameTaken(name))
+                }
+                _ => DatabaseError::Sqlx(err),
+            })
     }
 
     pub async fn update_metadata<E, S>(pool: E, name: S, metadata: String) -> Result<Model>
@@ -149,11 +224,12 @@ impl<'q> Model {
             .map_err(DatabaseError::Sqlx)
     }
 
+    /// Update the metadata of a name, returning the name and whether the metadata actually changed.
     pub async fn ctrl_update_metadata<S: AsRef<str>>(
         pool: &Pool<Postgres>,
         name: S,
         body: NameDataUpdateBody,
-    ) -> Result<Model> {
+    ) -> Result<(Model, bool)> {
         let name = name.as_ref();
 
         let metadata_record = match body.a {
@@ -177,7 +253,7 @@ impl<'q> Model {
             )));
         }
 
-        let name = name.trim().to_lowercase();
+        let name = validation::normalize_name(name);
         let wallet = Wallet::verify_address(pool, body.private_key).await?;
         if !wallet.authed {
             tracing::info!("Auth failed on name update");
@@ -191,13 +267,18 @@ impl<'q> Model {
             return Err(DatabaseError::Name(NameError::NotNameOwner(name)));
         }
 
-        if model.metadata == Some(metadata_record.clone()) {
-            return Ok(model);
+        if !model.metadata_changed(&metadata_record) {
+            return Ok((model, false));
         }
 
         let updated_model = Self::update_metadata(pool, &name, metadata_record).await?;
 
-        Ok(updated_model)
+        Ok((updated_model, true))
+    }
+
+    /// Whether setting the metadata to `metadata` would actually change it
+    pub fn metadata_changed(&self, metadata: &str) -> bool {
+        self.metadata.as_deref() != Some(metadata)
     }
 
     /// Fetches the owner of the wallet and returns its database model.
@@ -273,3 +354,128 @@ impl<'q> Model {
         Ok(updated_name)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::krist::{KristError, KristErrorExt};
+
+    fn name_with_metadata(metadata: Option<&str>) -> Model {
+        Model {
+            id: 1,
+            last_transfered: None,
+            last_updated: None,
+            name: "example".to_owned(),
+            owner: "kre3w0i79j".to_owned(),
+            original_owner: "kre3w0i79j".to_owned(),
+            time_registered: Utc::now(),
+            unpaid: dec!(0),
+            metadata: metadata.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn test_metadata_changed() {
+        let name = name_with_metadata(Some("https://example.com"));
+        assert!(!name.metadata_changed("https://example.com"));
+        assert!(name.metadata_changed("https://example.org"));
+
+        let name = name_with_metadata(None);
+        assert!(name.metadata_changed("https://example.com"));
+    }
+
+    #[test]
+    fn test_daily_registrations_json() {
+        let day = DailyRegistrations {
+            day: NaiveDate::from_ymd_opt(2025, 10, 16).unwrap(),
+            count: 3,
+        };
+
+        assert_eq!(
+            serde_json::to_value(&day).unwrap(),
+            serde_json::json!({"day": "2025-10-16", "count": 3})
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_mixed_case_duplicates(pool: sqlx::PgPool) {
+        Model::create(&pool, "Shop".into(), "kalice0000".into())
+            .await
+            .unwrap();
+
+        let err = Model::create(&pool, " SHOP ".into(), "kbob000000".into())
+            .await
+            .expect_err("a differently cased name is the same name");
+        assert!(
+            matches!(&err, DatabaseError::Name(NameError::NameTaken(name)) if name == "shop"),
+            "{err:?}"
+        );
+        assert_eq!(KristError::from(err).error_type(), "name_taken");
+
+        // The index holds even for an insert that skips normalizing
+        let q = "INSERT INTO names(name, owner, original_owner, time_registered) VALUES ('sHoP', 'kbob000000', 'kbob000000', NOW())";
+        let err = sqlx::query(q).execute(&pool).await.unwrap_err();
+        assert!(
+            err.as_database_error()
+                .is_some_and(|err| err.is_unique_violation())
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_registrations_per_day(pool: sqlx::PgPool) {
+        for (name, time_registered) in [
+            ("early", "2025-10-14 12:00:00+00"),
+            ("late", "2025-10-15 23:30:00+00"),
+            // Still the 15th in UTC, although it is the 16th where it was registered
+            ("ahead", "2025-10-16 01:00:00+02"),
+            ("next", "2025-10-16 00:30:00+00"),
+        ] {
+            sqlx::query("INSERT INTO names(name, owner, original_owner, time_registered) VALUES ($1, 'kalice0000', 'kalice0000', $2::timestamptz)")
+                .bind(name)
+                .bind(time_registered)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        // The session's own time zone must not move the day boundaries
+        let mut conn = pool.acquire().await.unwrap();
+        sqlx::query("SET TIME ZONE 'America/New_York'")
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        let days = Model::registrations_per_day(&mut *conn, &PaginationParams::default())
+            .await
+            .unwrap();
+
+        let day = |day: &str| day.parse::<NaiveDate>().unwrap();
+        assert_eq!(
+            days,
+            vec![
+                DailyRegistrations {
+                    day: day("2025-10-16"),
+                    count: 1
+                },
+                DailyRegistrations {
+                    day: day("2025-10-15"),
+                    count: 2
+                },
+                DailyRegistrations {
+                    day: day("2025-10-14"),
+                    count: 1
+                },
+            ]
+        );
+
+        let params = PaginationParams {
+            limit: Some(1),
+            offset: Some(1),
+            ..Default::default()
+        };
+        let page = Model::registrations_per_day(&mut *conn, &params)
+            .await
+            .unwrap();
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].day, day("2025-10-15"));
+    }
+}
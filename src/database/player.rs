@@ -4,6 +4,7 @@ use uuid::Uuid;
 
 use crate::database::wallet::Model as Wallet;
 use crate::database::{DatabaseError, ModelExt, Result};
+use crate::routes::page_limit;
 
 #[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
 pub struct Model {
@@ -34,8 +35,12 @@ impl<'q> ModelExt<'q> for Model {
         Self: Sized,
         E: 'q + Executor<'q, Database = Postgres>,
     {
-        let limit = limit.clamp(1, 1000);
-        let q = "SELECT * from players LIMIT $1 OFFSET $2";
+        let limit = page_limit(limit);
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+
+        let q = "SELECT * from players ORDER BY id LIMIT $1 OFFSET $2";
 
         sqlx::query_as(q)
             .bind(limit)
@@ -99,6 +104,38 @@ impl<'q> Model {
             .map_err(DatabaseError::Sqlx)
     }
 
+    /// Make this player the only owner of `wallet`, taking it away from whoever owned it before.
+    /// Only the ownership arrays change, the wallet itself is left alone.
+    pub async fn take_over_wallet(
+        &self,
+        tx: &mut sqlx::Transaction<'_, Postgres>,
+        wallet: &Wallet,
+    ) -> Result<Model> {
+        let q = "UPDATE players SET owned_wallets = array_remove(owned_wallets, $1) WHERE $1 = ANY(owned_wallets);";
+        sqlx::query(q).bind(wallet.id).execute(&mut **tx).await?;
+
+        self.add_wallet_to_owned(&mut **tx, wallet).await
+    }
+
+    /// Fetch the player owning the wallet with this address, if anyone owns it.
+    pub async fn fetch_by_wallet_address<E>(executor: E, address: &str) -> Result<Option<Self>>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = r#"
+            SELECT player.*
+            FROM players player
+            JOIN wallets wallet ON wallet.id = ANY(player.owned_wallets)
+            WHERE wallet.address = $1;
+            "#;
+
+        sqlx::query_as(q)
+            .bind(address)
+            .fetch_optional(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
     /// Get this player's owned wallets.
     pub async fn owned_wallets<E>(&self, executor: E) -> Result<Vec<Wallet>>
     where
@@ -120,3 +157,46 @@ impl<'q> Model {
             .map_err(DatabaseError::Sqlx)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::dec;
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn test_take_over_wallet(pool: sqlx::PgPool) {
+        let wallet = Wallet::create_wallet(&pool, "kshop00000", "shop", Some(dec!(25)))
+            .await
+            .unwrap();
+        let alice = Model::create(&pool, Uuid::new_v4(), "alice".to_owned())
+            .await
+            .unwrap();
+        let bob = Model::create(&pool, Uuid::new_v4(), "bob".to_owned())
+            .await
+            .unwrap();
+        alice.add_wallet_to_owned(&pool, &wallet).await.unwrap();
+
+        let mut tx = pool.begin().await.unwrap();
+        let bob = bob.take_over_wallet(&mut tx, &wallet).await.unwrap();
+        tx.commit().await.unwrap();
+
+        assert_eq!(bob.owned_wallets, vec![wallet.id]);
+        let alice = Model::fetch_by_id(&pool, alice.id).await.unwrap().unwrap();
+        assert!(alice.owned_wallets.is_empty());
+
+        let owner = Model::fetch_by_wallet_address(&pool, "kshop00000")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(owner.id, bob.id);
+
+        // The wallet itself is untouched
+        let after = Wallet::fetch_by_address(&pool, "kshop00000")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(after.balance, wallet.balance);
+        assert_eq!(after.address, wallet.address);
+    }
+}
@@ -1,5 +1,8 @@
+use std::collections::BTreeMap;
+
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
+use futures_util::{StreamExt, TryStreamExt, stream::BoxStream};
 use once_cell::sync::Lazy;
 use regex::Regex;
 use rust_decimal::Decimal;
@@ -8,14 +11,156 @@ use sqlx::{Acquire, Encode, Executor, Pool, Postgres, Type};
 use utoipa::ToSchema;
 
 use crate::database::{DatabaseError, Result};
-use crate::{database::ModelExt, routes::PaginationParams};
+use crate::{
+    database::ModelExt,
+    routes::{DEFAULT_PAGE_LIMIT, PaginationParams, SortOrder, page_limit},
+    utils::env,
+};
 
 use crate::database::wallet::Model as Wallet;
+use crate::errors::transaction::TransactionError;
 use crate::errors::wallet::WalletError;
 
 static KRO_REGEX: Lazy<Regex> =
     Lazy::new(|| Regex::new(r"^(?:([a-z0-9-_]{1,32})@)?([a-z0-9]{1,64})\.kro").unwrap());
 
+/// How many seconds an identical transfer is rejected for, 0 disables the check
+pub static DUPLICATE_TRANSACTION_WINDOW: Lazy<f64> =
+    Lazy::new(|| env::var_or("DUPLICATE_TRANSACTION_WINDOW_SECS", 0.0));
+
+/// Largest amount a single transfer may move, unset means there is no cap. A bad value is caught by
+/// [`validate_limits`] at startup, it must never quietly lift the cap.
+pub static MAX_TRANSACTION_AMOUNT: Lazy<Option<Decimal>> =
+    Lazy::new(|| max_transaction_amount().unwrap_or_else(|err| panic!("{err}")));
+
+fn max_transaction_amount() -> Result<Option<Decimal>, String> {
+    match env::var_opt::<Decimal>("MAX_TRANSACTION_AMOUNT")? {
+        Some(amount) if amount <= Decimal::ZERO => Err(format!(
+            "MAX_TRANSACTION_AMOUNT ({amount}) must be greater than 0"
+        )),
+        amount => Ok(amount),
+    }
+}
+
+/// Check the transfer limits and metadata mode read from the environment, meant to be called once
+/// at startup.
+pub fn validate_limits() -> Result<(), String> {
+    max_transaction_amount()?;
+    metadata_sanitize_mode()?;
+    Ok(())
+}
+
+/// Decimal places every amount is stored with, matching the scale of the amount columns
+pub const AMOUNT_SCALE: u32 = 2;
+
+/// Reject amounts with more decimal places than [`AMOUNT_SCALE`], and return the amount at exactly that scale.
+pub fn normalize_amount(amount: Decimal) -> Result<Decimal, TransactionError> {
+    // Trailing zeros are not extra precision, `1.500` is fine.
+    if amount.normalize().scale() > AMOUNT_SCALE {
+        return Err(TransactionError::TooPrecise(AMOUNT_SCALE));
+    }
+
+    let mut amount = amount;
+    amount.rescale(AMOUNT_SCALE);
+
+    Ok(amount)
+}
+
+/// Krist caps transaction metadata at this many characters
+pub const MAX_METADATA_LENGTH: usize = 255;
+
+/// Check a transfer against the amount cap and the metadata length limit.
+pub fn check_transfer_limits(
+    amount: Decimal,
+    metadata: Option<&str>,
+    max_amount: Option<Decimal>,
+) -> Result<(), TransactionError> {
+    if let Some(max_amount) = max_amount
+        && amount > max_amount
+    {
+        return Err(TransactionError::TooLarge(max_amount));
+    }
+
+    if metadata.is_some_and(|metadata| metadata.chars().count() > MAX_METADATA_LENGTH) {
+        return Err(TransactionError::MetadataTooLong(MAX_METADATA_LENGTH));
+    }
+
+    Ok(())
+}
+
+/// Longest `metadataJson` a transfer may carry, counted in characters once serialized
+pub const MAX_METADATA_JSON_LENGTH: usize = 1024;
+
+/// Check structured metadata against [`MAX_METADATA_JSON_LENGTH`], it isn't covered by the
+/// plain metadata limit in [`check_transfer_limits`].
+pub fn check_metadata_json(metadata_json: &serde_json::Value) -> Result<(), TransactionError> {
+    if metadata_json.to_string().chars().count() > MAX_METADATA_JSON_LENGTH {
+        return Err(TransactionError::MetadataTooLong(MAX_METADATA_JSON_LENGTH));
+    }
+
+    Ok(())
+}
+
+/// What happens to control characters and irregular whitespace in transaction metadata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MetadataSanitizeMode {
+    /// Store metadata as it was sent
+    #[default]
+    Off,
+    /// Collapse whitespace runs into a single space and strip other control characters
+    Sanitize,
+    /// Reject metadata that sanitizing would change
+    Strict,
+}
+
+impl std::str::FromStr for MetadataSanitizeMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "sanitize" => Ok(Self::Sanitize),
+            "strict" => Ok(Self::Strict),
+            _ => Err(format!("unknown metadata sanitize mode {value}")),
+        }
+    }
+}
+
+/// Read from `TRANSACTION_METADATA_SANITIZE`, one of `off`, `sanitize` or `strict`. A typo is
+/// caught by [`validate_limits`] at startup instead of quietly turning sanitizing off.
+pub static METADATA_SANITIZE_MODE: Lazy<MetadataSanitizeMode> =
+    Lazy::new(|| metadata_sanitize_mode().unwrap_or_else(|err| panic!("{err}")));
+
+fn metadata_sanitize_mode() -> Result<MetadataSanitizeMode, String> {
+    Ok(env::var_opt("TRANSACTION_METADATA_SANITIZE")?.unwrap_or_default())
+}
+
+/// Apply `mode` to transaction metadata before it is stored.
+pub fn sanitize_metadata(
+    metadata: Option<String>,
+    mode: MetadataSanitizeMode,
+) -> Result<Option<String>, TransactionError> {
+    let Some(metadata) = metadata else {
+        return Ok(None);
+    };
+    if mode == MetadataSanitizeMode::Off {
+        return Ok(Some(metadata));
+    }
+
+    let sanitized = metadata
+        .split(char::is_whitespace)
+        .filter(|word| !word.is_empty())
+        .map(|word| word.chars().filter(|c| !c.is_control()).collect::<String>())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    if mode == MetadataSanitizeMode::Strict && sanitized != metadata {
+        return Err(TransactionError::InvalidMetadata);
+    }
+
+    Ok(Some(sanitized))
+}
+
 #[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
 pub struct Model {
     pub id: i32,
@@ -23,6 +168,9 @@ pub struct Model {
     pub from: Option<String>,
     pub to: String,
     pub metadata: Option<String>,
+    /// Structured metadata, next to the CommonMeta string
+    #[sqlx(default)]
+    pub metadata_json: Option<serde_json::Value>,
     pub name: Option<String>,
     pub sent_metaname: Option<String>,
     pub sent_name: Option<String>,
@@ -51,10 +199,13 @@ pub struct TransactionCreateData {
     pub to: String,
     pub amount: Decimal,
     pub metadata: Option<String>,
+    pub metadata_json: Option<serde_json::Value>,
     pub name: Option<String>,
     pub sent_metaname: Option<String>,
     pub sent_name: Option<String>,
     pub transaction_type: TransactionType,
+    /// Skip the duplicate window check for this transfer
+    pub allow_duplicate: bool,
 }
 
 #[derive(Debug, Default, Clone, PartialEq, PartialOrd, serde::Serialize, serde::Deserialize)]
@@ -111,8 +262,12 @@ impl<'q> ModelExt<'q> for Model {
         Self: Sized,
         E: 'q + Executor<'q, Database = Postgres>,
     {
-        let limit = limit.clamp(1, 1000);
-        let q = "SELECT * from transactions LIMIT $1 OFFSET $2";
+        let limit = page_limit(limit);
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+
+        let q = "SELECT * from transactions ORDER BY id LIMIT $1 OFFSET $2";
 
         sqlx::query_as(q)
             .bind(limit)
@@ -137,19 +292,94 @@ impl<'q> Model {
     pub async fn sorted_by_date(
         pool: &Pool<Postgres>,
         pagination: &PaginationParams,
+        transaction_type: Option<TransactionType>,
     ) -> Result<Vec<Model>> {
-        let limit = pagination.limit.unwrap_or(50);
+        let limit = pagination.limit.unwrap_or(*DEFAULT_PAGE_LIMIT);
         let offset = pagination.offset.unwrap_or(0);
-        let limit = limit.clamp(1, 1000);
+        let limit = page_limit(limit);
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
 
-        let q = match pagination.exclude_mined {
-            Some(true) => {
-                r#"SELECT * FROM transactions WHERE transaction_type != 'mined' ORDER BY date DESC LIMIT $1 OFFSET $2;"#
-            }
-            _ => r#"SELECT * FROM transactions ORDER BY date DESC LIMIT $1 OFFSET $2;"#,
-        };
+        let q = format!(
+            "SELECT * FROM transactions {} ORDER BY date DESC, id DESC LIMIT $2 OFFSET $3;",
+            list_filter(pagination.exclude_mined)
+        );
+
+        sqlx::query_as(&q)
+            .bind(transaction_type)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(pool)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    /// Whether an identical transfer (same from, to, amount and metadata) was made within the duplicate window.
+    pub async fn is_recent_duplicate<E>(
+        executor: E,
+        data: &TransactionCreateData,
+        window: f64,
+    ) -> Result<bool>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        if window <= 0.0 {
+            return Ok(false);
+        }
+
+        let q = r#"SELECT EXISTS(
+            SELECT 1 FROM transactions
+            WHERE "from" = $1 AND "to" = $2 AND amount = $3 AND metadata IS NOT DISTINCT FROM $4
+                AND transaction_type = 'transfer' AND date > NOW() - make_interval(secs => $5)
+        );"#;
+
+        sqlx::query_scalar(q)
+            .bind(&data.from)
+            .bind(&data.to)
+            .bind(data.amount)
+            // Missing metadata is stored as an empty string
+            .bind(data.metadata.as_deref().unwrap_or_default())
+            .bind(window)
+            .fetch_one(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    /// Stream every transaction touching any of `addresses`, oldest first.
+    /// Rows are read from a cursor so the result set is never buffered as a whole.
+    pub fn stream_by_addresses<'e>(
+        pool: &'e Pool<Postgres>,
+        addresses: &'e [String],
+    ) -> BoxStream<'e, Result<Model>> {
+        let q = r#"SELECT * FROM transactions WHERE "from" = ANY($1) OR "to" = ANY($1) ORDER BY id ASC;"#;
 
         sqlx::query_as(q)
+            .bind(addresses)
+            .fetch(pool)
+            .map_err(DatabaseError::Sqlx)
+            .boxed()
+    }
+
+    /// Fetch the newest transactions whose structured metadata contains `contains`, using jsonb `@>`.
+    pub async fn fetch_by_metadata_json<E>(
+        pool: E,
+        contains: &serde_json::Value,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<Model>>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let limit = page_limit(limit);
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+
+        let q = "SELECT * FROM transactions WHERE metadata_json @> $1 ORDER BY date DESC, id DESC LIMIT $2 OFFSET $3;";
+
+        sqlx::query_as(q)
+            .bind(contains)
             .bind(limit)
             .bind(offset)
             .fetch_all(pool)
@@ -157,6 +387,10 @@ impl<'q> Model {
             .map_err(DatabaseError::Sqlx)
     }
 
+    #[tracing::instrument(
+        skip_all,
+        fields(from = %creation_data.from, to = %creation_data.to, amount = %creation_data.amount)
+    )]
     pub async fn create_no_update<E>(
         executor: E,
         creation_data: TransactionCreateData,
@@ -164,8 +398,9 @@ impl<'q> Model {
     where
         E: 'q + Executor<'q, Database = Postgres>,
     {
-        let metadata = creation_data.metadata.unwrap_or_default();
-        let q = r#"INSERT INTO transactions(amount, "from", "to", metadata, transaction_type, date) VALUES ($1, $2, $3, $4, $5, NOW()) RETURNING *"#;
+        let metadata =
+            sanitize_metadata(creation_data.metadata, *METADATA_SANITIZE_MODE)?.unwrap_or_default();
+        let q = r#"INSERT INTO transactions(amount, "from", "to", metadata, transaction_type, date, metadata_json) VALUES ($1, $2, $3, $4, $5, NOW(), $6) RETURNING *"#;
 
         sqlx::query_as(q)
             .bind(creation_data.amount)
@@ -173,9 +408,11 @@ impl<'q> Model {
             .bind(&creation_data.to)
             .bind(metadata)
             .bind(creation_data.transaction_type)
+            .bind(creation_data.metadata_json)
             .fetch_one(executor)
             .await
             .map_err(DatabaseError::Sqlx)
+            .inspect(|model: &Model| tracing::info!(id = model.id, "Created transaction"))
     }
 
     /// Internal helper that performs the actual transaction creation logic.
@@ -183,15 +420,32 @@ impl<'q> Model {
     /// Does NOT manage transaction lifecycle - works within an existing transaction.
     async fn create_impl(
         tx: &mut sqlx::Transaction<'_, Postgres>,
-        creation_data: TransactionCreateData,
+        mut creation_data: TransactionCreateData,
+        duplicate_window: f64,
+        sanitize_mode: MetadataSanitizeMode,
     ) -> Result<Model> {
-        let metadata = creation_data.metadata.unwrap_or_default();
-
-        let sender = Wallet::fetch_by_address(&mut **tx, &creation_data.from)
+        // Wallets are created on first authentication, so a missing sender is never just an
+        // unused address like a missing recipient can be. The row stays locked until the
+        // transaction ends, which makes the duplicate check below hold for concurrent sends too.
+        let sender = Wallet::fetch_by_address_for_update(&mut **tx, &creation_data.from)
             .await?
             .ok_or_else(|| {
-                DatabaseError::Wallet(WalletError::NotFound(creation_data.from.clone()))
+                DatabaseError::Wallet(WalletError::SenderNotFound(creation_data.from.clone()))
             })?;
+        // Only the sender is checked, locked wallets can still receive.
+        sender.ensure_can_send()?;
+
+        // Checked for duplicates as it will be stored, not as it was sent
+        let metadata =
+            sanitize_metadata(creation_data.metadata.take(), sanitize_mode)?.unwrap_or_default();
+        creation_data.metadata = Some(metadata);
+
+        if creation_data.transaction_type == TransactionType::Transfer
+            && !creation_data.allow_duplicate
+            && Self::is_recent_duplicate(&mut **tx, &creation_data, duplicate_window).await?
+        {
+            return Err(DatabaseError::Transaction(TransactionError::Duplicate));
+        }
 
         let recipient = Wallet::fetch_by_address(&mut **tx, &creation_data.to)
             .await?
@@ -206,17 +460,18 @@ impl<'q> Model {
             .update_balance(&mut **tx, creation_data.amount)
             .await?;
 
-        let q = r#"INSERT INTO transactions(amount, "from", "to", metadata, transaction_type, date, name, sent_metaname, sent_name) VALUES ($1, $2, $3, $4, $5, NOW(), $6, $7, $8) RETURNING *"#;
+        let q = r#"INSERT INTO transactions(amount, "from", "to", metadata, transaction_type, date, name, sent_metaname, sent_name, metadata_json) VALUES ($1, $2, $3, $4, $5, NOW(), $6, $7, $8, $9) RETURNING *"#;
 
         let model = sqlx::query_as(q)
             .bind(creation_data.amount)
             .bind(&creation_data.from)
             .bind(&creation_data.to)
-            .bind(metadata)
+            .bind(creation_data.metadata)
             .bind(creation_data.transaction_type)
             .bind(creation_data.name)
             .bind(creation_data.sent_metaname)
             .bind(creation_data.sent_name)
+            .bind(creation_data.metadata_json)
             .fetch_one(&mut **tx)
             .await?;
 
@@ -226,57 +481,174 @@ impl<'q> Model {
     /// Create a transaction within an existing database transaction.
     /// This does NOT commit - the caller is responsible for transaction management.
     /// Use this when you need to create a transaction as part of a larger atomic operation.
+    #[tracing::instrument(
+        skip_all,
+        fields(from = %creation_data.from, to = %creation_data.to, amount = %creation_data.amount)
+    )]
     pub async fn create_in_transaction(
         tx: &mut sqlx::Transaction<'_, Postgres>,
         creation_data: TransactionCreateData,
     ) -> Result<Model> {
-        Self::create_impl(tx, creation_data).await
+        let model = Self::create_impl(
+            tx,
+            creation_data,
+            *DUPLICATE_TRANSACTION_WINDOW,
+            *METADATA_SANITIZE_MODE,
+        )
+        .await?;
+        // The caller may still roll back, so this isn't worth more than a debug line.
+        tracing::debug!(id = model.id, "Created transaction, pending commit");
+        Ok(model)
     }
 
     /// Create a transaction and manage the database transaction lifecycle.
     /// This starts a new transaction, updates balances, inserts the record, and commits.
     /// Use this when you want a standalone transaction creation.
+    #[tracing::instrument(
+        skip_all,
+        fields(from = %creation_data.from, to = %creation_data.to, amount = %creation_data.amount)
+    )]
     pub async fn create<A>(conn: A, creation_data: TransactionCreateData) -> Result<Model>
     where
         A: Acquire<'q, Database = Postgres>,
     {
         let mut tx = conn.begin().await?;
-        let model = Self::create_impl(&mut tx, creation_data).await?;
+        let model = Self::create_impl(
+            &mut tx,
+            creation_data,
+            *DUPLICATE_TRANSACTION_WINDOW,
+            *METADATA_SANITIZE_MODE,
+        )
+        .await?;
         tx.commit().await?;
+        tracing::info!(id = model.id, "Created transaction");
         Ok(model)
     }
 
     // Implemented both of the "no_mined" functions here rather than simply modifying the existing total count function because I
     // don't want to change an entire trait def
-    pub async fn total_count_no_mined<E>(pool: E, params: &PaginationParams) -> Result<usize>
+    pub async fn total_count_no_mined<E>(
+        pool: E,
+        params: &PaginationParams,
+        transaction_type: Option<TransactionType>,
+    ) -> Result<usize>
     where
         E: 'q + Executor<'q, Database = Postgres>,
     {
-        let q = match params.exclude_mined {
-            Some(true) => r#"SELECT COUNT(*) FROM transactions WHERE transaction_type != 'mined'"#,
-            _ => r#"SELECT COUNT(*) FROM transactions"#,
-        };
-        let result: i64 = sqlx::query_scalar(q).fetch_one(pool).await?;
+        let q = total_count_query(params.exclude_mined);
+        let result: i64 = sqlx::query_scalar(&q)
+            .bind(transaction_type)
+            .fetch_one(pool)
+            .await?;
 
         Ok(result as usize)
     }
 
-    pub async fn fetch_all_no_mined<E>(pool: E, params: &PaginationParams) -> Result<Vec<Self>>
+    pub async fn fetch_all_no_mined<E>(
+        pool: E,
+        params: &PaginationParams,
+        transaction_type: Option<TransactionType>,
+    ) -> Result<Vec<Self>>
     where
         Self: Sized,
         E: 'q + Executor<'q, Database = Postgres>,
     {
-        let limit = params.limit.unwrap_or(50).clamp(1, 1000);
+        let limit = page_limit(params.limit.unwrap_or(*DEFAULT_PAGE_LIMIT));
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+
         let offset = params.offset.unwrap_or(0);
 
-        let q = match params.exclude_mined {
-            Some(true) => {
-                r#"SELECT * from transactions WHERE transaction_type != 'mined' LIMIT $1 OFFSET $2"#
-            }
-            _ => r#"SELECT * from transactions LIMIT $1 OFFSET $2"#,
-        };
+        let q = list_query(params.exclude_mined);
+
+        sqlx::query_as(&q)
+            .bind(transaction_type)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(pool)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    /// Count the transactions sent in either direction between `a` and `b`.
+    pub async fn total_between<E>(pool: E, a: &str, b: &str) -> Result<usize>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = format!("SELECT COUNT(*) FROM transactions WHERE {BETWEEN_FILTER};");
+        let result: i64 = sqlx::query_scalar(&q)
+            .bind(a)
+            .bind(b)
+            .fetch_one(pool)
+            .await?;
+
+        Ok(result as usize)
+    }
+
+    /// Fetch the transactions sent in either direction between `a` and `b`, ordered by date.
+    pub async fn fetch_between<E>(
+        pool: E,
+        a: &str,
+        b: &str,
+        params: &PaginationParams,
+        order: SortOrder,
+    ) -> Result<Vec<Self>>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let limit = page_limit(params.limit.unwrap_or(*DEFAULT_PAGE_LIMIT));
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+
+        let offset = params.offset.unwrap_or(0);
+
+        sqlx::query_as(&between_query(order))
+            .bind(a)
+            .bind(b)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(pool)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    /// Count the transactions whose CommonMeta `ref` is exactly `reference`.
+    pub async fn total_by_ref<E>(pool: E, reference: &str) -> Result<usize>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT COUNT(*) FROM transactions WHERE metadata_ref = $1;";
+        let result: i64 = sqlx::query_scalar(q)
+            .bind(reference)
+            .fetch_one(pool)
+            .await?;
+
+        Ok(result as usize)
+    }
+
+    /// Fetch the newest transactions whose CommonMeta `ref` is exactly `reference`.
+    ///
+    /// `metadata_ref` is a generated column holding what [`common_meta_value`] finds for `ref`.
+    pub async fn fetch_by_ref<E>(
+        pool: E,
+        reference: &str,
+        params: &PaginationParams,
+    ) -> Result<Vec<Self>>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let limit = page_limit(params.limit.unwrap_or(*DEFAULT_PAGE_LIMIT));
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+
+        let offset = params.offset.unwrap_or(0);
+        let q = "SELECT * FROM transactions WHERE metadata_ref = $1 ORDER BY date DESC, id DESC LIMIT $2 OFFSET $3;";
 
         sqlx::query_as(q)
+            .bind(reference)
             .bind(limit)
             .bind(offset)
             .fetch_all(pool)
@@ -285,6 +657,91 @@ impl<'q> Model {
     }
 }
 
+/// Find the value of `key` in CommonMeta formatted metadata (`key=value;key=value;...`).
+///
+/// The first entry wins, and entries without a value are skipped. These are the same rules the
+/// `metadata_ref` column uses for the `ref` key.
+///
+/// # Examples
+/// ```
+/// use kromer::database::transaction::common_meta_value;
+/// assert_eq!(common_meta_value("msg=thanks;ref=order-42", "ref"), Some("order-42"));
+/// assert_eq!(common_meta_value("reference=order-42", "ref"), None);
+/// ```
+pub fn common_meta_value<'a>(metadata: &'a str, key: &str) -> Option<&'a str> {
+    metadata
+        .split(';')
+        .filter_map(|entry| entry.split_once('='))
+        .find(|(entry_key, value)| *entry_key == key && !value.is_empty())
+        .map(|(_, value)| value)
+}
+
+/// Every `key=value` entry in CommonMeta formatted metadata, following the same rules as
+/// [`common_meta_value`]. Entries that are not key/value pairs, like the `meta@name.kro`
+/// recipient, are left out.
+///
+/// # Examples
+/// ```
+/// use kromer::database::transaction::common_meta_entries;
+/// let entries = common_meta_entries("shop@store.kro;ref=order-42;ref=ignored;msg=");
+/// assert_eq!(entries.len(), 1);
+/// assert_eq!(entries.get("ref"), Some(&"order-42"));
+/// ```
+pub fn common_meta_entries(metadata: &str) -> BTreeMap<&str, &str> {
+    let mut entries = BTreeMap::new();
+
+    for (key, value) in metadata
+        .split(';')
+        .filter_map(|entry| entry.split_once('='))
+        .filter(|(key, value)| !key.is_empty() && !value.is_empty())
+    {
+        entries.entry(key).or_insert(value);
+    }
+
+    entries
+}
+
+/// Transactions from `$1` to `$2` or from `$2` to `$1`
+const BETWEEN_FILTER: &str = r#"(("from" = $1 AND "to" = $2) OR ("from" = $2 AND "to" = $1))"#;
+
+fn between_query(order: SortOrder) -> String {
+    // The direction comes from a fixed allow-list, never from user input.
+    format!(
+        "SELECT * FROM transactions WHERE {BETWEEN_FILTER} ORDER BY date {}, id {} LIMIT $3 OFFSET $4;",
+        order.keyword(),
+        order.keyword()
+    )
+}
+
+/// The filter applied to the global transaction listings. The total and the pages share it so
+/// `total` always counts the same rows the pages are taken from.
+///
+/// The type filter is bound as `$1`, a `NULL` type lists every type.
+fn list_filter(exclude_mined: Option<bool>) -> &'static str {
+    match exclude_mined {
+        Some(true) => {
+            "WHERE ($1::transaction_type IS NULL OR transaction_type = $1) AND transaction_type != 'mined'"
+        }
+        _ => "WHERE ($1::transaction_type IS NULL OR transaction_type = $1)",
+    }
+}
+
+/// Postgres returns rows in whatever order is cheapest without an `ORDER BY`, which can repeat or
+/// skip rows between pages.
+fn list_query(exclude_mined: Option<bool>) -> String {
+    format!(
+        "SELECT * from transactions {} ORDER BY id LIMIT $2 OFFSET $3",
+        list_filter(exclude_mined)
+    )
+}
+
+fn total_count_query(exclude_mined: Option<bool>) -> String {
+    format!(
+        "SELECT COUNT(*) FROM transactions {}",
+        list_filter(exclude_mined)
+    )
+}
+
 impl TransactionNameData {
     /// Parse a transaction name from a string-like type according to CommonMeta format.
     /// Takes any type that can be converted to a string reference.
@@ -382,3 +839,421 @@ impl TransactionNameData {
         metaname_ref.map(|metaname| metaname.as_str())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::dec;
+
+    use super::*;
+
+    async fn send(pool: &sqlx::PgPool, data: TransactionCreateData) -> Result<Model> {
+        send_with(pool, data, MetadataSanitizeMode::Off).await
+    }
+
+    async fn send_with(
+        pool: &sqlx::PgPool,
+        data: TransactionCreateData,
+        sanitize_mode: MetadataSanitizeMode,
+    ) -> Result<Model> {
+        let mut tx = pool.begin().await?;
+        let model = Model::create_impl(&mut tx, data, 60.0, sanitize_mode).await?;
+        tx.commit().await?;
+        Ok(model)
+    }
+
+    #[sqlx::test]
+    async fn test_duplicate_transfers(pool: sqlx::PgPool) {
+        Wallet::create_wallet(&pool, "kalice0000", "alice", Some(dec!(100)))
+            .await
+            .unwrap();
+        Wallet::create_wallet(&pool, "kbob000000", "bob", None)
+            .await
+            .unwrap();
+        let transfer = |allow_duplicate| TransactionCreateData {
+            from: "kalice0000".into(),
+            to: "kbob000000".into(),
+            amount: dec!(5.00),
+            metadata: Some("order=42".into()),
+            transaction_type: TransactionType::Transfer,
+            allow_duplicate,
+            ..Default::default()
+        };
+
+        // The first send holds the sender's row, so an identical one sent meanwhile has to wait
+        // for it and then sees it as a duplicate.
+        let mut first = pool.begin().await.unwrap();
+        Model::create_impl(&mut first, transfer(false), 60.0, MetadataSanitizeMode::Off)
+            .await
+            .expect("first send should go through");
+
+        let second = tokio::spawn({
+            let pool = pool.clone();
+            let data = transfer(false);
+            async move { send(&pool, data).await }
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        assert!(
+            !second.is_finished(),
+            "second send did not wait for the first"
+        );
+
+        first.commit().await.unwrap();
+        let second = second.await.unwrap();
+        assert!(
+            matches!(
+                second,
+                Err(DatabaseError::Transaction(TransactionError::Duplicate))
+            ),
+            "{second:?}"
+        );
+
+        send(&pool, transfer(true))
+            .await
+            .expect("allowDuplicate should skip the check");
+
+        // Sends without metadata are duplicates of each other too
+        let bare = TransactionCreateData {
+            metadata: None,
+            ..transfer(false)
+        };
+        send(&pool, bare.clone())
+            .await
+            .expect("first send without metadata should go through");
+        let second = send(&pool, bare).await;
+        assert!(
+            matches!(
+                second,
+                Err(DatabaseError::Transaction(TransactionError::Duplicate))
+            ),
+            "{second:?}"
+        );
+
+        let alice = Wallet::fetch_by_address(&pool, "kalice0000")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(alice.balance, dec!(85.00));
+    }
+
+    #[sqlx::test]
+    async fn test_duplicates_compare_sanitized_metadata(pool: sqlx::PgPool) {
+        Wallet::create_wallet(&pool, "kalice0000", "alice", Some(dec!(100)))
+            .await
+            .unwrap();
+        Wallet::create_wallet(&pool, "kbob000000", "bob", None)
+            .await
+            .unwrap();
+        let transfer = |metadata: &str| TransactionCreateData {
+            from: "kalice0000".into(),
+            to: "kbob000000".into(),
+            amount: dec!(5.00),
+            metadata: Some(metadata.into()),
+            transaction_type: TransactionType::Transfer,
+            ..Default::default()
+        };
+
+        let first = send_with(&pool, transfer("order  42"), MetadataSanitizeMode::Sanitize)
+            .await
+            .unwrap();
+        assert_eq!(first.metadata.as_deref(), Some("order 42"));
+
+        // Sent differently, but stored the same
+        let second = send_with(
+            &pool,
+            transfer("order 42\t"),
+            MetadataSanitizeMode::Sanitize,
+        )
+        .await;
+        assert!(
+            matches!(
+                second,
+                Err(DatabaseError::Transaction(TransactionError::Duplicate))
+            ),
+            "{second:?}"
+        );
+    }
+
+    #[derive(Clone, Default)]
+    struct CapturedLogs(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_create_span_fields() {
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_span_events(tracing_subscriber::fmt::format::FmtSpan::NEW)
+            .with_max_level(tracing::Level::DEBUG)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        // Nothing listens here, the span is opened before the insert fails.
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .acquire_timeout(std::time::Duration::from_millis(100))
+            .connect_lazy("postgres://127.0.0.1:1/kromer")
+            .expect("lazy pool should not connect");
+        let creation_data = TransactionCreateData {
+            from: "kre3w0i79j".into(),
+            to: "kqxhx5yn9v".into(),
+            amount: dec!(12.5),
+            metadata: Some("shop.kro;note=hunter2".into()),
+            ..Default::default()
+        };
+        assert!(Model::create_no_update(&pool, creation_data).await.is_err());
+        assert!(Wallet::verify_address(&pool, "hunter2-key").await.is_err());
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert!(logs.contains("from=kre3w0i79j"), "{logs}");
+        assert!(logs.contains("to=kqxhx5yn9v"), "{logs}");
+        assert!(logs.contains("amount=12.5"), "{logs}");
+        // Only the chosen fields are recorded, metadata can carry anything the sender typed
+        // and the private key never is
+        assert!(!logs.contains("hunter2"), "{logs}");
+        assert!(logs.contains("verify_address"), "{logs}");
+    }
+
+    #[test]
+    fn test_common_meta_ref() {
+        let metadata = "kre3w0i79j;ref=order-42;msg=thanks";
+        assert_eq!(common_meta_value(metadata, "ref"), Some("order-42"));
+        assert_eq!(
+            common_meta_value("ref=order-42=b", "ref"),
+            Some("order-42=b")
+        );
+        assert_eq!(
+            common_meta_value("ref=;ref=order-43", "ref"),
+            Some("order-43")
+        );
+
+        // Only an exact key counts
+        assert_eq!(common_meta_value("reference=order-42", "ref"), None);
+        assert_eq!(common_meta_value("msg=ref=order-42", "ref"), None);
+        assert_eq!(common_meta_value("msg=thanks", "ref"), None);
+        assert_eq!(common_meta_value("", "ref"), None);
+    }
+
+    #[test]
+    fn test_sanitize_metadata() {
+        let metadata = || Some("to=shop;\n\tmsg=hi\u{7}  there ".to_owned());
+
+        assert_eq!(
+            sanitize_metadata(metadata(), MetadataSanitizeMode::Sanitize).unwrap(),
+            Some("to=shop; msg=hi there".to_owned())
+        );
+        assert!(matches!(
+            sanitize_metadata(metadata(), MetadataSanitizeMode::Strict),
+            Err(TransactionError::InvalidMetadata)
+        ));
+        assert_eq!(
+            sanitize_metadata(metadata(), MetadataSanitizeMode::Off).unwrap(),
+            metadata()
+        );
+
+        // Clean metadata passes strict mode untouched
+        let clean = Some("to=shop;msg=hi there".to_owned());
+        assert_eq!(
+            sanitize_metadata(clean.clone(), MetadataSanitizeMode::Strict).unwrap(),
+            clean
+        );
+    }
+
+    #[test]
+    fn test_between_query_is_symmetric() {
+        let q = between_query(SortOrder::Desc);
+
+        assert!(q.contains(r#"("from" = $1 AND "to" = $2)"#));
+        assert!(q.contains(r#"("from" = $2 AND "to" = $1)"#));
+        assert!(q.contains("ORDER BY date DESC"));
+        assert!(between_query(SortOrder::Asc).contains("ORDER BY date ASC"));
+    }
+
+    #[sqlx::test]
+    async fn test_total_honours_exclude_mined(pool: sqlx::PgPool) {
+        for transaction_type in [
+            TransactionType::Mined,
+            TransactionType::Mined,
+            TransactionType::Transfer,
+            TransactionType::NamePurchase,
+        ] {
+            let data = TransactionCreateData {
+                from: "serverwelf".into(),
+                to: "kalice0000".into(),
+                amount: dec!(1.00),
+                transaction_type,
+                ..Default::default()
+            };
+            Model::create_no_update(&pool, data).await.unwrap();
+        }
+
+        for (exclude_mined, expected) in [(None, 4), (Some(false), 4), (Some(true), 2)] {
+            let params = PaginationParams {
+                exclude_mined,
+                ..Default::default()
+            };
+            let total = Model::total_count_no_mined(&pool, &params, None)
+                .await
+                .unwrap();
+            let page = Model::fetch_all_no_mined(&pool, &params, None)
+                .await
+                .unwrap();
+
+            assert_eq!(total, expected, "{exclude_mined:?}");
+            assert_eq!(page.len(), total, "{exclude_mined:?}");
+            if exclude_mined == Some(true) {
+                assert!(
+                    page.iter()
+                        .all(|transaction| transaction.transaction_type != TransactionType::Mined)
+                );
+            }
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_list_pages_do_not_overlap(pool: sqlx::PgPool) {
+        Wallet::create_wallet(&pool, "kalice0000", "alice", Some(dec!(100)))
+            .await
+            .unwrap();
+        // All in one transaction, so they share a date and only the id tells them apart
+        let mut tx = pool.begin().await.unwrap();
+        for _ in 0..7 {
+            let data = TransactionCreateData {
+                from: "kalice0000".into(),
+                to: "kbob000000".into(),
+                amount: dec!(1.00),
+                transaction_type: TransactionType::Transfer,
+                ..Default::default()
+            };
+            Model::create_no_update(&mut *tx, data).await.unwrap();
+        }
+        tx.commit().await.unwrap();
+
+        let (mut listed, mut latest) = (Vec::new(), Vec::new());
+        for offset in (0..7).step_by(2) {
+            let params = PaginationParams {
+                limit: Some(2),
+                offset: Some(offset),
+                ..Default::default()
+            };
+            let page = Model::fetch_all_no_mined(&pool, &params, None)
+                .await
+                .unwrap();
+            listed.extend(page.into_iter().map(|t| t.id));
+            let page = Model::sorted_by_date(&pool, &params, None).await.unwrap();
+            latest.extend(page.into_iter().map(|t| t.id));
+        }
+
+        for ids in [&listed, &latest] {
+            let mut unique = ids.clone();
+            unique.sort();
+            unique.dedup();
+            assert_eq!(unique.len(), 7, "a row appeared on two pages: {ids:?}");
+        }
+        assert!(listed.is_sorted());
+        assert!(latest.iter().rev().is_sorted());
+    }
+
+    #[sqlx::test]
+    async fn test_type_filter(pool: sqlx::PgPool) {
+        Wallet::create_wallet(&pool, "kalice0000", "alice", Some(dec!(100)))
+            .await
+            .unwrap();
+        for (to, transaction_type) in [
+            ("kbob000000", TransactionType::Transfer),
+            ("kbob000000", TransactionType::Transfer),
+            ("name", TransactionType::NamePurchase),
+            ("kalice0000", TransactionType::Mined),
+        ] {
+            let data = TransactionCreateData {
+                from: "kalice0000".into(),
+                to: to.into(),
+                amount: dec!(1.00),
+                transaction_type,
+                ..Default::default()
+            };
+            Model::create_no_update(&pool, data).await.unwrap();
+        }
+        let params = PaginationParams::default();
+
+        let transfers = Model::fetch_all_no_mined(&pool, &params, Some(TransactionType::Transfer))
+            .await
+            .unwrap();
+        assert_eq!(transfers.len(), 2);
+        assert!(
+            transfers
+                .iter()
+                .all(|t| t.transaction_type == TransactionType::Transfer)
+        );
+        let total = Model::total_count_no_mined(&pool, &params, Some(TransactionType::Transfer))
+            .await
+            .unwrap();
+        assert_eq!(total, 2);
+
+        let latest = Model::sorted_by_date(&pool, &params, Some(TransactionType::NamePurchase))
+            .await
+            .unwrap();
+        assert_eq!(latest.len(), 1);
+        assert_eq!(latest[0].transaction_type, TransactionType::NamePurchase);
+
+        let all = Model::fetch_all_no_mined(&pool, &params, None)
+            .await
+            .unwrap();
+        assert_eq!(all.len(), 4);
+        let no_mined = PaginationParams {
+            exclude_mined: Some(true),
+            ..Default::default()
+        };
+        let total = Model::total_count_no_mined(&pool, &no_mined, None)
+            .await
+            .unwrap();
+        assert_eq!(total, 3);
+        let mined = Model::total_count_no_mined(&pool, &no_mined, Some(TransactionType::Mined))
+            .await
+            .unwrap();
+        assert_eq!(mined, 0);
+    }
+
+    #[test]
+    fn test_amount_precision() {
+        assert_eq!(normalize_amount(dec!(1.25)).unwrap().to_string(), "1.25");
+        assert_eq!(normalize_amount(dec!(1.500)).unwrap().to_string(), "1.50");
+        assert_eq!(normalize_amount(dec!(7)).unwrap().to_string(), "7.00");
+
+        assert!(matches!(
+            normalize_amount(dec!(1.255)),
+            Err(TransactionError::TooPrecise(AMOUNT_SCALE))
+        ));
+        assert!(normalize_amount(dec!(0.000000001)).is_err());
+    }
+
+    #[test]
+    fn test_transfer_limits() {
+        let max = Some(dec!(1000));
+
+        assert!(check_transfer_limits(dec!(1000), None, max).is_ok());
+        assert!(matches!(
+            check_transfer_limits(dec!(1000.01), None, max),
+            Err(TransactionError::TooLarge(_))
+        ));
+        assert!(check_transfer_limits(dec!(1000000), None, None).is_ok());
+
+        let longest = "é".repeat(MAX_METADATA_LENGTH);
+        let too_long = "a".repeat(MAX_METADATA_LENGTH + 1);
+        assert!(check_transfer_limits(dec!(1), Some(&longest), None).is_ok());
+        assert!(matches!(
+            check_transfer_limits(dec!(1), Some(&too_long), None),
+            Err(TransactionError::MetadataTooLong(MAX_METADATA_LENGTH))
+        ));
+    }
+}
@@ -5,7 +5,10 @@ use sqlx::{Acquire, Encode, Executor, Postgres, Type};
 
 use crate::database::{DatabaseError, ModelExt, Result, name, transaction};
 use crate::errors::KromerError;
-use crate::routes::PaginationParams;
+use crate::errors::wallet::WalletError;
+use crate::models::krist::addresses::AddressOrderBy;
+use crate::models::krist::transactions::{TransactionDirection, TransactionOrderBy};
+use crate::routes::{DEFAULT_PAGE_LIMIT, PaginationParams, SortOrder, page_limit};
 use crate::utils::crypto;
 
 #[derive(Debug, Clone, PartialEq, sqlx::FromRow, serde::Serialize)]
@@ -52,8 +55,12 @@ impl<'q> ModelExt<'q> for Model {
         Self: Sized,
         E: 'q + Executor<'q, Database = Postgres>,
     {
-        let limit = limit.clamp(1, 1000);
-        let q = "SELECT * from wallets LIMIT $1 OFFSET $2";
+        let limit = page_limit(limit);
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+
+        let q = "SELECT * from wallets ORDER BY id LIMIT $1 OFFSET $2";
 
         sqlx::query_as(q)
             .bind(limit)
@@ -90,13 +97,74 @@ impl<'q> Model {
             .map_err(DatabaseError::Sqlx)
     }
 
+    /// Fetch a wallet and lock its row until the surrounding transaction ends, so concurrent
+    /// transfers from the same address run one after the other.
+    pub async fn fetch_by_address_for_update<S, E>(pool: E, address: S) -> Result<Option<Self>>
+    where
+        S: AsRef<str>,
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT * FROM wallets WHERE address = $1 FOR UPDATE;";
+        sqlx::query_as(q)
+            .bind(address.as_ref())
+            .fetch_optional(pool)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    /// Fetch a page of wallets sorted by one of the allowed columns.
+    pub async fn fetch_all_ordered<E>(
+        pool: E,
+        limit: i64,
+        offset: i64,
+        order_by: AddressOrderBy,
+        order: SortOrder,
+    ) -> Result<Vec<Self>>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let limit = page_limit(limit);
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+
+        // Both the column and direction come from fixed allow-lists, never from user input.
+        let q = format!(
+            "SELECT * FROM wallets ORDER BY {} {}, id ASC LIMIT $1 OFFSET $2;",
+            order_by.column(),
+            order.keyword()
+        );
+        sqlx::query_as(&q)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(pool)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    /// The sum of every balance outside of `welfare_address`, which holds the money that was never minted.
+    pub async fn money_supply<E>(pool: E, welfare_address: &str) -> Result<Decimal>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT COALESCE(SUM(balance), 0) FROM wallets WHERE address != $1;";
+        sqlx::query_scalar(q)
+            .bind(welfare_address)
+            .fetch_one(pool)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
     pub async fn fetch_richest<E>(pool: E, limit: i64, offset: i64) -> Result<Vec<Self>>
     where
         E: 'q + Executor<'q, Database = Postgres>,
     {
-        let limit = limit.clamp(1, 1000);
+        let limit = page_limit(limit);
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
 
-        let q = "SELECT * FROM wallets ORDER BY balance DESC LIMIT $1 OFFSET $2;";
+        let q = "SELECT * FROM wallets ORDER BY balance DESC, id ASC LIMIT $1 OFFSET $2;";
         sqlx::query_as(q)
             .bind(limit)
             .bind(offset)
@@ -105,7 +173,7 @@ impl<'q> Model {
             .map_err(DatabaseError::Sqlx)
     }
 
-    #[tracing::instrument(skip(pool))]
+    #[tracing::instrument(skip(pool, private_key))]
     pub async fn verify_address<A, S>(pool: A, private_key: S) -> Result<VerifyResponse>
     where
         S: AsRef<str> + std::fmt::Debug,
@@ -123,12 +191,10 @@ impl<'q> Model {
             let mut tx = pool.acquire().await?;
             let result = Model::fetch_by_address(&mut *tx, &address).await?;
 
-            let wallet = match result {
+            match result {
                 Some(w) => w,
                 None => Self::create_wallet(&mut *tx, &address, &hash, None).await?,
-            };
-
-            wallet
+            }
         };
 
         let pkey = &wallet.private_key;
@@ -144,6 +210,28 @@ impl<'q> Model {
         })
     }
 
+    /// Make sure this wallet is allowed to send money, locked wallets can still receive.
+    pub fn ensure_can_send(&self) -> Result<(), WalletError> {
+        match self.locked {
+            true => Err(WalletError::Locked(self.address.clone())),
+            false => Ok(()),
+        }
+    }
+
+    /// Lock or unlock this wallet, returning the updated model
+    pub async fn set_locked<E>(&self, executor: E, locked: bool) -> sqlx::Result<Model>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "UPDATE wallets SET locked = $1 WHERE address = $2 RETURNING *";
+
+        sqlx::query_as(q)
+            .bind(locked)
+            .bind(&self.address)
+            .fetch_one(executor)
+            .await
+    }
+
     pub async fn create_wallet<E>(
         pool: E,
         address: &str,
@@ -166,18 +254,18 @@ impl<'q> Model {
             .await
     }
 
-    pub async fn total_transactions<E>(&self, executor: E, exclude_mined: bool) -> sqlx::Result<i64>
+    pub async fn total_transactions<E>(
+        &self,
+        executor: E,
+        exclude_mined: bool,
+        direction: TransactionDirection,
+    ) -> sqlx::Result<i64>
     where
         E: 'q + Executor<'q, Database = Postgres>,
     {
-        let q = match exclude_mined {
-            true => {
-                r#"SELECT COUNT(*) FROM transactions WHERE ("from" = $1 OR "to" = $1) AND transaction_type != 'mined';"#
-            }
-            false => r#"SELECT COUNT(*) FROM transactions  WHERE "from" = $1 OR "to" = $1;"#,
-        };
+        let q = total_transactions_query(exclude_mined, direction);
 
-        sqlx::query_scalar(q)
+        sqlx::query_scalar(&q)
             .bind(&self.address)
             .fetch_one(executor)
             .await
@@ -187,23 +275,28 @@ impl<'q> Model {
         &self,
         pool: E,
         query: &PaginationParams,
+        order_by: TransactionOrderBy,
+        order: SortOrder,
+        direction: TransactionDirection,
     ) -> sqlx::Result<Vec<transaction::Model>>
     where
         E: 'q + Executor<'q, Database = Postgres>,
     {
-        let limit = query.limit.unwrap_or(50).clamp(1, 1000);
+        let limit = page_limit(query.limit.unwrap_or(*DEFAULT_PAGE_LIMIT));
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+
         let offset = query.offset.unwrap_or(0);
 
-        let q = match query.exclude_mined {
-            Some(true) => {
-                r#"SELECT * FROM transactions WHERE ("from" = $1 OR "to" = $1) AND transaction_type != 'mined' ORDER BY date DESC LIMIT $2 OFFSET $3;"#
-            }
-            _ => {
-                r#"SELECT * FROM transactions WHERE "from" = $1 OR "to" = $1 ORDER BY date DESC LIMIT $2 OFFSET $3;"#
-            }
-        };
+        let q = transactions_query(
+            query.exclude_mined.unwrap_or(false),
+            direction,
+            order_by,
+            order,
+        );
 
-        sqlx::query_as(q)
+        sqlx::query_as(&q)
             .bind(&self.address)
             .bind(limit)
             .bind(offset)
@@ -219,9 +312,12 @@ impl<'q> Model {
     where
         E: 'q + Executor<'q, Database = Postgres>,
     {
-        let limit = query.limit.unwrap_or(50);
+        let limit = query.limit.unwrap_or(*DEFAULT_PAGE_LIMIT);
         let offset = query.offset.unwrap_or(0);
-        let limit = limit.clamp(1, 1000);
+        let limit = page_limit(limit);
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
 
         let q = r#"SELECT * FROM names WHERE owner = $1 ORDER BY name ASC LIMIT $2 OFFSET $3;"#;
         sqlx::query_as(q)
@@ -267,7 +363,7 @@ impl<'q> Model {
         SET
             balance = balance + $1,
             total_in = total_in + CASE WHEN $1 > 0 THEN $1 ELSE 0 END,
-            total_out = abs(total_out) + CASE WHEN $1 < 0 THEN abs($1) ELSE 0 END
+            total_out = total_out + CASE WHEN $1 < 0 THEN -$1 ELSE 0 END
         WHERE address = $2
         RETURNING *;
         "#;
@@ -336,3 +432,198 @@ impl<'q> Model {
             .map_err(DatabaseError::Sqlx)
     }
 }
+
+/// The filter for an address's transactions, used by both [`total_transactions_query`] and
+/// [`transactions_query`].
+fn address_transactions_filter(exclude_mined: bool, direction: TransactionDirection) -> String {
+    match exclude_mined {
+        true => format!("{} AND transaction_type != 'mined'", direction.filter()),
+        false => direction.filter().to_owned(),
+    }
+}
+
+fn total_transactions_query(exclude_mined: bool, direction: TransactionDirection) -> String {
+    format!(
+        "SELECT COUNT(*) FROM transactions WHERE {};",
+        address_transactions_filter(exclude_mined, direction)
+    )
+}
+
+fn transactions_query(
+    exclude_mined: bool,
+    direction: TransactionDirection,
+    order_by: TransactionOrderBy,
+    order: SortOrder,
+) -> String {
+    // Both the column and direction come from fixed allow-lists, never from user input.
+    format!(
+        "SELECT * FROM transactions WHERE {} ORDER BY {} {}, id {} LIMIT $2 OFFSET $3;",
+        address_transactions_filter(exclude_mined, direction),
+        order_by.column(),
+        order.keyword(),
+        order.keyword()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::transaction::{TransactionCreateData, TransactionType};
+
+    fn wallet(locked: bool) -> Model {
+        Model {
+            id: 1,
+            address: "kre3w0i79j".into(),
+            balance: dec!(100),
+            created_at: Utc::now(),
+            locked,
+            total_in: dec!(0),
+            total_out: dec!(0),
+            private_key: None,
+            names: None,
+        }
+    }
+
+    #[test]
+    fn test_locked_wallet_cannot_send() {
+        assert!(wallet(false).ensure_can_send().is_ok());
+        assert!(matches!(
+            wallet(true).ensure_can_send(),
+            Err(WalletError::Locked(address)) if address == "kre3w0i79j"
+        ));
+    }
+
+    #[sqlx::test]
+    async fn test_transaction_count_matches_page(pool: sqlx::PgPool) {
+        use TransactionDirection::{All, In, Out};
+
+        let alice = Model::create_wallet(&pool, "kalice0000", "alice", None)
+            .await
+            .unwrap();
+        for (from, to, transaction_type) in [
+            ("serverwelf", "kalice0000", TransactionType::Mined),
+            ("kalice0000", "kbob000000", TransactionType::Transfer),
+            ("kbob000000", "kalice0000", TransactionType::Transfer),
+            ("kbob000000", "kcarol0000", TransactionType::Transfer),
+        ] {
+            let data = TransactionCreateData {
+                from: from.into(),
+                to: to.into(),
+                amount: dec!(1.00),
+                transaction_type,
+                ..Default::default()
+            };
+            transaction::Model::create_no_update(&pool, data)
+                .await
+                .unwrap();
+        }
+
+        for (exclude_mined, direction, expected) in [
+            (false, In, 2),
+            (true, In, 1),
+            (false, Out, 1),
+            (true, Out, 1),
+            (false, All, 3),
+            (true, All, 2),
+        ] {
+            let total = alice
+                .total_transactions(&pool, exclude_mined, direction)
+                .await
+                .unwrap();
+            let params = PaginationParams {
+                exclude_mined: Some(exclude_mined),
+                ..Default::default()
+            };
+            let page = alice
+                .transactions(
+                    &pool,
+                    &params,
+                    TransactionOrderBy::Time,
+                    SortOrder::Desc,
+                    direction,
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(total, expected, "{exclude_mined} {direction:?}");
+            assert_eq!(page.len() as i64, total, "{exclude_mined} {direction:?}");
+            assert!(
+                page.iter().all(|transaction| match direction {
+                    In => transaction.to == "kalice0000",
+                    Out => transaction.from.as_deref() == Some("kalice0000"),
+                    All => true,
+                }),
+                "{direction:?} listed a transaction in the other direction"
+            );
+        }
+    }
+
+    #[test]
+    fn test_transactions_order() {
+        let query = |exclude_mined, order_by, order| {
+            transactions_query(exclude_mined, TransactionDirection::All, order_by, order)
+        };
+
+        let newest_first = query(false, TransactionOrderBy::Time, SortOrder::Desc);
+        assert!(newest_first.contains("ORDER BY date DESC, id DESC"));
+
+        let oldest_first = query(false, TransactionOrderBy::Time, SortOrder::Asc);
+        assert!(oldest_first.contains("ORDER BY date ASC, id ASC"));
+
+        let by_value = query(true, TransactionOrderBy::Value, SortOrder::Desc);
+        assert!(by_value.contains("ORDER BY amount DESC"));
+        assert!(by_value.contains("'mined'"));
+    }
+
+    #[sqlx::test]
+    async fn test_richest_pages_do_not_overlap(pool: sqlx::PgPool) {
+        // Mostly tied balances, which is where an order on balance alone falls apart
+        for i in 0..9 {
+            let balance = if i == 4 { dec!(50) } else { dec!(10) };
+            Model::create_wallet(&pool, &format!("krich0000{i}"), "hash", Some(balance))
+                .await
+                .unwrap();
+        }
+
+        let mut seen = Vec::new();
+        for offset in (0..).step_by(2) {
+            let page = Model::fetch_richest(&pool, 2, offset).await.unwrap();
+            if page.is_empty() {
+                break;
+            }
+            seen.extend(page.into_iter().map(|wallet| wallet.address));
+        }
+        // The migrations' serverwelf wallet is listed last, with nothing in it
+        assert_eq!(seen.pop().as_deref(), Some("serverwelf"));
+
+        assert_eq!(seen[0], "krich00004");
+        let mut unique = seen.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), 9, "a wallet appeared on two pages: {seen:?}");
+    }
+
+    #[sqlx::test]
+    async fn test_many_renewals_keep_exact_totals(pool: sqlx::PgPool) {
+        let wallet = Model::create_wallet(&pool, "kshop00000", "shop", None)
+            .await
+            .unwrap();
+
+        // A year of daily renewals at a price that isn't exact in binary, topped up every week
+        for day in 0..365 {
+            if day % 7 == 0 {
+                wallet.update_balance(&pool, dec!(2.31)).await.unwrap();
+            }
+            wallet.update_balance(&pool, dec!(-0.33)).await.unwrap();
+        }
+
+        let wallet = Model::fetch_by_address(&pool, "kshop00000")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(wallet.total_out, dec!(120.45));
+        assert_eq!(wallet.total_in, dec!(122.43));
+        assert_eq!(wallet.balance, dec!(1.98));
+        assert_eq!(wallet.balance, wallet.total_in - wallet.total_out);
+    }
+}
@@ -0,0 +1,47 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, Postgres};
+
+use crate::database::{DatabaseError, Result};
+
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct Model {
+    pub id: i32,
+    pub url: String,
+    /// The events the webhook is sent, see [`crate::webhooks::WEBHOOK_EVENTS`]
+    pub events: Vec<String>,
+    /// Key for the HMAC-SHA256 signature sent with every delivery
+    pub secret: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl<'q> Model {
+    /// Register a webhook for `events`.
+    pub async fn create<E>(pool: E, url: &str, events: &[String], secret: &str) -> Result<Model>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "INSERT INTO webhooks(url, events, secret, created_at) VALUES ($1, $2, $3, NOW()) RETURNING *";
+
+        sqlx::query_as(q)
+            .bind(url)
+            .bind(events)
+            .bind(secret)
+            .fetch_one(pool)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    /// Fetch every webhook registered for `event`.
+    pub async fn fetch_by_event<E>(pool: E, event: &str) -> Result<Vec<Model>>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT * FROM webhooks WHERE $1 = ANY(events) ORDER BY id ASC";
+
+        sqlx::query_as(q)
+            .bind(event)
+            .fetch_all(pool)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+}
@@ -0,0 +1,142 @@
+use actix_web::error::JsonPayloadError;
+
+/// What was wrong with a JSON body that didn't deserialize, for telling the client which field to fix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonFieldError {
+    /// The offending field. Plain serde only names it for missing and unknown fields, a
+    /// deserialize that tracks its path names it for bad values too.
+    pub field: Option<String>,
+    /// Whether the field was left out, rather than present with a bad value
+    pub missing: bool,
+    /// serde's description of the problem, including where in the body it is
+    pub detail: String,
+}
+
+impl JsonFieldError {
+    /// Only deserialize errors are about fields, `None` for anything else like an oversized body.
+    pub fn from_payload(err: &JsonPayloadError) -> Option<Self> {
+        let JsonPayloadError::Deserialize(err) = err else {
+            return None;
+        };
+
+        Some(Self::from_serde(err))
+    }
+
+    /// Name the field from the path serde was at when it failed. A missing or unknown field is
+    /// reported from the object holding it, so its name is joined onto that path.
+    pub fn from_path_error(err: &serde_path_to_error::Error<serde_json::Error>) -> Self {
+        let mut field_error = Self::from_serde(err.inner());
+        let path = err.path().to_string();
+
+        field_error.field = match (field_error.field.take(), path.as_str()) {
+            (field, ".") => field,
+            (Some(field), path) => Some(format!("{path}.{field}")),
+            (None, path) => Some(path.to_owned()),
+        };
+
+        field_error
+    }
+
+    fn from_serde(err: &serde_json::Error) -> Self {
+        let detail = err.to_string();
+        let missing = detail
+            .strip_prefix("missing field `")
+            .and_then(|rest| rest.split_once('`'));
+        let unknown = detail
+            .strip_prefix("unknown field `")
+            .and_then(|rest| rest.split_once('`'));
+
+        let (field, missing) = match (missing, unknown) {
+            (Some((field, _)), _) => (Some(field.to_owned()), true),
+            (None, Some((field, _))) => (Some(field.to_owned()), false),
+            (None, None) => (None, false),
+        };
+
+        Self {
+            field,
+            missing,
+            detail,
+        }
+    }
+
+    /// A short message naming the field, in the same words as the Krist parameter errors.
+    pub fn message(&self) -> String {
+        match (&self.field, self.missing) {
+            (Some(field), true) => format!("Missing parameter {field}"),
+            (Some(field), false) => format!("Invalid parameter {field}"),
+            (None, _) => "Invalid request body".to_owned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field_error(body: &str) -> JsonFieldError {
+        #[derive(Debug, serde::Deserialize)]
+        #[serde(deny_unknown_fields)]
+        #[allow(dead_code)]
+        struct Body {
+            privatekey: String,
+            amount: f64,
+        }
+
+        let err = serde_json::from_str::<Body>(body).expect_err("body should not deserialize");
+        JsonFieldError::from_payload(&JsonPayloadError::Deserialize(err))
+            .expect("deserialize errors are field errors")
+    }
+
+    #[test]
+    fn test_json_field_error() {
+        let err = field_error(r#"{"amount": 5}"#);
+        assert_eq!(err.field.as_deref(), Some("privatekey"));
+        assert!(err.missing);
+
+        let err = field_error(r#"{"privatekey": "a", "amount": 5, "to": "b"}"#);
+        assert_eq!(err.field.as_deref(), Some("to"));
+        assert!(!err.missing);
+
+        let err = field_error(r#"{"privatekey": "a", "amount": "lots"}"#);
+        assert_eq!(err.field, None);
+        assert!(err.detail.contains("invalid type"));
+
+        assert_eq!(
+            JsonFieldError::from_payload(&JsonPayloadError::ContentType),
+            None
+        );
+    }
+
+    #[test]
+    fn test_json_field_error_path() {
+        #[derive(Debug, serde::Deserialize)]
+        #[allow(dead_code)]
+        struct Inner {
+            amount: f64,
+        }
+        #[derive(Debug, serde::Deserialize)]
+        #[allow(dead_code)]
+        struct Body {
+            amount: f64,
+            inner: Option<Inner>,
+        }
+        let field_error = |body: &str| {
+            let de = &mut serde_json::Deserializer::from_str(body);
+            let err = serde_path_to_error::deserialize::<_, Body>(de)
+                .expect_err("body should not deserialize");
+            JsonFieldError::from_path_error(&err)
+        };
+
+        let err = field_error(r#"{"amount": "lots"}"#);
+        assert_eq!(err.field.as_deref(), Some("amount"));
+        assert!(!err.missing);
+
+        let err = field_error(r#"{"inner": {"amount": 5}}"#);
+        assert_eq!(err.field.as_deref(), Some("amount"));
+        assert!(err.missing);
+
+        let err = field_error(r#"{"amount": 5, "inner": {}}"#);
+        assert_eq!(err.field.as_deref(), Some("inner.amount"));
+        assert!(err.missing);
+    }
+}
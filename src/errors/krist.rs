@@ -1,5 +1,6 @@
 //! Responses and error types for the krist api routes
 pub mod address;
+pub mod catalog;
 pub mod generic;
 pub mod name;
 pub mod transaction;
@@ -12,6 +13,10 @@ use actix_web::{
 };
 use serde::{Deserialize, Serialize};
 
+use catalog::KristErrorCode;
+
+use super::json::JsonFieldError;
+
 #[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct KristErrorResponse {
     pub ok: bool,
@@ -19,6 +24,9 @@ pub struct KristErrorResponse {
     pub message: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub info: Option<String>,
+    /// The offending parameter for `invalid_parameter` and `missing_parameter` errors
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parameter: Option<String>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -39,7 +47,7 @@ pub enum KristError {
     WebSocket(#[from] websockets::WebSocketError),
 
     #[error(transparent)]
-    Database(#[from] sqlx::Error), // Do we really want to expose all of this to the client?
+    Database(#[from] sqlx::Error),
 
     #[error(transparent)]
     JsonPayload(#[from] JsonPayloadError),
@@ -47,27 +55,57 @@ pub enum KristError {
     #[error(transparent)]
     Path(#[from] PathError),
 
+    /// A JSON body that parsed but didn't fit the request type
+    #[error("{}", .0.message())]
+    JsonField(JsonFieldError),
+
+    #[error("The server is in maintenance mode, try again later")]
+    Maintenance,
+
     #[error("{0}")]
     Custom(&'static str),
 }
 
 pub trait KristErrorExt {
+    /// Get the catalog entry (error code and status) for this error
+    fn catalog_entry(&self) -> KristErrorCode;
+
     /// Get the error type for the `message` field in a krist error response
-    fn error_type(&self) -> &'static str;
+    fn error_type(&self) -> &'static str {
+        self.catalog_entry().code
+    }
 }
 
 impl KristErrorExt for KristError {
-    fn error_type(&self) -> &'static str {
+    fn catalog_entry(&self) -> KristErrorCode {
+        match self {
+            KristError::Address(e) => e.catalog_entry(),
+            KristError::Generic(e) => e.catalog_entry(),
+            KristError::Name(e) => e.catalog_entry(),
+            KristError::Transaction(e) => e.catalog_entry(),
+            KristError::WebSocket(e) => e.catalog_entry(),
+            KristError::Database(_) => catalog::INTERNAL_SERVER_ERROR,
+            KristError::JsonPayload(_) | KristError::JsonField(_) => {
+                match self.json_field_error() {
+                    Some(field_error) if field_error.missing => catalog::MISSING_PARAMETER,
+                    Some(_) => catalog::INVALID_PARAMETER,
+                    None => catalog::INTERNAL_SERVER_ERROR,
+                }
+            }
+            KristError::Path(_) => catalog::INTERNAL_SERVER_ERROR,
+            KristError::Maintenance => catalog::MAINTENANCE,
+            // Same way as krist, where message is the error type when no message type is given
+            KristError::Custom(e) => KristErrorCode::new(e, StatusCode::BAD_REQUEST),
+        }
+    }
+}
+
+impl KristError {
+    fn json_field_error(&self) -> Option<JsonFieldError> {
         match self {
-            KristError::Address(e) => e.error_type(),
-            KristError::Generic(e) => e.error_type(),
-            KristError::Name(e) => e.error_type(),
-            KristError::Transaction(e) => e.error_type(),
-            KristError::WebSocket(e) => e.error_type(),
-            KristError::Database(_) => "internal_server_error",
-            KristError::JsonPayload(_) => "internal_server_error",
-            KristError::Path(_) => "internal_server_error",
-            KristError::Custom(e) => e, // Same way as krist, where message is the error type when no message type is given
+            KristError::JsonPayload(e) => JsonFieldError::from_payload(e),
+            KristError::JsonField(field_error) => Some(field_error.clone()),
+            _ => None,
         }
     }
 }
@@ -77,17 +115,7 @@ impl error::ResponseError for KristError {
         // TODO: Evaluate whether or not programs might break when returning the correct error code or not.
         //       In Krist, responses are always error code 200 because of a long standing bug.
         //       For some reason, that bug was never fixed and is just set there for forever, pretty stupid if you ask me.
-        match self {
-            KristError::Address(e) => e.status_code(),
-            KristError::Generic(e) => e.status_code(),
-            KristError::Name(e) => e.status_code(),
-            KristError::Transaction(e) => e.status_code(),
-            KristError::WebSocket(e) => e.status_code(),
-            KristError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
-            KristError::JsonPayload(_) => StatusCode::INTERNAL_SERVER_ERROR,
-            KristError::Path(_) => StatusCode::INTERNAL_SERVER_ERROR,
-            KristError::Custom(_) => StatusCode::BAD_REQUEST,
-        }
+        self.catalog_entry().status
     }
 
     fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
@@ -103,16 +131,37 @@ impl error::ResponseError for KristError {
                     error: e,
                     message: e.to_string(),
                     info: None,
+                    parameter: None,
                 };
 
                 HttpResponse::build(StatusCode::BAD_REQUEST).json(error)
             }
             _ => {
+                let (message, info, parameter) = match self {
+                    // The database error itself goes to the logs, the client only learns something broke.
+                    KristError::Database(e) => {
+                        tracing::error!("Database error in krist route: {e}");
+                        ("Internal server error".to_owned(), None, None)
+                    }
+                    KristError::JsonPayload(_) | KristError::JsonField(_) => {
+                        match self.json_field_error() {
+                            Some(field_error) => (
+                                field_error.message(),
+                                Some(field_error.detail),
+                                field_error.field,
+                            ),
+                            None => (self.to_string(), None, None),
+                        }
+                    }
+                    _ => (self.to_string(), None, None),
+                };
+
                 let error = KristErrorResponse {
                     ok: false,
                     error: self.error_type(),
-                    message: self.to_string(),
-                    info: None,
+                    message,
+                    info,
+                    parameter,
                 };
 
                 HttpResponse::build(self.status_code()).json(error)
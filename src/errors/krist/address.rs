@@ -1,7 +1,7 @@
 use actix_web::{HttpResponse, error, http::StatusCode};
 use thiserror::Error;
 
-use super::{KristErrorExt, KristErrorResponse};
+use super::{KristErrorExt, KristErrorResponse, catalog};
 
 use crate::errors::wallet;
 
@@ -10,15 +10,27 @@ pub enum AddressError {
     #[error("Address {0} not found")]
     NotFound(String),
 
+    #[error("Sender address {0} not found")]
+    SenderNotFound(String),
+
     #[error("Authentication failed")]
     AuthFailed,
+
+    #[error("Address {0} is locked")]
+    Locked(String),
+
+    #[error("{0} is not a valid address")]
+    InvalidAddress(String),
 }
 
 impl KristErrorExt for AddressError {
-    fn error_type(&self) -> &'static str {
+    fn catalog_entry(&self) -> catalog::KristErrorCode {
         match self {
-            AddressError::NotFound(_) => "address_not_found",
-            AddressError::AuthFailed => "auth_failed",
+            AddressError::NotFound(_) => catalog::ADDRESS_NOT_FOUND,
+            AddressError::SenderNotFound(_) => catalog::SENDER_NOT_FOUND,
+            AddressError::AuthFailed => catalog::AUTH_FAILED,
+            AddressError::Locked(_) => catalog::ADDRESS_LOCKED,
+            AddressError::InvalidAddress(_) => catalog::INVALID_ADDRESS,
         }
     }
 }
@@ -28,10 +40,7 @@ impl error::ResponseError for AddressError {
         // TODO: Evaluate whether or not programs might break when returning the correct error code or not.
         //       In Krist, responses are always error code 200 because of a long standing bug.
         //       For some reason, that bug was never fixed and is just set there for forever, pretty stupid if you ask me.
-        match self {
-            AddressError::NotFound(_) => StatusCode::NOT_FOUND,
-            AddressError::AuthFailed => StatusCode::UNAUTHORIZED,
-        }
+        self.catalog_entry().status
     }
 
     fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
@@ -40,6 +49,7 @@ impl error::ResponseError for AddressError {
             error: self.error_type(),
             message: self.to_string(),
             info: None, // Unsure wheter or not this is right
+            parameter: None,
         };
 
         HttpResponse::build(self.status_code()).json(message)
@@ -50,7 +60,10 @@ impl From<wallet::WalletError> for AddressError {
     fn from(value: wallet::WalletError) -> Self {
         match value {
             wallet::WalletError::NotFound(address) => AddressError::NotFound(address),
+            wallet::WalletError::SenderNotFound(address) => AddressError::SenderNotFound(address),
             wallet::WalletError::AuthFailed => AddressError::AuthFailed,
+            wallet::WalletError::Locked(address) => AddressError::Locked(address),
+            wallet::WalletError::InvalidAddress(address) => AddressError::InvalidAddress(address),
         }
     }
 }
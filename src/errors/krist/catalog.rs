@@ -0,0 +1,264 @@
+//! Every Krist error code we send, together with the HTTP status it is sent with.
+//!
+//! Error types should map their variants onto these instead of writing the wire strings out
+//! themselves, so the same error can never end up with different codes between endpoints.
+
+use actix_web::http::StatusCode;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KristErrorCode {
+    /// The value of the `error` field in a krist error response
+    pub code: &'static str,
+    pub status: StatusCode,
+}
+
+impl KristErrorCode {
+    pub const fn new(code: &'static str, status: StatusCode) -> Self {
+        Self { code, status }
+    }
+}
+
+// Generic
+pub const INVALID_PARAMETER: KristErrorCode =
+    KristErrorCode::new("invalid_parameter", StatusCode::BAD_REQUEST);
+pub const MISSING_PARAMETER: KristErrorCode =
+    KristErrorCode::new("missing_parameter", StatusCode::BAD_REQUEST);
+pub const INTERNAL_SERVER_ERROR: KristErrorCode =
+    KristErrorCode::new("internal_server_error", StatusCode::INTERNAL_SERVER_ERROR);
+pub const DATABASE_ERROR: KristErrorCode =
+    KristErrorCode::new("database_error", StatusCode::INTERNAL_SERVER_ERROR);
+pub const MAINTENANCE: KristErrorCode =
+    KristErrorCode::new("maintenance", StatusCode::SERVICE_UNAVAILABLE);
+
+// Addresses
+pub const ADDRESS_NOT_FOUND: KristErrorCode =
+    KristErrorCode::new("address_not_found", StatusCode::NOT_FOUND);
+pub const SENDER_NOT_FOUND: KristErrorCode =
+    KristErrorCode::new("sender_not_found", StatusCode::NOT_FOUND);
+pub const AUTH_FAILED: KristErrorCode =
+    KristErrorCode::new("auth_failed", StatusCode::UNAUTHORIZED);
+pub const ADDRESS_LOCKED: KristErrorCode =
+    KristErrorCode::new("address_locked", StatusCode::FORBIDDEN);
+pub const INVALID_ADDRESS: KristErrorCode =
+    KristErrorCode::new("invalid_address", StatusCode::BAD_REQUEST);
+pub const UNAUTHORIZED: KristErrorCode =
+    KristErrorCode::new("unauthorized", StatusCode::UNAUTHORIZED);
+
+// Names
+pub const NAME_NOT_FOUND: KristErrorCode =
+    KristErrorCode::new("name_not_found", StatusCode::NOT_FOUND);
+pub const INVALID_NAME: KristErrorCode =
+    KristErrorCode::new("invalid_name", StatusCode::BAD_REQUEST);
+pub const NAME_TAKEN: KristErrorCode = KristErrorCode::new("name_taken", StatusCode::CONFLICT);
+pub const NOT_NAME_OWNER: KristErrorCode =
+    KristErrorCode::new("not_name_owner", StatusCode::FORBIDDEN);
+// Really dont know what to put here instead of 418.
+pub const INSUFFICIENT_BALANCE: KristErrorCode =
+    KristErrorCode::new("insufficient_balance", StatusCode::IM_A_TEAPOT);
+
+// Transactions
+pub const INSUFFICIENT_FUNDS: KristErrorCode =
+    KristErrorCode::new("insufficient_funds", StatusCode::FORBIDDEN);
+pub const TRANSACTION_NOT_FOUND: KristErrorCode =
+    KristErrorCode::new("transaction_not_found", StatusCode::NOT_FOUND);
+pub const TRANSACTIONS_DISABLED: KristErrorCode =
+    KristErrorCode::new("transactions_disabled", StatusCode::LOCKED);
+pub const SAME_WALLET_TRANSFER: KristErrorCode =
+    KristErrorCode::new("same_wallet_transfer", StatusCode::BAD_REQUEST);
+pub const TRANSACTION_CONFLICT: KristErrorCode =
+    KristErrorCode::new("transaction_conflict", StatusCode::CONFLICT);
+pub const DUPLICATE_TRANSACTION: KristErrorCode =
+    KristErrorCode::new("duplicate_transaction", StatusCode::CONFLICT);
+pub const TRANSACTION_TOO_LARGE: KristErrorCode =
+    KristErrorCode::new("transaction_too_large", StatusCode::BAD_REQUEST);
+pub const METADATA_TOO_LONG: KristErrorCode =
+    KristErrorCode::new("metadata_too_long", StatusCode::BAD_REQUEST);
+pub const AMOUNT_TOO_PRECISE: KristErrorCode =
+    KristErrorCode::new("amount_too_precise", StatusCode::BAD_REQUEST);
+pub const INVALID_METADATA: KristErrorCode =
+    KristErrorCode::new("invalid_metadata", StatusCode::BAD_REQUEST);
+pub const MINING_DISABLED: KristErrorCode =
+    KristErrorCode::new("mining_disabled", StatusCode::LOCKED);
+
+// Websockets
+pub const INVALID_WEBSOCKET_TOKEN: KristErrorCode =
+    KristErrorCode::new("invalid_websocket_token", StatusCode::FORBIDDEN);
+pub const HANDSHAKE_ERROR: KristErrorCode =
+    KristErrorCode::new("handshake_error", StatusCode::FORBIDDEN);
+pub const TOKEN_NOT_FOUND: KristErrorCode =
+    KristErrorCode::new("token_not_found", StatusCode::FORBIDDEN);
+pub const SESSION_NOT_FOUND: KristErrorCode =
+    KristErrorCode::new("session_not_found", StatusCode::NOT_FOUND);
+pub const INVALID_MESSAGE: KristErrorCode =
+    KristErrorCode::new("invalid_message", StatusCode::BAD_REQUEST);
+pub const INVALID_MESSAGE_TYPE: KristErrorCode =
+    KristErrorCode::new("invalid_message_type", StatusCode::BAD_REQUEST);
+pub const MESSAGE_TOO_LONG: KristErrorCode =
+    KristErrorCode::new("message_too_long", StatusCode::PAYLOAD_TOO_LARGE);
+pub const SLOW_CONSUMER: KristErrorCode =
+    KristErrorCode::new("slow_consumer", StatusCode::TOO_MANY_REQUESTS);
+pub const GUEST_LIMIT_REACHED: KristErrorCode =
+    KristErrorCode::new("guest_limit_reached", StatusCode::TOO_MANY_REQUESTS);
+pub const IP_LIMIT_REACHED: KristErrorCode =
+    KristErrorCode::new("ip_limit_reached", StatusCode::TOO_MANY_REQUESTS);
+pub const BINARY_NOT_SUPPORTED: KristErrorCode =
+    KristErrorCode::new("binary_not_supported", StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+#[cfg(test)]
+mod tests {
+    use actix_web::ResponseError;
+    use rust_decimal::dec;
+
+    use super::*;
+    use crate::errors::krist::{
+        KristError, KristErrorExt, address::AddressError, generic::GenericError, name::NameError,
+        transaction::TransactionError, websockets::WebSocketError,
+    };
+
+    #[test]
+    fn test_documented_codes() {
+        let cases = [
+            (
+                KristError::Address(AddressError::NotFound("kre3w0i79j".into())),
+                "address_not_found",
+                StatusCode::NOT_FOUND,
+            ),
+            (
+                KristError::Address(AddressError::SenderNotFound("kre3w0i79j".into())),
+                "sender_not_found",
+                StatusCode::NOT_FOUND,
+            ),
+            (
+                KristError::Address(AddressError::AuthFailed),
+                "auth_failed",
+                StatusCode::UNAUTHORIZED,
+            ),
+            (
+                KristError::Address(AddressError::Locked("kre3w0i79j".into())),
+                "address_locked",
+                StatusCode::FORBIDDEN,
+            ),
+            (
+                KristError::Address(AddressError::InvalidAddress("nope!!".into())),
+                "invalid_address",
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                KristError::Generic(GenericError::InvalidParameter("a".into())),
+                "invalid_parameter",
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                KristError::Generic(GenericError::MissingParameter("a".into())),
+                "missing_parameter",
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                KristError::Name(NameError::NameNotFound("foo".into())),
+                "name_not_found",
+                StatusCode::NOT_FOUND,
+            ),
+            (
+                KristError::Name(NameError::InvalidName("Foo".into())),
+                "invalid_name",
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                KristError::Name(NameError::NameTaken("foo".into())),
+                "name_taken",
+                StatusCode::CONFLICT,
+            ),
+            (
+                KristError::Name(NameError::NotNameOwner("foo".into())),
+                "not_name_owner",
+                StatusCode::FORBIDDEN,
+            ),
+            (
+                KristError::Name(NameError::InsufficientBalance),
+                "insufficient_balance",
+                StatusCode::IM_A_TEAPOT,
+            ),
+            (
+                KristError::Transaction(TransactionError::InsufficientFunds),
+                "insufficient_funds",
+                StatusCode::FORBIDDEN,
+            ),
+            (
+                KristError::Transaction(TransactionError::NotFound),
+                "transaction_not_found",
+                StatusCode::NOT_FOUND,
+            ),
+            (
+                KristError::Transaction(TransactionError::Disabled),
+                "transactions_disabled",
+                StatusCode::LOCKED,
+            ),
+            (
+                KristError::Transaction(TransactionError::SameWalletTransfer),
+                "same_wallet_transfer",
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                KristError::Transaction(TransactionError::Conflict("to".into())),
+                "transaction_conflict",
+                StatusCode::CONFLICT,
+            ),
+            (
+                KristError::Transaction(TransactionError::Duplicate),
+                "duplicate_transaction",
+                StatusCode::CONFLICT,
+            ),
+            (
+                KristError::Transaction(TransactionError::TooLarge(dec!(1000))),
+                "transaction_too_large",
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                KristError::Transaction(TransactionError::MetadataTooLong(255)),
+                "metadata_too_long",
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                KristError::Transaction(TransactionError::InvalidMetadata),
+                "invalid_metadata",
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                KristError::Transaction(TransactionError::TooPrecise(2)),
+                "amount_too_precise",
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                KristError::WebSocket(WebSocketError::InvalidWebsocketToken),
+                "invalid_websocket_token",
+                StatusCode::FORBIDDEN,
+            ),
+            (
+                KristError::WebSocket(WebSocketError::HandshakeError),
+                "handshake_error",
+                StatusCode::FORBIDDEN,
+            ),
+            (
+                KristError::Database(sqlx::Error::RowNotFound),
+                "internal_server_error",
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            (
+                KristError::Maintenance,
+                "maintenance",
+                StatusCode::SERVICE_UNAVAILABLE,
+            ),
+            (
+                KristError::Custom("server_config_error"),
+                "server_config_error",
+                StatusCode::BAD_REQUEST,
+            ),
+        ];
+
+        for (error, code, status) in cases {
+            assert_eq!(error.error_type(), code, "wrong code for {error:?}");
+            assert_eq!(error.status_code(), status, "wrong status for {error:?}");
+            assert_eq!(error.error_response().status(), status);
+        }
+    }
+}
@@ -1,7 +1,7 @@
-use actix_web::{HttpResponse, error};
+use actix_web::{HttpResponse, error, http::StatusCode};
 use thiserror::Error;
 
-use super::{KristErrorExt, KristErrorResponse};
+use super::{KristErrorExt, KristErrorResponse, catalog};
 
 #[derive(Error, Debug)]
 pub enum GenericError {
@@ -15,8 +15,8 @@ pub enum GenericError {
 }
 
 impl error::ResponseError for GenericError {
-    fn status_code(&self) -> actix_web::http::StatusCode {
-        actix_web::http::StatusCode::BAD_REQUEST
+    fn status_code(&self) -> StatusCode {
+        self.catalog_entry().status
     }
 
     fn error_response(&self) -> actix_web::HttpResponse<actix_web::body::BoxBody> {
@@ -25,18 +25,77 @@ impl error::ResponseError for GenericError {
             error: self.error_type(),
             message: self.to_string(),
             info: None, // Unsure wheter or not this is right
+            parameter: Some(self.parameter().to_owned()),
         };
 
         HttpResponse::build(self.status_code()).json(message)
     }
 }
 
+impl GenericError {
+    /// The name of the parameter this error is about
+    pub fn parameter(&self) -> &str {
+        match self {
+            GenericError::InvalidParameter(parameter) => parameter,
+            GenericError::MissingParameter(parameter) => parameter,
+        }
+    }
+}
+
 impl KristErrorExt for GenericError {
-    fn error_type(&self) -> &'static str {
+    fn catalog_entry(&self) -> catalog::KristErrorCode {
         match self {
-            GenericError::InvalidParameter(_) => "invalid_parameter",
-            GenericError::MissingParameter(_) => "missing_parameter",
+            GenericError::InvalidParameter(_) => catalog::INVALID_PARAMETER,
+            GenericError::MissingParameter(_) => catalog::MISSING_PARAMETER,
             // GenericError::ValidationError(_) => "validation_error",
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{ResponseError, body};
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    async fn body_of(error: GenericError) -> (StatusCode, Value) {
+        let response = error.error_response();
+        let status = response.status();
+        let bytes = body::to_bytes(response.into_body())
+            .await
+            .expect("failed to read body");
+
+        (
+            status,
+            serde_json::from_slice(&bytes).expect("body is not json"),
+        )
+    }
+
+    #[actix_web::test]
+    async fn test_invalid_parameter_body() {
+        for parameter in ["a", "name"] {
+            let (status, body) = body_of(GenericError::InvalidParameter(parameter.into())).await;
+
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert_eq!(
+                body,
+                json!({
+                    "ok": false,
+                    "error": "invalid_parameter",
+                    "message": format!("Invalid parameter {parameter}"),
+                    "parameter": parameter,
+                })
+            );
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_missing_parameter_body() {
+        let (status, body) = body_of(GenericError::MissingParameter("privatekey".into())).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "missing_parameter");
+        assert_eq!(body["parameter"], "privatekey");
+    }
+}
@@ -1,7 +1,7 @@
 use actix_web::{HttpResponse, error, http::StatusCode};
 use thiserror::Error;
 
-use super::{KristErrorExt, KristErrorResponse};
+use super::{KristErrorExt, KristErrorResponse, catalog};
 
 use crate::errors::name;
 
@@ -10,6 +10,9 @@ pub enum NameError {
     #[error("Name {0} not found")]
     NameNotFound(String),
 
+    #[error("Invalid name {0}")]
+    InvalidName(String),
+
     #[error("Name {0} is already taken")]
     NameTaken(String),
 
@@ -22,12 +25,7 @@ pub enum NameError {
 
 impl error::ResponseError for NameError {
     fn status_code(&self) -> StatusCode {
-        match self {
-            NameError::NameNotFound(_) => StatusCode::NOT_FOUND,
-            NameError::NameTaken(_) => StatusCode::CONFLICT,
-            NameError::NotNameOwner(_) => StatusCode::FORBIDDEN,
-            NameError::InsufficientBalance => StatusCode::IM_A_TEAPOT, // Really dont know what to put here instead of 418.
-        }
+        self.catalog_entry().status
     }
 
     fn error_response(&self) -> HttpResponse {
@@ -36,6 +34,7 @@ impl error::ResponseError for NameError {
             error: self.error_type(),
             message: self.to_string(),
             info: None,
+            parameter: None,
         };
 
         HttpResponse::build(self.status_code()).json(error)
@@ -43,12 +42,13 @@ impl error::ResponseError for NameError {
 }
 
 impl KristErrorExt for NameError {
-    fn error_type(&self) -> &'static str {
+    fn catalog_entry(&self) -> catalog::KristErrorCode {
         match self {
-            NameError::NameNotFound(_) => "name_not_found",
-            NameError::NameTaken(_) => "name_taken",
-            NameError::NotNameOwner(_) => "not_name_owner",
-            NameError::InsufficientBalance => "insufficient_balance",
+            NameError::NameNotFound(_) => catalog::NAME_NOT_FOUND,
+            NameError::InvalidName(_) => catalog::INVALID_NAME,
+            NameError::NameTaken(_) => catalog::NAME_TAKEN,
+            NameError::NotNameOwner(_) => catalog::NOT_NAME_OWNER,
+            NameError::InsufficientBalance => catalog::INSUFFICIENT_BALANCE,
         }
     }
 }
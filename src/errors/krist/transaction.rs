@@ -1,7 +1,8 @@
 use actix_web::{HttpResponse, error, http::StatusCode};
+use rust_decimal::Decimal;
 use thiserror::Error;
 
-use super::{KristErrorExt, KristErrorResponse};
+use super::{KristErrorExt, KristErrorResponse, catalog};
 
 use crate::errors::transaction;
 
@@ -21,29 +22,43 @@ pub enum TransactionError {
 
     #[error("Transaction conflict for parameter {0}")]
     Conflict(String),
+
+    #[error("An identical transaction was just made, set allowDuplicate to send it again")]
+    Duplicate,
+
+    #[error("Transaction amount is over the limit of {0}")]
+    TooLarge(Decimal),
+
+    #[error("Metadata is longer than {0} characters")]
+    MetadataTooLong(usize),
+
+    #[error("Amount has more than {0} decimal places")]
+    TooPrecise(u32),
+
+    #[error("Metadata contains control characters or irregular whitespace")]
+    InvalidMetadata,
 }
 
 impl KristErrorExt for TransactionError {
-    fn error_type(&self) -> &'static str {
+    fn catalog_entry(&self) -> catalog::KristErrorCode {
         match self {
-            TransactionError::InsufficientFunds => "insufficient_funds",
-            TransactionError::NotFound => "transaction_not_found",
-            TransactionError::Disabled => "transactions_disabled",
-            TransactionError::SameWalletTransfer => "same_wallet_transfer",
-            TransactionError::Conflict(_) => "transaction_conflict",
+            TransactionError::InsufficientFunds => catalog::INSUFFICIENT_FUNDS,
+            TransactionError::NotFound => catalog::TRANSACTION_NOT_FOUND,
+            TransactionError::Disabled => catalog::TRANSACTIONS_DISABLED,
+            TransactionError::SameWalletTransfer => catalog::SAME_WALLET_TRANSFER,
+            TransactionError::Conflict(_) => catalog::TRANSACTION_CONFLICT,
+            TransactionError::Duplicate => catalog::DUPLICATE_TRANSACTION,
+            TransactionError::TooLarge(_) => catalog::TRANSACTION_TOO_LARGE,
+            TransactionError::MetadataTooLong(_) => catalog::METADATA_TOO_LONG,
+            TransactionError::TooPrecise(_) => catalog::AMOUNT_TOO_PRECISE,
+            TransactionError::InvalidMetadata => catalog::INVALID_METADATA,
         }
     }
 }
 
 impl error::ResponseError for TransactionError {
     fn status_code(&self) -> StatusCode {
-        match self {
-            TransactionError::InsufficientFunds => StatusCode::FORBIDDEN,
-            TransactionError::NotFound => StatusCode::NOT_FOUND,
-            TransactionError::Disabled => StatusCode::LOCKED,
-            TransactionError::SameWalletTransfer => StatusCode::BAD_REQUEST,
-            TransactionError::Conflict(_) => StatusCode::CONFLICT,
-        }
+        self.catalog_entry().status
     }
 
     fn error_response(&self) -> HttpResponse {
@@ -52,6 +67,7 @@ impl error::ResponseError for TransactionError {
             error: self.error_type(),
             message: self.to_string(),
             info: None,
+            parameter: None,
         };
 
         HttpResponse::build(self.status_code()).json(error)
@@ -66,6 +82,26 @@ impl From<transaction::TransactionError> for TransactionError {
             transaction::TransactionError::Disabled => Self::Disabled,
             transaction::TransactionError::SameWalletTransfer => Self::SameWalletTransfer,
             transaction::TransactionError::Conflict(param) => Self::Conflict(param),
+            transaction::TransactionError::Duplicate => Self::Duplicate,
+            transaction::TransactionError::TooLarge(max) => Self::TooLarge(max),
+            transaction::TransactionError::MetadataTooLong(max) => Self::MetadataTooLong(max),
+            transaction::TransactionError::TooPrecise(scale) => Self::TooPrecise(scale),
+            transaction::TransactionError::InvalidMetadata => Self::InvalidMetadata,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use actix_web::ResponseError;
+
+    use super::*;
+
+    #[test]
+    fn test_duplicate_transaction() {
+        let error = TransactionError::from(transaction::TransactionError::Duplicate);
+
+        assert_eq!(error.error_type(), "duplicate_transaction");
+        assert_eq!(error.status_code(), StatusCode::CONFLICT);
+    }
+}
@@ -1,7 +1,7 @@
 use actix_web::{HttpResponse, error};
 use thiserror::Error;
 
-use super::{KristErrorExt, KristErrorResponse};
+use super::{KristErrorExt, KristErrorResponse, catalog};
 
 #[derive(Error, Debug)]
 pub enum WebSocketError {
@@ -14,7 +14,7 @@ pub enum WebSocketError {
 
 impl error::ResponseError for WebSocketError {
     fn status_code(&self) -> actix_web::http::StatusCode {
-        actix_web::http::StatusCode::FORBIDDEN
+        self.catalog_entry().status
     }
 
     fn error_response(&self) -> actix_web::HttpResponse<actix_web::body::BoxBody> {
@@ -23,6 +23,7 @@ impl error::ResponseError for WebSocketError {
             error: self.error_type(),
             message: self.to_string(),
             info: None, // Unsure wheter or not this is right
+            parameter: None,
         };
 
         HttpResponse::build(self.status_code()).json(message)
@@ -30,10 +31,10 @@ impl error::ResponseError for WebSocketError {
 }
 
 impl KristErrorExt for WebSocketError {
-    fn error_type(&self) -> &'static str {
+    fn catalog_entry(&self) -> catalog::KristErrorCode {
         match self {
-            WebSocketError::InvalidWebsocketToken => "invalid_websocket_token",
-            WebSocketError::HandshakeError => "handshake_error",
+            WebSocketError::InvalidWebsocketToken => catalog::INVALID_WEBSOCKET_TOKEN,
+            WebSocketError::HandshakeError => catalog::HANDSHAKE_ERROR,
         }
     }
 }
@@ -1,3 +1,4 @@
+pub mod json;
 pub mod krist;
 pub mod name;
 pub mod player;
@@ -12,7 +13,8 @@ use actix_web::{
     http::StatusCode,
 };
 
-use crate::models::kromer::responses::{ApiError, ApiResponse, None};
+use crate::models::kromer::responses::{ApiError, ApiResponse, ErrorDetail, None};
+use json::JsonFieldError;
 
 #[derive(Debug, thiserror::Error)]
 pub enum KromerError {
@@ -69,10 +71,21 @@ impl error::ResponseError for KromerError {
 
     fn error_response(&self) -> HttpResponse<BoxBody> {
         let message = self.to_string();
+        let field_error = match self {
+            KromerError::JsonPayload(e) => JsonFieldError::from_payload(e),
+            _ => Option::None,
+        };
+        let details: Vec<ErrorDetail<'_>> = field_error
+            .iter()
+            .map(|field_error| ErrorDetail {
+                field: field_error.field.as_deref().unwrap_or("body"),
+                message: &field_error.detail,
+            })
+            .collect();
 
         let error = ApiError {
             code: match self {
-                KromerError::NotFound => "resource_not_found_error",
+                KromerError::NotFound => "not_found",
                 KromerError::Database(..) => "database_error",
                 KromerError::Wallet(..) => "wallet_error",
                 KromerError::Transaction(..) => "transaction_error",
@@ -85,10 +98,11 @@ impl error::ResponseError for KromerError {
                 KromerError::JsonPayload(_) => "json_payload_error",
             },
             message: &message,
-            details: &[],
+            details: &details,
         };
 
         let response: ApiResponse<'_, None> = ApiResponse {
+            ok: false,
             error: Some(error),
             ..Default::default()
         };
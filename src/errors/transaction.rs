@@ -1,4 +1,5 @@
 use actix_web::{error, http::StatusCode};
+use rust_decimal::Decimal;
 
 #[derive(Debug, thiserror::Error)]
 pub enum TransactionError {
@@ -16,6 +17,21 @@ pub enum TransactionError {
 
     #[error("Transaction conflict for parameter {0}")]
     Conflict(String),
+
+    #[error("An identical transaction was just made, set allowDuplicate to send it again")]
+    Duplicate,
+
+    #[error("Transaction amount is over the limit of {0}")]
+    TooLarge(Decimal),
+
+    #[error("Metadata is longer than {0} characters")]
+    MetadataTooLong(usize),
+
+    #[error("Amount has more than {0} decimal places")]
+    TooPrecise(u32),
+
+    #[error("Metadata contains control characters or irregular whitespace")]
+    InvalidMetadata,
 }
 
 impl error::ResponseError for TransactionError {
@@ -26,6 +42,11 @@ impl error::ResponseError for TransactionError {
             TransactionError::Disabled => StatusCode::FORBIDDEN,
             TransactionError::SameWalletTransfer => StatusCode::FORBIDDEN,
             TransactionError::Conflict(_) => StatusCode::CONFLICT,
+            TransactionError::Duplicate => StatusCode::CONFLICT,
+            TransactionError::TooLarge(_) => StatusCode::BAD_REQUEST,
+            TransactionError::MetadataTooLong(_) => StatusCode::BAD_REQUEST,
+            TransactionError::TooPrecise(_) => StatusCode::BAD_REQUEST,
+            TransactionError::InvalidMetadata => StatusCode::BAD_REQUEST,
         }
     }
 }
@@ -5,15 +5,27 @@ pub enum WalletError {
     #[error("Wallet {0} was not found")]
     NotFound(String),
 
+    #[error("Sender wallet {0} was not found")]
+    SenderNotFound(String),
+
     #[error("Authentication failed")]
     AuthFailed,
+
+    #[error("Wallet {0} is locked")]
+    Locked(String),
+
+    #[error("{0} is not a valid wallet address")]
+    InvalidAddress(String),
 }
 
 impl error::ResponseError for WalletError {
     fn status_code(&self) -> actix_web::http::StatusCode {
         match self {
             WalletError::NotFound(_) => actix_web::http::StatusCode::NOT_FOUND,
+            WalletError::SenderNotFound(_) => actix_web::http::StatusCode::NOT_FOUND,
             WalletError::AuthFailed => actix_web::http::StatusCode::BAD_REQUEST,
+            WalletError::Locked(_) => actix_web::http::StatusCode::FORBIDDEN,
+            WalletError::InvalidAddress(_) => actix_web::http::StatusCode::BAD_REQUEST,
         }
     }
 }
@@ -1,13 +1,19 @@
 #![deny(clippy::unused_async)]
 
+use std::sync::atomic::{AtomicBool, Ordering};
+
 use sqlx::{Pool, Postgres};
 
+use errors::krist::KristError;
+use routes::v1::stats::StatsCache;
+
 pub mod database;
 pub mod errors;
 pub mod guards;
 pub mod models;
 pub mod routes;
 pub mod utils;
+pub mod webhooks;
 pub mod websockets;
 
 pub mod build_info {
@@ -18,4 +24,42 @@ pub mod build_info {
 #[derive(Debug)]
 pub struct AppState {
     pub pool: Pool<Postgres>,
+    /// While set, write routes and websocket `make_transaction` answer with a `maintenance` error
+    /// and reads keep working. The internal routes are left writable on purpose, they are how an
+    /// operator corrects balances while everyone else is kept out.
+    pub maintenance: AtomicBool,
+    /// Totals served by `/api/v1/stats`, kept here so every worker shares the same copy.
+    pub stats_cache: StatsCache,
+    /// Where clients reach this server, read from `PUBLIC_URL`. Routes that hand out URLs answer
+    /// with `server_config_error` while it is unset.
+    pub public_url: Option<String>,
+}
+
+impl AppState {
+    /// Starts in maintenance mode when `MAINTENANCE_MODE` is set to true.
+    pub fn new(pool: Pool<Postgres>) -> Self {
+        Self {
+            pool,
+            maintenance: AtomicBool::new(utils::env::var_or("MAINTENANCE_MODE", false)),
+            stats_cache: StatsCache::default(),
+            public_url: std::env::var("PUBLIC_URL").ok(),
+        }
+    }
+
+    pub fn set_maintenance(&self, enabled: bool) {
+        self.maintenance.store(enabled, Ordering::SeqCst);
+    }
+
+    pub fn is_maintenance(&self) -> bool {
+        self.maintenance.load(Ordering::SeqCst)
+    }
+
+    /// Called at the top of write routes, refuses the write while in maintenance mode.
+    pub fn ensure_writable(&self) -> Result<(), KristError> {
+        if self.is_maintenance() {
+            return Err(KristError::Maintenance);
+        }
+
+        Ok(())
+    }
 }
@@ -1,11 +1,16 @@
-use actix_cors::Cors;
 use actix_web::{App, HttpServer, middleware, web};
 
 use std::env;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
-use kromer::{AppState, routes, websockets::WebSocketServer};
+use kromer::{
+    AppState,
+    database::{ledger, transaction},
+    routes,
+    webhooks::WebhookDispatcher,
+    websockets::{WebSocketServer, WebSocketServerConfig},
+};
 
 #[actix_web::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -25,19 +30,35 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     sqlx::migrate!("./migrations").run(&pool).await?;
     tracing::info!("Database migrations completed successfully");
 
-    let krist_ws_server = WebSocketServer::new();
-    let state = web::Data::new(AppState { pool });
+    ledger::ensure_system_wallet(&pool, &ledger::WELFARE_ADDRESS).await?;
+    transaction::validate_limits()?;
+    routes::validate_limits()?;
+
+    let ws_config = WebSocketServerConfig::from_env()?;
+    ws_config.validate()?;
+    let krist_ws_server = WebSocketServer::with_config(ws_config)
+        .with_webhooks(WebhookDispatcher::spawn(pool.clone()));
+    let state = web::Data::new(AppState::new(pool));
 
     #[derive(OpenApi)]
     #[openapi(
         paths(
             routes::v1::wallet::wallet_get_by_uuid,
             routes::v1::wallet::wallet_get_by_name,
+            routes::v1::wallet::wallet_get_owner,
+            routes::v1::stats::stats_get,
             routes::v1::ws::ws_session_get_count,
+            routes::v1::ws::ws_subscriptions_get_counts,
+            routes::v1::transactions::transaction_export,
+            routes::v1::transactions::transaction_by_metadata,
             routes::krist::transactions::transaction_list,
             routes::krist::transactions::transaction_create,
             routes::krist::transactions::transaction_latest,
+            routes::krist::transactions::transaction_between,
+            routes::krist::transactions::transaction_by_ref,
+            routes::krist::transactions::transaction_latest_for_address,
             routes::krist::transactions::transaction_get,
+            routes::krist::transactions::transaction_get_meta,
             routes::krist::misc::login_address,
             routes::krist::misc::get_motd,
             routes::krist::misc::get_walletversion,
@@ -48,6 +69,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             routes::krist::names::name_check,
             routes::krist::names::name_bonus,
             routes::krist::names::name_new,
+            routes::krist::names::name_stats,
             routes::krist::names::name_get,
             routes::krist::names::name_register,
             routes::krist::names::name_update_data,
@@ -57,11 +79,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             routes::krist::wallet::wallet_richest,
             routes::krist::wallet::wallet_get_transactions,
             routes::krist::wallet::wallet_get_names,
-            routes::krist::lookup::addresses::addresses_lookup
+            routes::krist::lookup::addresses::addresses_lookup,
+            routes::krist::lookup::names::names_lookup_specific,
+            routes::krist::lookup::resolve::lookup_resolve
         ),
         components(schemas(
+            kromer::models::kromer::players::Player,
             kromer::models::kromer::wallets::Wallet,
             kromer::models::kromer::websockets::SessionCountResponse,
+            kromer::models::kromer::stats::Stats,
+            kromer::models::kromer::transactions::ExportFormat,
             kromer::models::kromer::responses::None,
             kromer::models::kromer::responses::ResponseMeta,
             kromer::models::kromer::responses::ApiError,
@@ -71,6 +98,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             kromer::models::krist::transactions::TransactionResponse,
             kromer::models::krist::transactions::AddressTransactionQuery,
             kromer::models::krist::transactions::TransactionJson,
+            kromer::models::krist::transactions::TransactionMetaResponse,
+            kromer::models::krist::transactions::CommonMetaJson,
             kromer::database::transaction::TransactionType,
             kromer::routes::PaginationParams,
             kromer::models::krist::auth::LoginDetails,
@@ -84,6 +113,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             kromer::models::krist::names::DetailedUnpaidResponseRow,
             kromer::models::krist::names::NameAvailablityResponse,
             kromer::models::krist::names::NameBonusResponse,
+            kromer::models::krist::names::NameStatsResponse,
+            kromer::database::name::DailyRegistrations,
             kromer::models::krist::names::RegisterNameRequest,
             kromer::models::krist::names::TransferNameRequest,
             kromer::models::krist::names::NameDataUpdateBody,
@@ -102,26 +133,27 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             kromer::models::krist::addresses::AddressJson,
             kromer::models::krist::addresses::VerifyResponse,
             kromer::models::krist::addresses::AddressGetQuery,
+            kromer::models::krist::addresses::AddressListQuery,
             kromer::models::krist::webserver::lookup::addresses::LookupResponse,
             kromer::models::krist::webserver::lookup::addresses::QueryParameters,
+            kromer::models::krist::webserver::lookup::names::LookupResponse,
+            kromer::models::krist::webserver::lookup::ResolveQuery,
+            kromer::models::krist::webserver::lookup::ResolveResponse,
         ))
     )]
     struct ApiDocs;
 
     let http_server = HttpServer::new(move || {
-        let cors = Cors::default()
-            .allow_any_origin()
-            .allowed_methods(["GET", "POST", "PUT"])
-            .allow_any_header()
-            .max_age(3600);
+        let cors = routes::cors(routes::CORS_ALLOWED_ORIGINS.as_deref());
 
         App::new()
             .app_data(state.clone())
             .app_data(web::Data::new(krist_ws_server.clone()))
-            .wrap(middleware::Logger::new(
-                r#"%a "%r" %s %b "%{Referer}i" "%{User-Agent}i" "%{X-CC-ID}i" %T"#,
-            ))
+            .wrap(middleware::from_fn(routes::request_id::request_id))
+            // Wraps the request id middleware so the id is on the response by the time it's logged
+            .wrap(routes::request_id::access_log())
             .wrap(cors)
+            .wrap(middleware::Compress::default())
             .service(web::redirect("/swagger-ui", "/swagger-ui/")) // kinda cursed but it does work!
             .service(
                 SwaggerUi::new("/swagger-ui/{_:.*}")
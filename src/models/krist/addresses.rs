@@ -30,11 +30,12 @@ pub struct AddressCreationResponse {
 pub struct AddressJson {
     pub address: String,
     #[schema(value_type = f64, example = 10.0)]
+    #[serde(serialize_with = "super::serialize_amount")]
     pub balance: Decimal,
-    #[serde(rename = "totalin")]
+    #[serde(rename = "totalin", serialize_with = "super::serialize_amount")]
     #[schema(value_type = f64, example = 10.0)]
     pub total_in: Decimal,
-    #[serde(rename = "totalout")]
+    #[serde(rename = "totalout", serialize_with = "super::serialize_amount")]
     #[schema(value_type = f64, example = 10.0)]
     pub total_out: Decimal,
     #[serde(rename = "firstseen")]
@@ -70,6 +71,44 @@ pub struct AddressGetQuery {
     pub fetch_names: Option<bool>,
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema, IntoParams, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AddressListQuery {
+    /// One of `balance`, `address` or `firstseen`. Defaults to `address`.
+    pub order_by: Option<String>,
+    /// Either `ASC` or `DESC`. Defaults to `ASC`.
+    pub order: Option<String>,
+}
+
+/// Columns the address list may be sorted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AddressOrderBy {
+    Balance,
+    #[default]
+    Address,
+    FirstSeen,
+}
+
+impl AddressOrderBy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "balance" => Some(Self::Balance),
+            "address" => Some(Self::Address),
+            "firstseen" => Some(Self::FirstSeen),
+            _ => None,
+        }
+    }
+
+    /// The column to sort by, only ever one of these fixed values so it is safe to put in a query
+    pub fn column(&self) -> &'static str {
+        match self {
+            Self::Balance => "balance",
+            Self::Address => "address",
+            Self::FirstSeen => "created_at",
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -107,4 +146,25 @@ mod tests {
 
         assert_eq!(response_str, response_str_test);
     }
+
+    #[test]
+    fn test_address_order_by() {
+        assert_eq!(AddressOrderBy::default(), AddressOrderBy::Address);
+
+        let orderings = [
+            ("balance", AddressOrderBy::Balance, "balance"),
+            ("address", AddressOrderBy::Address, "address"),
+            ("firstseen", AddressOrderBy::FirstSeen, "created_at"),
+            ("firstSeen", AddressOrderBy::FirstSeen, "created_at"),
+        ];
+
+        for (value, expected, column) in orderings {
+            let order_by = AddressOrderBy::parse(value).expect("ordering should be allowed");
+            assert_eq!(order_by, expected);
+            assert_eq!(order_by.column(), column);
+        }
+
+        assert_eq!(AddressOrderBy::parse("private_key"), None);
+        assert_eq!(AddressOrderBy::parse("balance; DROP TABLE wallets"), None);
+    }
 }
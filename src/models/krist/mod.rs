@@ -9,7 +9,15 @@ pub mod transactions;
 pub mod webserver;
 pub mod websockets;
 
-use serde::{Deserialize, Deserializer};
+use once_cell::sync::Lazy;
+use rust_decimal::{Decimal, prelude::ToPrimitive};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::utils::env;
+
+/// Krist compatibility mode where amounts are whole numbers on the wire, like vanilla Krist.
+/// Fractional transfers are rejected while it is enabled.
+pub static INTEGER_AMOUNTS: Lazy<bool> = Lazy::new(|| env::var_or("KRIST_INTEGER_AMOUNTS", false));
 
 pub fn deserialize_number_into_string<'de, D>(deserializer: D) -> Result<String, D::Error>
 where
@@ -27,3 +35,51 @@ where
         StringOrInt::Number(i) => Ok(i.to_string()),
     }
 }
+
+/// Serialize a transaction value or balance, as an integer when [`INTEGER_AMOUNTS`] is enabled.
+pub fn serialize_amount<S>(amount: &Decimal, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serialize_amount_as(amount, *INTEGER_AMOUNTS, serializer)
+}
+
+fn serialize_amount_as<S>(amount: &Decimal, integer: bool, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    // Balances made before the mode was enabled can still have cents, those get truncated.
+    match amount.trunc().to_i64() {
+        Some(value) if integer => serializer.serialize_i64(value),
+        _ => Serialize::serialize(amount, serializer),
+    }
+}
+
+/// Whether an amount can be sent, which in integer mode means it has no fractional part.
+pub fn is_sendable_amount(amount: &Decimal, integer: bool) -> bool {
+    !integer || amount.fract().is_zero()
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::dec;
+    use serde_json::json;
+
+    use super::*;
+
+    fn amount_json(amount: Decimal, integer: bool) -> serde_json::Value {
+        serialize_amount_as(&amount, integer, serde_json::value::Serializer)
+            .expect("failed to serialize amount")
+    }
+
+    #[test]
+    fn test_integer_amounts() {
+        assert_eq!(amount_json(dec!(100), true), json!(100));
+        assert_eq!(amount_json(dec!(12.75), true), json!(12));
+        assert_eq!(amount_json(dec!(12.75), false), json!(12.75));
+
+        assert!(is_sendable_amount(&dec!(5), true));
+        assert!(!is_sendable_amount(&dec!(5.5), true));
+        assert!(is_sendable_amount(&dec!(5.5), false));
+    }
+}
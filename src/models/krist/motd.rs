@@ -11,6 +11,20 @@ pub const MINING_CONSTANTS: Constants = Constants {
     seconds_per_block: 300,
 };
 
+/// The work value we advertise, Kromer is not mined so this never changes
+pub const MOTD_WORK: i64 = 500;
+
+/// The constants sent in the MOTD and in response to websocket `work` messages
+pub const MOTD_CONSTANTS: Constants = Constants {
+    wallet_version: 3,
+    nonce_max_size: 500,
+    name_cost: 500,
+    min_work: 50,
+    max_work: 500,
+    work_factor: 500.0,
+    seconds_per_block: 5000,
+};
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct DetailedMotdResponse {
     pub ok: bool,
@@ -46,6 +46,19 @@ pub struct NameBonusResponse {
     pub name_bonus: i64,
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct NameStatsResponse {
+    pub ok: bool,
+    /// All registered names
+    pub total: usize,
+    /// Names that still have unpaid blocks left
+    pub total_unpaid: i64,
+    /// The count of days in this page
+    pub count: usize,
+    /// Registrations per day, newest day first
+    pub days: Vec<name::DailyRegistrations>,
+}
+
 #[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize, ToSchema)]
 pub struct RegisterNameRequest {
     //#[serde(rename = "desiredName")]
@@ -1,11 +1,12 @@
+use std::collections::BTreeMap;
+
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use utoipa::{IntoParams, ToSchema};
 
-use crate::database::transaction::{self, TransactionType};
-// use transaction::TransactionNameData;
+use crate::database::transaction::{self, TransactionNameData, TransactionType};
 
-#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct TransactionListResponse {
     pub ok: bool,
 
@@ -18,7 +19,7 @@ pub struct TransactionListResponse {
     pub transactions: Vec<TransactionJson>,
 }
 
-#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct TransactionDetails {
     #[serde(rename = "privatekey")]
     pub private_key: String,
@@ -27,14 +28,75 @@ pub struct TransactionDetails {
     pub amount: Decimal,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub metadata: Option<String>,
+    /// Structured metadata, stored next to `metadata`. Must be a JSON object of at most 1024
+    /// characters once serialized.
+    #[serde(
+        rename = "metadataJson",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schema(value_type = Option<Object>)]
+    pub metadata_json: Option<serde_json::Value>,
+    /// Send the transaction even if an identical one was just made
+    #[serde(
+        rename = "allowDuplicate",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub allow_duplicate: Option<bool>,
 }
 
-#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct TransactionResponse {
     pub ok: bool,
     pub transaction: TransactionJson,
 }
 
+/// A transaction's CommonMeta metadata taken apart, every field is left out when it is not there.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct CommonMetaJson {
+    /// The part before the `@` of the recipient name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metaname: Option<String>,
+    /// The recipient name, without the `.kro` suffix
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The full recipient, like `meta@name.kro`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recipient: Option<String>,
+    /// The `key=value` entries
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub entries: BTreeMap<String, String>,
+}
+
+impl CommonMetaJson {
+    pub fn parse(metadata: &str) -> Self {
+        let TransactionNameData { name, metaname } = TransactionNameData::parse(metadata);
+        let recipient = name.as_ref().map(|name| match &metaname {
+            Some(metaname) => format!("{metaname}@{name}.kro"),
+            None => format!("{name}.kro"),
+        });
+
+        let entries = transaction::common_meta_entries(metadata)
+            .into_iter()
+            .map(|(key, value)| (key.to_owned(), value.to_owned()))
+            .collect();
+
+        Self {
+            metaname,
+            name,
+            recipient,
+            entries,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct TransactionMetaResponse {
+    pub ok: bool,
+    pub meta: CommonMetaJson,
+}
+
 #[derive(
     Debug, Default, Clone, PartialEq, PartialOrd, Serialize, Deserialize, ToSchema, IntoParams,
 )]
@@ -46,7 +108,103 @@ pub struct AddressTransactionQuery {
     pub include_mined: Option<bool>,
 }
 
-#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema, IntoParams, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionOrderQuery {
+    /// One of `id`, `from`, `to`, `value` or `time`. Defaults to `time`.
+    pub order_by: Option<String>,
+    /// Either `ASC` or `DESC`. Defaults to `DESC`.
+    pub order: Option<String>,
+}
+
+/// Columns a transaction listing may be sorted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TransactionOrderBy {
+    Id,
+    From,
+    To,
+    Value,
+    #[default]
+    Time,
+}
+
+impl TransactionOrderBy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "id" => Some(Self::Id),
+            "from" => Some(Self::From),
+            "to" => Some(Self::To),
+            "value" => Some(Self::Value),
+            "time" => Some(Self::Time),
+            _ => None,
+        }
+    }
+
+    /// The column to sort by, only ever one of these fixed values so it is safe to put in a query
+    pub fn column(&self) -> &'static str {
+        match self {
+            Self::Id => "id",
+            Self::From => r#""from""#,
+            Self::To => r#""to""#,
+            Self::Value => "amount",
+            Self::Time => "date",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema, IntoParams, Default)]
+pub struct TransactionDirectionQuery {
+    /// `in` for received, `out` for sent or `all` for both. Defaults to `all`.
+    pub direction: Option<String>,
+}
+
+/// Which side of a transaction an address has to be on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TransactionDirection {
+    In,
+    Out,
+    #[default]
+    All,
+}
+
+impl TransactionDirection {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "in" => Some(Self::In),
+            "out" => Some(Self::Out),
+            "all" => Some(Self::All),
+            _ => None,
+        }
+    }
+
+    /// Condition matching transactions in this direction for the address bound to `$1`
+    pub fn filter(&self) -> &'static str {
+        match self {
+            Self::In => r#""to" = $1"#,
+            Self::Out => r#""from" = $1"#,
+            Self::All => r#"("from" = $1 OR "to" = $1)"#,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema, IntoParams)]
+pub struct TransactionsBetweenQuery {
+    /// One of the two addresses
+    pub a: String,
+    /// The other address
+    pub b: String,
+    /// Either `ASC` or `DESC` by date. Defaults to `DESC`.
+    pub order: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema, IntoParams)]
+pub struct TransactionTypeQuery {
+    /// Only list transactions of this type, e.g. `transfer` or `name_purchase`
+    #[serde(rename = "type")]
+    pub transaction_type: Option<TransactionType>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct TransactionJson {
     /// The ID of this transaction.
     pub id: i32,
@@ -59,6 +217,7 @@ pub struct TransactionJson {
 
     /// The amount of Krist transferred in this transaction. Can be 0, notably if the transaction was a name's data change.
     #[schema(value_type = f64, example = 100.00)]
+    #[serde(serialize_with = "super::serialize_amount")]
     pub value: Decimal,
 
     /// The time this transaction this was made, as an ISO-8601 string.
@@ -69,6 +228,10 @@ pub struct TransactionJson {
     pub name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub metadata: Option<String>,
+    /// Structured metadata, if the sender included any
+    #[serde(rename = "metadataJson", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub metadata_json: Option<serde_json::Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sent_metaname: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -88,6 +251,7 @@ impl From<transaction::Model> for TransactionJson {
             value: transaction.amount,
             time: transaction.date.to_rfc3339(),
             metadata: transaction.metadata,
+            metadata_json: transaction.metadata_json,
             sent_metaname: transaction.sent_metaname,
             sent_name: transaction.sent_name,
             transaction_type: transaction.transaction_type,
@@ -95,3 +259,28 @@ impl From<transaction::Model> for TransactionJson {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_common_meta_json() {
+        let meta = CommonMetaJson::parse("shop@store.kro;ref=order-42;msg=thanks");
+        assert_eq!(
+            serde_json::to_value(&meta).unwrap(),
+            json!({
+                "metaname": "shop",
+                "name": "store",
+                "recipient": "shop@store.kro",
+                "entries": { "msg": "thanks", "ref": "order-42" },
+            })
+        );
+
+        // Plain text that isn't CommonMeta comes out as an empty object
+        let meta = CommonMetaJson::parse("just a note");
+        assert_eq!(serde_json::to_value(&meta).unwrap(), json!({}));
+    }
+}
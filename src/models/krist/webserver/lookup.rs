@@ -3,6 +3,9 @@ pub mod names;
 pub mod transactions;
 
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::models::krist::{addresses::AddressJson, names::NameJson};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -51,3 +54,19 @@ pub struct LookupQuery {
     pub order_by: Option<String>,
     pub order: Option<String>,
 }
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema, IntoParams)]
+pub struct ResolveQuery {
+    /// An address or a name, with or without the `.kro` suffix
+    pub q: String,
+}
+
+/// The address and/or name a query resolved to. Address-shaped queries are also valid names, so both can be set.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ResolveResponse {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<AddressJson>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<NameJson>,
+}
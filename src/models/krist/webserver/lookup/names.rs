@@ -1,11 +1,14 @@
 use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 use crate::models::krist::{names::NameJson, transactions::TransactionJson};
 
 /// All the names owned by the given address(es), or the whole network if no addresses are specified.
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Also used for looking up specific names, keyed by name.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[schema(as = NameLookupResponse)]
 pub struct LookupResponse {
     pub ok: bool,
     pub found: usize,
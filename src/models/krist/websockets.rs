@@ -1,9 +1,13 @@
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 
-use super::{addresses::AddressJson, motd::DetailedMotd, transactions::TransactionJson};
+use super::{
+    addresses::AddressJson,
+    motd::{Constants, DetailedMotd},
+    transactions::TransactionJson,
+};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct WebSocketMessage {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ok: Option<bool>,
@@ -13,7 +17,7 @@ pub struct WebSocketMessage {
     pub r#type: WebSocketMessageInner,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "snake_case", tag = "type")]
 pub enum WebSocketMessageInner {
     Hello {
@@ -34,6 +38,9 @@ pub enum WebSocketMessageInner {
     Event {
         #[serde(flatten)]
         event: WebSocketEvent,
+        /// Set on transaction and name events, pass the last one seen to `replay` after reconnecting
+        #[serde(rename = "eventId", default, skip_serializing_if = "Option::is_none")]
+        event_id: Option<u64>,
     },
     Work,
     MakeTransaction {
@@ -49,6 +56,14 @@ pub enum WebSocketMessageInner {
 
         /// Optional metadata to include in the transaction.
         metadata: Option<String>,
+
+        /// Send the transaction even if an identical one was just made.
+        #[serde(
+            rename = "allowDuplicate",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
+        allow_duplicate: Option<bool>,
     },
 
     GetValidSubscriptionLevels,
@@ -76,14 +91,35 @@ pub enum WebSocketMessageInner {
     Unsubscribe {
         event: String,
     },
+
+    /// Replace the list of addresses whose transactions the session receives.
+    Watch {
+        addresses: Vec<String>,
+    },
+
+    Transactions {
+        limit: Option<i64>,
+        offset: Option<i64>,
+
+        /// When supplied, exclude mined transactions from the page.
+        #[serde(rename = "excludeMined")]
+        exclude_mined: Option<bool>,
+    },
+
+    /// Resend the buffered transaction and name events after `since`, the last `eventId` seen.
+    Replay {
+        since: u64,
+    },
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(tag = "responding_to", rename_all = "snake_case")]
 pub enum WebSocketMessageResponse {
     Work {
         /// The current Krist work (difficulty)
-        work: usize,
+        work: i64,
+        /// The same constants as in the MOTD
+        constants: Constants,
     },
 
     MakeTransaction {
@@ -128,9 +164,27 @@ pub enum WebSocketMessageResponse {
     Unsubscribe {
         subscription_level: Vec<String>,
     },
+
+    Watch {
+        /// The addresses the session watches now
+        addresses: Vec<String>,
+    },
+
+    Transactions {
+        /// The count of results.
+        count: usize,
+        /// The total amount of transactions
+        total: usize,
+        transactions: Vec<TransactionJson>,
+    },
+
+    Replay {
+        /// The missed events the session would have received, oldest first
+        events: Vec<WebSocketMessage>,
+    },
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(tag = "event", rename_all = "camelCase")]
 pub enum WebSocketEvent {
     Block {
@@ -143,6 +197,12 @@ pub enum WebSocketEvent {
     Name {
         name: super::names::NameJson,
     },
+    /// The new balance of an address, only sent to that address's own sessions
+    Balance {
+        address: String,
+        #[serde(serialize_with = "super::serialize_amount")]
+        balance: Decimal,
+    },
 }
 
 impl WebSocketMessage {
@@ -150,7 +210,10 @@ impl WebSocketMessage {
         WebSocketMessage {
             ok: None,
             id: None,
-            r#type: WebSocketMessageInner::Event { event },
+            r#type: WebSocketMessageInner::Event {
+                event,
+                event_id: None,
+            },
         }
     }
 }
@@ -203,6 +266,23 @@ impl WebSocketMessage {
 // }
 
 impl WebSocketMessageInner {
+    /// Message types that a client is allowed to send to the server
+    pub const CLIENT_MESSAGE_TYPES: &'static [&'static str] = &[
+        "address",
+        "login",
+        "logout",
+        "me",
+        "subscribe",
+        "get_subscription_level",
+        "get_valid_subscription_levels",
+        "unsubscribe",
+        "watch",
+        "make_transaction",
+        "work",
+        "transactions",
+        "replay",
+    ];
+
     /// Return the enum member name as a str
     pub fn member_str(&self) -> &'static str {
         match self {
@@ -215,8 +295,11 @@ impl WebSocketMessageInner {
             WebSocketMessageInner::GetSubscriptionLevel => "get_subscription_level",
             WebSocketMessageInner::GetValidSubscriptionLevels => "get_valid_subscription_levels",
             WebSocketMessageInner::Unsubscribe { .. } => "unsubscribe",
+            WebSocketMessageInner::Watch { .. } => "watch",
             WebSocketMessageInner::MakeTransaction { .. } => "make_transaction",
             WebSocketMessageInner::Work => "work",
+            WebSocketMessageInner::Transactions { .. } => "transactions",
+            WebSocketMessageInner::Replay { .. } => "replay",
             WebSocketMessageInner::Hello { .. } => "hello",
             WebSocketMessageInner::Error { .. } => "error",
             WebSocketMessageInner::Response { .. } => "response",
@@ -1,3 +1,6 @@
+pub mod players;
 pub mod responses;
+pub mod stats;
+pub mod transactions;
 pub mod wallets;
 pub mod websockets;
@@ -0,0 +1,43 @@
+//! All kromer player related models
+
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::database::player;
+
+/// The Minecraft account behind a player, without the wallets it owns
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct Player {
+    pub uuid: Uuid,
+    pub name: String,
+}
+
+impl From<player::Model> for Player {
+    fn from(value: player::Model) -> Self {
+        Self {
+            uuid: value.id,
+            name: value.name,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_player_json() {
+        let uuid = Uuid::new_v4();
+        let player = Player::from(player::Model {
+            id: uuid,
+            name: "Steve".into(),
+            owned_wallets: vec![1, 2],
+        });
+
+        assert_eq!(
+            serde_json::to_value(&player).unwrap(),
+            serde_json::json!({"uuid": uuid, "name": "Steve"})
+        );
+    }
+}
@@ -3,6 +3,9 @@ use utoipa::ToSchema;
 
 #[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
 pub struct ApiResponse<'a, T: Serialize + ToSchema> {
+    /// False when the response carries an error, like Krist's responses
+    pub ok: bool,
+
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub data: Option<T>,
 
@@ -42,6 +45,7 @@ pub struct ErrorDetail<'a> {
 impl<'a, T: Serialize + ToSchema> Default for ApiResponse<'a, T> {
     fn default() -> Self {
         Self {
+            ok: true,
             data: None,
             meta: None,
             error: None,
@@ -0,0 +1,16 @@
+//! Aggregate stats for the status page
+
+use rust_decimal::Decimal;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct Stats {
+    pub transactions: usize,
+    pub names: usize,
+    pub wallets: usize,
+    #[schema(value_type = f64, example = 125000.00)]
+    pub money_supply: Decimal,
+    /// Websocket sessions connected right now
+    pub sessions: usize,
+}
@@ -0,0 +1,179 @@
+//! All kromer transaction related models
+
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::database::transaction;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Ndjson,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, IntoParams)]
+pub struct ExportQuery {
+    /// Comma separated list of addresses to export the transactions of
+    pub addresses: String,
+    /// Either `csv` or `ndjson`, defaults to `csv`
+    pub format: Option<ExportFormat>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, IntoParams)]
+pub struct MetadataQuery {
+    /// A JSON object, transactions whose structured metadata contains it are returned
+    pub contains: String,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+impl MetadataQuery {
+    /// Parse `contains`, which has to be a JSON object.
+    pub fn contains_object(&self) -> Option<serde_json::Value> {
+        serde_json::from_str(&self.contains)
+            .ok()
+            .filter(serde_json::Value::is_object)
+    }
+}
+
+/// A single exported transaction
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExportRow {
+    pub id: i32,
+    pub date: DateTime<Utc>,
+    pub from: Option<String>,
+    pub to: String,
+    pub amount: Decimal,
+    #[serde(rename = "type")]
+    pub transaction_type: &'static str,
+    pub metadata: Option<String>,
+}
+
+impl ExportRow {
+    pub const CSV_HEADER: &'static str = "id,date,from,to,amount,type,metadata\n";
+
+    /// Format the row as a single CSV line, including the trailing newline
+    pub fn to_csv_line(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{}\n",
+            self.id,
+            self.date.to_rfc3339(),
+            csv_field(self.from.as_deref().unwrap_or_default()),
+            csv_field(&self.to),
+            self.amount,
+            self.transaction_type,
+            csv_field(self.metadata.as_deref().unwrap_or_default()),
+        )
+    }
+
+    /// Format the row as a single line of JSON, including the trailing newline
+    pub fn to_ndjson_line(&self) -> String {
+        let mut line = serde_json::to_string(self).expect("Failed to serialize export row");
+        line.push('\n');
+
+        line
+    }
+}
+
+impl From<transaction::Model> for ExportRow {
+    fn from(value: transaction::Model) -> Self {
+        Self {
+            id: value.id,
+            date: value.date,
+            from: value.from,
+            to: value.to,
+            amount: value.amount,
+            transaction_type: value.transaction_type.into(),
+            metadata: value.metadata,
+        }
+    }
+}
+
+/// Quote a CSV field when it contains characters that would otherwise break the row
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use rust_decimal::dec;
+
+    use super::*;
+
+    #[test]
+    fn test_metadata_query_must_be_object() {
+        let query = |contains: &str| MetadataQuery {
+            contains: contains.to_owned(),
+            limit: None,
+            offset: None,
+        };
+
+        assert_eq!(
+            query(r#"{"order":{"id":42}}"#).contains_object(),
+            Some(serde_json::json!({"order": {"id": 42}}))
+        );
+        assert_eq!(query("[1, 2]").contains_object(), None);
+        assert_eq!(query("order=42").contains_object(), None);
+    }
+
+    #[test]
+    fn test_csv_export() {
+        let rows = [
+            ExportRow {
+                id: 1,
+                date: Utc.with_ymd_and_hms(2025, 7, 1, 12, 0, 0).unwrap(),
+                from: Some("kre3w0i79j".to_owned()),
+                to: "k5ztameslf".to_owned(),
+                amount: dec!(10.5),
+                transaction_type: "transfer",
+                metadata: Some("message=hi, \"friend\"".to_owned()),
+            },
+            ExportRow {
+                id: 2,
+                date: Utc.with_ymd_and_hms(2025, 7, 2, 8, 30, 0).unwrap(),
+                from: None,
+                to: "kre3w0i79j".to_owned(),
+                amount: dec!(100),
+                transaction_type: "mined",
+                metadata: None,
+            },
+        ];
+
+        let csv: String = std::iter::once(ExportRow::CSV_HEADER.to_owned())
+            .chain(rows.iter().map(ExportRow::to_csv_line))
+            .collect();
+
+        assert_eq!(
+            csv,
+            "id,date,from,to,amount,type,metadata\n\
+             1,2025-07-01T12:00:00+00:00,kre3w0i79j,k5ztameslf,10.5,transfer,\"message=hi, \"\"friend\"\"\"\n\
+             2,2025-07-02T08:30:00+00:00,,kre3w0i79j,100,mined,\n"
+        );
+    }
+
+    #[test]
+    fn test_ndjson_export() {
+        let row = ExportRow {
+            id: 3,
+            date: Utc.with_ymd_and_hms(2025, 7, 3, 0, 0, 0).unwrap(),
+            from: Some("kre3w0i79j".to_owned()),
+            to: "k5ztameslf".to_owned(),
+            amount: dec!(1),
+            transaction_type: "transfer",
+            metadata: None,
+        };
+
+        let line = row.to_ndjson_line();
+        assert!(line.ends_with('\n'));
+        assert!(line.contains(r#""type":"transfer""#));
+    }
+}
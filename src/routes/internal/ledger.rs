@@ -0,0 +1,23 @@
+use actix_web::{HttpResponse, get, web};
+
+use crate::database::ledger::{self, SYSTEM_ADDRESSES};
+use crate::{AppState, errors::KromerError};
+
+#[get("/reconcile")]
+async fn ledger_reconcile(state: web::Data<AppState>) -> Result<HttpResponse, KromerError> {
+    let report = ledger::reconcile(&state.pool, &SYSTEM_ADDRESSES).await?;
+
+    if !report.ok {
+        tracing::warn!(
+            "Ledger reconciliation failed, supply is off by {} and {} wallets do not match their totals",
+            report.discrepancy,
+            report.mismatched_wallets.len()
+        );
+    }
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::scope("/ledger").service(ledger_reconcile));
+}
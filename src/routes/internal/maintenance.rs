@@ -0,0 +1,44 @@
+use std::sync::atomic::Ordering;
+
+use actix_web::{HttpResponse, get, post, web};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{AppState, errors::KromerError};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct MaintenanceReq {
+    pub enabled: bool,
+}
+
+#[get("")]
+async fn maintenance_get(state: web::Data<AppState>) -> Result<HttpResponse, KromerError> {
+    Ok(HttpResponse::Ok().json(json!({
+        "maintenance": state.maintenance.load(Ordering::SeqCst),
+    })))
+}
+
+#[post("")]
+async fn maintenance_set(
+    state: web::Data<AppState>,
+    data: web::Json<MaintenanceReq>,
+) -> Result<HttpResponse, KromerError> {
+    state.set_maintenance(data.enabled);
+
+    tracing::warn!(
+        "Maintenance mode {}",
+        if data.enabled { "enabled" } else { "disabled" }
+    );
+
+    Ok(HttpResponse::Ok().json(json!({
+        "maintenance": data.enabled,
+    })))
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/maintenance")
+            .service(maintenance_get)
+            .service(maintenance_set),
+    );
+}
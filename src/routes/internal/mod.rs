@@ -1,9 +1,17 @@
+pub mod ledger;
+pub mod maintenance;
+pub mod supply;
 pub mod wallet;
+pub mod webhooks;
 pub mod ws;
 
 use actix_web::web;
 
 pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.configure(ledger::config);
+    cfg.configure(maintenance::config);
+    cfg.configure(supply::config);
     cfg.configure(wallet::config);
+    cfg.configure(webhooks::config);
     cfg.configure(ws::config);
 }
@@ -0,0 +1,310 @@
+use actix_web::{HttpResponse, post, web};
+use rust_decimal::{Decimal, dec};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::database::ledger::WELFARE_ADDRESS;
+use crate::database::transaction::{
+    Model as Transaction, TransactionCreateData, TransactionType, check_transfer_limits,
+    normalize_amount,
+};
+use crate::database::wallet::Model as Wallet;
+use crate::errors::transaction::TransactionError;
+use crate::errors::wallet::WalletError;
+use crate::models::krist::transactions::TransactionJson;
+use crate::models::krist::websockets::{WebSocketEvent, WebSocketMessage};
+use crate::websockets::WebSocketServer;
+use crate::{AppState, errors::KromerError};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct MintReq {
+    pub address: String,
+    pub amount: Decimal,
+    pub metadata: Option<String>,
+}
+
+impl MintReq {
+    /// Validate the request and turn it into the transaction that records the mint.
+    fn into_creation_data(self) -> Result<TransactionCreateData, KromerError> {
+        let amount = normalize_amount(self.amount)?;
+
+        if amount <= dec!(0.00) {
+            return Err(KromerError::Validation("Invalid amount".into()));
+        }
+
+        check_transfer_limits(amount, self.metadata.as_deref(), None)?;
+
+        Ok(TransactionCreateData {
+            from: WELFARE_ADDRESS.clone(),
+            to: self.address,
+            amount,
+            metadata: self.metadata,
+            transaction_type: TransactionType::Transfer,
+            ..Default::default()
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct BurnReq {
+    pub address: String,
+    pub amount: Decimal,
+}
+
+/// Check that a wallet holding `balance` can have `amount` burned, and return what is left.
+fn burn_from(balance: Decimal, amount: Decimal) -> Result<Decimal, KromerError> {
+    let amount = normalize_amount(amount)?;
+
+    if amount <= dec!(0.00) {
+        return Err(KromerError::Validation("Invalid amount".into()));
+    }
+
+    if balance < amount {
+        return Err(KromerError::Transaction(
+            TransactionError::InsufficientFunds,
+        ));
+    }
+
+    Ok(balance - amount)
+}
+
+#[post("/mint")]
+async fn supply_mint(
+    state: web::Data<AppState>,
+    server: web::Data<WebSocketServer>,
+    data: web::Json<MintReq>,
+) -> Result<HttpResponse, KromerError> {
+    let pool = &state.pool;
+    let creation_data = data.into_inner().into_creation_data()?;
+
+    let mut tx = pool.begin().await?;
+
+    let wallet = Wallet::fetch_by_address(&mut *tx, &creation_data.to)
+        .await?
+        .ok_or_else(|| KromerError::Wallet(WalletError::NotFound(creation_data.to.clone())))?;
+    let updated_wallet = wallet
+        .update_balance(&mut *tx, creation_data.amount)
+        .await?;
+
+    let transaction = Transaction::create_no_update(&mut *tx, creation_data).await?;
+
+    tx.commit().await?;
+
+    tracing::info!(
+        "Minted {} into {} with transaction ID {}",
+        transaction.amount,
+        transaction.to,
+        transaction.id
+    );
+
+    let transaction: TransactionJson = transaction.into();
+    let event = WebSocketMessage::new_event(WebSocketEvent::Transaction {
+        transaction: transaction.clone(),
+    });
+    server.broadcast_event(event).await;
+    server
+        .broadcast_balances(pool, &[&updated_wallet.address])
+        .await;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "wallet": updated_wallet,
+        "transaction": transaction,
+    })))
+}
+
+#[post("/burn")]
+async fn supply_burn(
+    state: web::Data<AppState>,
+    server: web::Data<WebSocketServer>,
+    data: web::Json<BurnReq>,
+) -> Result<HttpResponse, KromerError> {
+    let pool = &state.pool;
+    let data = data.into_inner();
+    let amount = normalize_amount(data.amount)?;
+
+    let mut tx = pool.begin().await?;
+
+    // Locked so a transfer committing meanwhile can't spend what is about to be burned
+    let wallet = Wallet::fetch_by_address_for_update(&mut *tx, &data.address)
+        .await?
+        .ok_or_else(|| KromerError::Wallet(WalletError::NotFound(data.address.clone())))?;
+    burn_from(wallet.balance, amount)?;
+
+    let updated_wallet = wallet.update_balance(&mut *tx, -amount).await?;
+
+    let creation_data = TransactionCreateData {
+        from: data.address,
+        to: WELFARE_ADDRESS.clone(),
+        amount,
+        transaction_type: TransactionType::Transfer,
+        ..Default::default()
+    };
+    let transaction = Transaction::create_no_update(&mut *tx, creation_data).await?;
+
+    tx.commit().await?;
+
+    tracing::info!(
+        "Burned {} from {} with transaction ID {}",
+        transaction.amount,
+        updated_wallet.address,
+        transaction.id
+    );
+
+    let transaction: TransactionJson = transaction.into();
+    let event = WebSocketMessage::new_event(WebSocketEvent::Transaction { transaction });
+    server.broadcast_event(event).await;
+    server
+        .broadcast_balances(pool, &[&updated_wallet.address])
+        .await;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "balance": updated_wallet.balance,
+    })))
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(supply_mint).service(supply_burn);
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        App,
+        test::{TestRequest, call_service, init_service, read_body_json},
+    };
+
+    use super::*;
+    use crate::database::ModelExt;
+    use crate::database::ledger::SYSTEM_ADDRESSES;
+
+    fn mint(amount: Decimal) -> MintReq {
+        MintReq {
+            address: "kre3w0i79j".into(),
+            amount,
+            metadata: Some("event payout".into()),
+        }
+    }
+
+    #[test]
+    fn test_mint_creation_data() {
+        let data = mint(dec!(25.5))
+            .into_creation_data()
+            .expect("mint should be valid");
+
+        // Coming from a system address is what makes the ledger count this as new supply.
+        assert!(SYSTEM_ADDRESSES.contains(&data.from));
+        assert_eq!(data.to, "kre3w0i79j");
+        assert_eq!(data.amount, dec!(25.50));
+        assert_eq!(data.transaction_type, TransactionType::Transfer);
+        assert_eq!(data.metadata.as_deref(), Some("event payout"));
+
+        assert!(mint(dec!(-5)).into_creation_data().is_err());
+        assert!(mint(dec!(0)).into_creation_data().is_err());
+    }
+
+    #[sqlx::test]
+    async fn test_mint_adds_to_supply(pool: sqlx::PgPool) {
+        Wallet::create_wallet(&pool, "kre3w0i79j", "hash", Some(dec!(10)))
+            .await
+            .unwrap();
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new(pool.clone())))
+                .app_data(web::Data::new(WebSocketServer::new()))
+                .configure(config),
+        )
+        .await;
+        let supply_before = Wallet::money_supply(&pool, &WELFARE_ADDRESS).await.unwrap();
+
+        let req = TestRequest::post()
+            .uri("/mint")
+            .set_json(mint(dec!(25.5)))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert!(resp.status().is_success(), "{}", resp.status());
+        let body: serde_json::Value = read_body_json(resp).await;
+        let id = body["transaction"]["id"].as_i64().unwrap() as i32;
+
+        let supply_after = Wallet::money_supply(&pool, &WELFARE_ADDRESS).await.unwrap();
+        assert_eq!(supply_after - supply_before, dec!(25.50));
+
+        let transaction = Transaction::fetch_by_id(&pool, id)
+            .await
+            .unwrap()
+            .expect("the mint should be recorded");
+        assert_eq!(transaction.from.as_deref(), Some(WELFARE_ADDRESS.as_str()));
+        assert_eq!(transaction.to, "kre3w0i79j");
+        assert_eq!(transaction.amount, dec!(25.50));
+        assert_eq!(transaction.metadata.as_deref(), Some("event payout"));
+
+        let wallet = Wallet::fetch_by_address(&pool, "kre3w0i79j")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(wallet.balance, dec!(35.50));
+        assert_eq!(wallet.total_in, dec!(25.50));
+    }
+
+    #[test]
+    fn test_burn_sufficient_balance() {
+        let remaining = burn_from(dec!(100.00), dec!(40)).expect("burn should succeed");
+        assert_eq!(remaining, dec!(60.00));
+
+        // Burning everything is fine
+        assert_eq!(burn_from(dec!(40.00), dec!(40)).ok(), Some(dec!(0.00)));
+    }
+
+    #[test]
+    fn test_burn_insufficient_balance() {
+        assert!(matches!(
+            burn_from(dec!(10.00), dec!(10.01)),
+            Err(KromerError::Transaction(
+                TransactionError::InsufficientFunds
+            ))
+        ));
+        assert!(matches!(
+            burn_from(dec!(10.00), dec!(-5)),
+            Err(KromerError::Validation(_))
+        ));
+    }
+
+    #[sqlx::test]
+    async fn test_burn_waits_for_concurrent_spend(pool: sqlx::PgPool) {
+        Wallet::create_wallet(&pool, "kalice0000", "alice", Some(dec!(100)))
+            .await
+            .unwrap();
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new(pool.clone())))
+                .app_data(web::Data::new(WebSocketServer::new()))
+                .configure(config),
+        )
+        .await;
+
+        // A transfer out of the wallet that hasn't committed yet
+        let mut spend = pool.begin().await.unwrap();
+        Wallet::fetch_by_address_for_update(&mut *spend, "kalice0000")
+            .await
+            .unwrap()
+            .unwrap()
+            .update_balance(&mut *spend, dec!(-80))
+            .await
+            .unwrap();
+
+        let req = TestRequest::post()
+            .uri("/burn")
+            .set_json(json!({ "address": "kalice0000", "amount": 50 }))
+            .to_request();
+        let (resp, _) = tokio::join!(call_service(&app, req), async {
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            spend.commit().await.unwrap();
+        });
+        assert!(resp.status().is_client_error(), "{}", resp.status());
+
+        let alice = Wallet::fetch_by_address(&pool, "kalice0000")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(alice.balance, dec!(20.00));
+    }
+}
@@ -4,15 +4,20 @@ use serde::{Deserialize, Serialize};
 use serde_json::json;
 use uuid::Uuid;
 
+use crate::database::ledger::WELFARE_ADDRESS;
 use crate::database::player::Model as Player;
-use crate::database::transaction::{Model as Transaction, TransactionCreateData, TransactionType};
+use crate::database::transaction::{
+    Model as Transaction, TransactionCreateData, TransactionType, normalize_amount,
+};
 use crate::database::wallet::Model as Wallet;
 
 use crate::database::ModelExt;
 use crate::errors::player::PlayerError;
 use crate::errors::wallet::WalletError;
 use crate::models::krist::addresses::AddressCreationResponse;
+use crate::models::kromer::players::Player as PlayerResponse;
 use crate::utils::crypto::generate_random_password;
+use crate::websockets::WebSocketServer;
 use crate::{AppState, errors::KromerError};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -44,7 +49,18 @@ async fn wallet_create(
     let wallet_verification_response = Wallet::verify_address(pool, &private_key).await?;
 
     let wallet = wallet_verification_response.model;
-    let updated_wallet = wallet.set_balance(&mut *tx, dec!(100)).await?;
+
+    // Credit the starting balance like any other welfare payment, so it shows up in the wallet's
+    // totals and in the ledger.
+    let updated_wallet = wallet.update_balance(&mut *tx, dec!(100)).await?;
+    let creation_data = TransactionCreateData {
+        from: WELFARE_ADDRESS.clone(),
+        to: updated_wallet.address.clone(),
+        amount: dec!(100),
+        transaction_type: TransactionType::Mined,
+        ..Default::default()
+    };
+    Transaction::create_no_update(&mut *tx, creation_data).await?;
 
     let _updated_player = player
         .add_wallet_to_owned(&mut *tx, &updated_wallet)
@@ -63,11 +79,12 @@ async fn wallet_create(
 #[post("/give-money")]
 async fn wallet_give_money(
     state: web::Data<AppState>,
+    server: web::Data<WebSocketServer>,
     data: web::Json<GiveMoneyReq>,
 ) -> Result<HttpResponse, KromerError> {
     let pool = &state.pool;
     let data = data.into_inner();
-    let amount = data.amount.round_dp(2);
+    let amount = normalize_amount(data.amount)?;
 
     if amount <= dec!(0.00) {
         return Err(KromerError::Validation("Invalid amount".into()));
@@ -81,7 +98,7 @@ async fn wallet_give_money(
     let updated_wallet = wallet.update_balance(&mut *tx, amount).await?;
 
     let creation_data = TransactionCreateData {
-        from: "serverwelf".into(),
+        from: WELFARE_ADDRESS.clone(),
         to: data.address,
         amount,
         transaction_type: TransactionType::Mined,
@@ -95,11 +112,87 @@ async fn wallet_give_money(
 
     tx.commit().await?;
 
+    server
+        .broadcast_balances(pool, &[&updated_wallet.address])
+        .await;
+
     Ok(HttpResponse::Ok().json(json!({
         "wallet": updated_wallet
     })))
 }
 
+#[post("/{address}/lock")]
+async fn wallet_lock(
+    state: web::Data<AppState>,
+    address: web::Path<String>,
+) -> Result<HttpResponse, KromerError> {
+    set_wallet_locked(&state, &address, true).await
+}
+
+#[post("/{address}/unlock")]
+async fn wallet_unlock(
+    state: web::Data<AppState>,
+    address: web::Path<String>,
+) -> Result<HttpResponse, KromerError> {
+    set_wallet_locked(&state, &address, false).await
+}
+
+async fn set_wallet_locked(
+    state: &AppState,
+    address: &str,
+    locked: bool,
+) -> Result<HttpResponse, KromerError> {
+    let pool = &state.pool;
+
+    let wallet = Wallet::fetch_by_address(pool, address)
+        .await?
+        .ok_or_else(|| KromerError::Wallet(WalletError::NotFound(address.to_owned())))?;
+    let updated_wallet = wallet.set_locked(pool, locked).await?;
+
+    tracing::info!("Set locked to {locked} for wallet {address}");
+
+    Ok(HttpResponse::Ok().json(json!({
+        "wallet": updated_wallet
+    })))
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct TransferOwnerReq {
+    /// UUID of the player receiving the wallet
+    pub uuid: Uuid,
+}
+
+#[post("/{address}/transfer-owner")]
+async fn wallet_transfer_owner(
+    state: web::Data<AppState>,
+    address: web::Path<String>,
+    data: web::Json<TransferOwnerReq>,
+) -> Result<HttpResponse, KromerError> {
+    let pool = &state.pool;
+    let address = address.into_inner();
+    let data = data.into_inner();
+
+    let mut tx = pool.begin().await?;
+
+    let wallet = Wallet::fetch_by_address(&mut *tx, &address)
+        .await?
+        .ok_or_else(|| KromerError::Wallet(WalletError::NotFound(address.clone())))?;
+    let player = Player::fetch_by_id(&mut *tx, data.uuid)
+        .await?
+        .ok_or_else(|| KromerError::Player(PlayerError::NotFound))?;
+
+    let player = player.take_over_wallet(&mut tx, &wallet).await?;
+
+    tx.commit().await?;
+
+    tracing::info!("Transferred wallet {address} to player {}", player.id);
+
+    Ok(HttpResponse::Ok().json(json!({
+        "wallet": wallet.address,
+        "owner": PlayerResponse::from(player),
+    })))
+}
+
 #[get("/by-player/{uuid}")]
 async fn wallet_get_by_uuid(
     state: web::Data<AppState>,
@@ -130,4 +223,147 @@ pub fn config(cfg: &mut web::ServiceConfig) {
             .service(wallet_give_money)
             .service(wallet_get_by_uuid),
     );
+    cfg.service(
+        web::scope("/wallets")
+            .service(wallet_lock)
+            .service(wallet_transfer_owner)
+            .service(wallet_unlock),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        App,
+        test::{TestRequest, call_service, init_service, read_body_json},
+    };
+    use serde_json::Value;
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn test_lock_paths(pool: sqlx::PgPool) {
+        Wallet::create_wallet(&pool, "kalice0000", "alice", None)
+            .await
+            .unwrap();
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new(pool.clone())))
+                .configure(config),
+        )
+        .await;
+
+        let req = TestRequest::post()
+            .uri("/wallets/kalice0000/lock")
+            .to_request();
+        assert!(call_service(&app, req).await.status().is_success());
+        let wallet = Wallet::fetch_by_address(&pool, "kalice0000")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(wallet.locked);
+
+        let req = TestRequest::post()
+            .uri("/wallets/kalice0000/unlock")
+            .to_request();
+        assert!(call_service(&app, req).await.status().is_success());
+        let wallet = Wallet::fetch_by_address(&pool, "kalice0000")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!wallet.locked);
+    }
+
+    #[sqlx::test]
+    async fn test_locked_wallet_receives_but_cannot_send(pool: sqlx::PgPool) {
+        let mut addresses = Vec::new();
+        for key in ["alice-key", "bob-key"] {
+            let wallet = Wallet::verify_address(&pool, key).await.unwrap().model;
+            wallet.update_balance(&pool, dec!(50)).await.unwrap();
+            addresses.push(wallet.address);
+        }
+        let (alice, bob) = (&addresses[0], &addresses[1]);
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new(pool.clone())))
+                .app_data(web::Data::new(WebSocketServer::new()))
+                .configure(config)
+                .configure(crate::routes::krist::transactions::config),
+        )
+        .await;
+        let send = |key: &str, to: &str| {
+            TestRequest::post()
+                .uri("/transactions")
+                .set_json(json!({ "privatekey": key, "to": to, "amount": 10 }))
+                .to_request()
+        };
+
+        let req = TestRequest::post()
+            .uri(&format!("/wallets/{alice}/lock"))
+            .to_request();
+        assert!(call_service(&app, req).await.status().is_success());
+
+        let resp = call_service(&app, send("alice-key", bob)).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::FORBIDDEN);
+        let body: Value = read_body_json(resp).await;
+        assert_eq!(body["error"], "address_locked");
+
+        let resp = call_service(&app, send("bob-key", alice)).await;
+        assert!(resp.status().is_success(), "{}", resp.status());
+
+        let balance = async |address: &str| {
+            Wallet::fetch_by_address(&pool, address)
+                .await
+                .unwrap()
+                .unwrap()
+                .balance
+        };
+        assert_eq!(balance(alice).await, dec!(60));
+        assert_eq!(balance(bob).await, dec!(40));
+    }
+
+    #[sqlx::test]
+    async fn test_transfer_owner(pool: sqlx::PgPool) {
+        Wallet::create_wallet(&pool, "kshop00000", "shop", Some(dec!(25)))
+            .await
+            .unwrap();
+        let bob = Player::create(&pool, Uuid::new_v4(), "bob".to_owned())
+            .await
+            .unwrap();
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new(pool.clone())))
+                .configure(config),
+        )
+        .await;
+
+        let req = TestRequest::post()
+            .uri("/wallets/kshop00000/transfer-owner")
+            .set_json(json!({ "uuid": bob.id }))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert!(resp.status().is_success());
+        let body: Value = read_body_json(resp).await;
+        assert_eq!(body["wallet"], "kshop00000");
+
+        let owner = Player::fetch_by_wallet_address(&pool, "kshop00000")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(owner.id, bob.id);
+
+        let req = TestRequest::post()
+            .uri("/wallets/kshop00000/transfer-owner")
+            .set_json(json!({ "uuid": Uuid::new_v4() }))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::NOT_FOUND);
+
+        // A failed transfer leaves the current owner alone
+        let owner = Player::fetch_by_wallet_address(&pool, "kshop00000")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(owner.id, bob.id);
+    }
 }
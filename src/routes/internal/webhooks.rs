@@ -0,0 +1,114 @@
+use actix_web::{HttpResponse, post, web};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::database::webhook::Model as Webhook;
+use crate::webhooks::WEBHOOK_EVENTS;
+use crate::{AppState, errors::KromerError};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct WebhookCreateReq {
+    pub url: String,
+    pub events: Vec<String>,
+    pub secret: String,
+}
+
+impl WebhookCreateReq {
+    /// Check the URL is one we can POST to and that every event is one webhooks are sent.
+    fn validate(mut self) -> Result<Self, KromerError> {
+        let url = reqwest::Url::parse(&self.url)
+            .map_err(|_| KromerError::Validation("Invalid webhook url".into()))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(KromerError::Validation(
+                "Webhook url must be http or https".into(),
+            ));
+        }
+
+        if self.secret.is_empty() {
+            return Err(KromerError::Validation("Webhook secret is empty".into()));
+        }
+
+        self.events.sort();
+        self.events.dedup();
+        if self.events.is_empty() {
+            return Err(KromerError::Validation("No webhook events given".into()));
+        }
+        if let Some(event) = self
+            .events
+            .iter()
+            .find(|event| !WEBHOOK_EVENTS.contains(&event.as_str()))
+        {
+            return Err(KromerError::Validation(format!(
+                "Unknown webhook event {event}"
+            )));
+        }
+
+        Ok(self)
+    }
+}
+
+#[post("")]
+async fn webhook_create(
+    state: web::Data<AppState>,
+    data: web::Json<WebhookCreateReq>,
+) -> Result<HttpResponse, KromerError> {
+    let data = data.into_inner().validate()?;
+
+    let webhook = Webhook::create(&state.pool, &data.url, &data.events, &data.secret).await?;
+
+    tracing::info!(
+        "Registered webhook {} for {:?} at {}",
+        webhook.id,
+        webhook.events,
+        webhook.url
+    );
+
+    // The secret stays with whoever registered it.
+    Ok(HttpResponse::Ok().json(json!({
+        "id": webhook.id,
+        "url": webhook.url,
+        "events": webhook.events,
+        "created_at": webhook.created_at,
+    })))
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::scope("/webhooks").service(webhook_create));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(url: &str, events: &[&str]) -> WebhookCreateReq {
+        WebhookCreateReq {
+            url: url.into(),
+            events: events.iter().map(|event| event.to_string()).collect(),
+            secret: "shop-secret".into(),
+        }
+    }
+
+    #[test]
+    fn test_webhook_validation() {
+        let req = request(
+            "https://shop.example/kromer",
+            &["transactions", "names", "transactions"],
+        )
+        .validate()
+        .expect("request should be valid");
+        assert_eq!(req.events, vec!["names", "transactions"]);
+
+        assert!(
+            request("ftp://shop.example", &["transactions"])
+                .validate()
+                .is_err()
+        );
+        assert!(request("not a url", &["transactions"]).validate().is_err());
+        assert!(request("https://shop.example", &[]).validate().is_err());
+        assert!(
+            request("https://shop.example", &["blocks"])
+                .validate()
+                .is_err()
+        );
+    }
+}
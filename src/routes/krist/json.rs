@@ -0,0 +1,44 @@
+use actix_web::{FromRequest, HttpRequest, dev::Payload, web};
+use futures_util::future::LocalBoxFuture;
+use serde::de::DeserializeOwned;
+
+use crate::errors::{json::JsonFieldError, krist::KristError};
+
+/// A JSON request body, like [`web::Json`] but a body that doesn't fit `T` is reported with the
+/// path to the offending field, so a bad value is named as well as a missing one.
+///
+/// Everything before deserializing, like the content type and size limit, is still up to the
+/// scope's [`web::JsonConfig`].
+#[derive(Debug)]
+pub struct Json<T>(pub T);
+
+impl<T> Json<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> std::ops::Deref for Json<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: DeserializeOwned + 'static> FromRequest for Json<T> {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let body = web::Json::<serde_json::Value>::from_request(req, payload);
+
+        Box::pin(async move {
+            let body = body.await?.into_inner();
+
+            serde_path_to_error::deserialize(body)
+                .map(Json)
+                .map_err(|err| KristError::JsonField(JsonFieldError::from_path_error(&err)).into())
+        })
+    }
+}
@@ -1,11 +1,13 @@
 pub mod addresses;
-// mod names;
+pub mod names;
+pub mod resolve;
 // mod transactions;
 
 use actix_web::web;
 
 pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.configure(resolve::config);
     cfg.service(web::scope("/addresses").configure(addresses::config));
     // cfg.service(web::scope("/transactions").configure(transactions::config));
-    // cfg.service(web::scope("/names").configure(names::config));
+    cfg.service(web::scope("/names").configure(names::config));
 }
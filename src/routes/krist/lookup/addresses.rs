@@ -6,6 +6,7 @@ use crate::database::wallet::Model as Wallet;
 
 use crate::models::krist::addresses::AddressJson;
 use crate::models::krist::webserver::lookup::addresses::{LookupResponse, QueryParameters};
+use crate::routes::krist::check_address;
 use crate::{AppState, errors::krist::KristError};
 
 #[utoipa::path(
@@ -29,11 +30,12 @@ async fn addresses_lookup(
     let addresses = addresses.into_inner();
     let params = params.into_inner();
 
-    let addresses: Vec<&str> = addresses.split(',').collect();
+    let (addresses, invalid) = normalize_addresses(&addresses);
     let address_count = addresses.len();
 
     let fetch_names = params.fetch_names.unwrap_or(false);
 
+    let addresses: Vec<&str> = addresses.iter().map(String::as_str).collect();
     let looked_up = Wallet::lookup_addresses(pool, addresses, fetch_names).await?;
     let json_models: Vec<AddressJson> = looked_up.into_iter().map(|model| model.into()).collect();
     let len = json_models.len();
@@ -46,13 +48,80 @@ async fn addresses_lookup(
     let response = LookupResponse {
         ok: true,
         found: len,
-        not_found: address_count - len,
+        not_found: address_count - len + invalid,
         addresses: hashmap,
     };
 
     Ok(HttpResponse::Ok().json(response))
 }
 
+/// Split a comma separated address list, trimming and lowercasing each address like Krist does.
+/// Returns the unique valid addresses and how many inputs were not addresses at all.
+fn normalize_addresses(addresses: &str) -> (Vec<String>, usize) {
+    let mut valid = Vec::new();
+    let mut invalid = 0;
+
+    for address in addresses.split(',') {
+        let address = address.trim().to_lowercase();
+
+        if check_address(&address).is_err() {
+            invalid += 1;
+        } else if !valid.contains(&address) {
+            valid.push(address);
+        }
+    }
+
+    (valid, invalid)
+}
+
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(addresses_lookup);
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use actix_web::{
+        App,
+        http::StatusCode,
+        test::{TestRequest, call_service, init_service, read_body_json},
+    };
+    use serde_json::Value;
+    use sqlx::postgres::PgPoolOptions;
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_database_error_body() {
+        // Nothing listens here, so the lookup fails once it reaches the database.
+        let pool = PgPoolOptions::new()
+            .acquire_timeout(Duration::from_millis(100))
+            .connect_lazy("postgres://127.0.0.1:1/kromer")
+            .expect("lazy pool should not connect");
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new(pool)))
+                .configure(config),
+        )
+        .await;
+
+        let req = TestRequest::get().uri("/kre3w0i79j").to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let body: Value = read_body_json(resp).await;
+        assert_eq!(body["ok"], false);
+        assert_eq!(body["error"], "internal_server_error");
+        assert_eq!(body["message"], "Internal server error");
+    }
+
+    #[test]
+    fn test_normalize_addresses() {
+        let (valid, invalid) =
+            normalize_addresses(" KRE3W0I79J,kre3w0i79j, ServerWelf ,not-an-address,,kqxhx5yn9v");
+
+        assert_eq!(valid, vec!["kre3w0i79j", "serverwelf", "kqxhx5yn9v"]);
+        assert_eq!(invalid, 2);
+    }
+}
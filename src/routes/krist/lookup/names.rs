@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+use actix_web::{HttpResponse, get, web};
+
+use crate::database::name::Model as Name;
+use crate::errors::krist::generic::GenericError;
+use crate::models::krist::names::NameJson;
+use crate::models::krist::webserver::lookup::names::LookupResponse;
+use crate::utils::validation::{is_valid_name, normalize_name};
+use crate::{AppState, errors::krist::KristError};
+
+/// Most names that can be looked up in one request
+const MAX_LOOKUP_NAMES: usize = 128;
+
+#[utoipa::path(
+    get,
+    path = "/api/krist/lookup/names/specific/{names}",
+    params(
+        ("names", description = "Comma separated list of names, with or without the `.kro` suffix"),
+    ),
+    responses(
+        (status = 200, description = "Lookup specific names", body = LookupResponse)
+    )
+)]
+#[get("/specific/{names}")]
+async fn names_lookup_specific(
+    state: web::Data<AppState>,
+    names: web::Path<String>,
+) -> Result<HttpResponse, KristError> {
+    let pool = &state.pool;
+
+    let (names, invalid) = normalize_names(&names.into_inner());
+    if names.len() + invalid > MAX_LOOKUP_NAMES {
+        return Err(KristError::Generic(GenericError::InvalidParameter(
+            "names".to_owned(),
+        )));
+    }
+
+    let looked_up = Name::fetch_by_names(pool, &names).await?;
+    let found = looked_up.into_iter().map(Into::into).collect();
+
+    Ok(HttpResponse::Ok().json(lookup_response(names.len() + invalid, found)))
+}
+
+/// Split a comma separated name list, dropping any `.kro` suffix. Returns the unique valid names
+/// and how many inputs were not names at all.
+fn normalize_names(names: &str) -> (Vec<String>, usize) {
+    let mut valid = Vec::new();
+    let mut invalid = 0;
+
+    for name in names.split(',') {
+        let name = normalize_name(name);
+        let name = name.strip_suffix(".kro").unwrap_or(&name);
+
+        if !is_valid_name(name, true) {
+            invalid += 1;
+        } else if !valid.iter().any(|valid| valid == name) {
+            valid.push(name.to_owned());
+        }
+    }
+
+    (valid, invalid)
+}
+
+fn lookup_response(requested: usize, found: Vec<NameJson>) -> LookupResponse {
+    let names: HashMap<String, NameJson> = found
+        .into_iter()
+        .map(|name| (name.name.clone(), name))
+        .collect();
+
+    LookupResponse {
+        ok: true,
+        found: names.len(),
+        not_found: requested - names.len(),
+        names,
+    }
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(names_lookup_specific);
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        App,
+        test::{TestRequest, call_service, init_service, read_body_json},
+    };
+    use serde_json::Value;
+
+    use super::*;
+
+    #[test]
+    fn test_normalize_names() {
+        let (names, invalid) = normalize_names("Shop.kro, bank ,shop,not a name,,missing");
+        assert_eq!(names, vec!["shop", "bank", "missing"]);
+        assert_eq!(invalid, 2);
+    }
+
+    #[sqlx::test]
+    async fn test_mixed_names_lookup(pool: sqlx::PgPool) {
+        for name in ["shop", "bank", "other"] {
+            Name::create(&pool, name.into(), "kre3w0i79j".into())
+                .await
+                .unwrap();
+        }
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new(pool)))
+                .configure(config),
+        )
+        .await;
+
+        // Only `shop` and `bank` of the requested names exist
+        let req = TestRequest::get()
+            .uri("/specific/Shop.kro,%20bank%20,shop,not%20a%20name,,missing")
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert!(resp.status().is_success());
+
+        let body: Value = read_body_json(resp).await;
+        assert_eq!(body["found"], 2);
+        assert_eq!(body["notFound"], 3);
+        assert_eq!(body["names"]["shop"]["owner"], "kre3w0i79j");
+        assert_eq!(body["names"]["bank"]["name"], "bank");
+        assert!(body["names"].get("missing").is_none());
+        assert!(body["names"].get("other").is_none());
+    }
+}
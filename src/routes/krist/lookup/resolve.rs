@@ -0,0 +1,112 @@
+use actix_web::{HttpResponse, get, web};
+
+use crate::database::name::Model as Name;
+use crate::database::wallet::Model as Wallet;
+
+use crate::errors::krist::generic::GenericError;
+use crate::models::krist::webserver::lookup::{ResolveQuery, ResolveResponse};
+use crate::utils::validation::{is_valid_kromer_address, is_valid_name};
+use crate::{AppState, errors::krist::KristError};
+
+/// What a lookup query could refer to
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+struct ResolveTargets {
+    address: Option<String>,
+    name: Option<String>,
+}
+
+impl ResolveTargets {
+    fn parse(query: &str) -> Self {
+        let query = query.trim().to_lowercase();
+
+        // A `.kro` suffix can only ever mean a name.
+        if let Some(name) = query.strip_suffix(".kro") {
+            return Self {
+                address: None,
+                name: is_valid_name(name, true).then(|| name.to_owned()),
+            };
+        }
+
+        Self {
+            address: is_valid_kromer_address(&query).then(|| query.clone()),
+            name: is_valid_name(&query, true).then_some(query),
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/krist/lookup",
+    params(ResolveQuery),
+    responses(
+        (status = 200, description = "Resolve an address or name", body = ResolveResponse)
+    )
+)]
+#[get("")]
+async fn lookup_resolve(
+    state: web::Data<AppState>,
+    query: web::Query<ResolveQuery>,
+) -> Result<HttpResponse, KristError> {
+    let pool = &state.pool;
+    let targets = ResolveTargets::parse(&query.q);
+
+    if targets == ResolveTargets::default() {
+        return Err(KristError::Generic(GenericError::InvalidParameter(
+            "q".to_string(),
+        )));
+    }
+
+    let address = match targets.address {
+        Some(address) => Wallet::fetch_by_address(pool, address).await?,
+        None => None,
+    };
+    let name = match targets.name {
+        Some(name) => Name::fetch_by_name(pool, name).await?,
+        None => None,
+    };
+
+    let response = ResolveResponse {
+        ok: true,
+        address: address.map(Into::into),
+        name: name.map(Into::into),
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(lookup_resolve);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_targets() {
+        let address = ResolveTargets::parse("kre3w0i79j");
+        assert_eq!(address.address.as_deref(), Some("kre3w0i79j"));
+        // Every address is also a valid name
+        assert_eq!(address.name.as_deref(), Some("kre3w0i79j"));
+
+        assert_eq!(
+            ResolveTargets::parse("Example.kro"),
+            ResolveTargets {
+                address: None,
+                name: Some("example".into()),
+            }
+        );
+        assert_eq!(
+            ResolveTargets::parse("example"),
+            ResolveTargets {
+                address: None,
+                name: Some("example".into()),
+            }
+        );
+
+        assert_eq!(
+            ResolveTargets::parse("not a name"),
+            ResolveTargets::default()
+        );
+    }
+}
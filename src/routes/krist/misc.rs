@@ -1,15 +1,18 @@
 use actix_web::{HttpResponse, get, post, web};
-use rust_decimal::Decimal;
 
 use crate::{
     AppState,
-    database::wallet::Model as Wallet,
+    database::{block::Model as Block, ledger::WELFARE_ADDRESS, wallet::Model as Wallet},
     errors::krist::KristError,
     models::krist::{
         auth::{AddressAuthenticationResponse, LoginDetails},
         misc::{MoneySupplyResponse, PrivateKeyAddressResponse, WalletVersionResponse},
-        motd::{Constants, CurrencyInfo, DetailedMotd, DetailedMotdResponse, PackageInfo},
+        motd::{
+            CurrencyInfo, DetailedMotd, DetailedMotdResponse, MOTD_CONSTANTS, MOTD_WORK,
+            PackageInfo,
+        },
     },
+    routes::krist::json::Json,
     utils::crypto,
 };
 
@@ -17,19 +20,14 @@ use chrono::Utc;
 use once_cell::sync::Lazy;
 use std::env;
 
-pub static PUBLIC_URL: Lazy<Result<String, KristError>> =
-    Lazy::new(|| env::var("PUBLIC_URL").map_err(|_| KristError::Custom("server_config_error")));
-
-pub static PUBLIC_WS_URL: Lazy<Result<String, KristError>> = Lazy::new(|| {
-    let url = PUBLIC_URL
-        .as_ref()
-        .map_err(|_| KristError::Custom("server_config_error"))?;
+/// The websocket URL advertised for `public_url`, `wss` only when `FORCE_WS_INSECURE` is `false`.
+fn public_ws_url(public_url: &str) -> String {
     let schema = match env::var("FORCE_WS_INSECURE").as_deref() {
         Ok("false") => "wss",
         _ => "ws",
     };
-    Ok(format!("{schema}://{url}/api/krist/ws"))
-});
+    format!("{schema}://{public_url}/api/krist/ws")
+}
 
 #[utoipa::path(
     post,
@@ -42,12 +40,21 @@ pub static PUBLIC_WS_URL: Lazy<Result<String, KristError>> = Lazy::new(|| {
 #[post("/login")]
 async fn login_address(
     state: web::Data<AppState>,
-    query: web::Json<LoginDetails>,
+    query: Json<LoginDetails>,
 ) -> Result<HttpResponse, KristError> {
     let db = &state.pool;
     let query = query.into_inner();
 
     let private_key = query.private_key;
+
+    // Logging into an existing wallet only reads, creating a new one has to wait out maintenance.
+    if state.is_maintenance() {
+        let address = crypto::make_v2_address(&private_key, "k");
+        if Wallet::fetch_by_address(db, &address).await?.is_none() {
+            return Err(KristError::Maintenance);
+        }
+    }
+
     let result = Wallet::verify_address(db, private_key).await?;
 
     Ok(HttpResponse::Ok().json(AddressAuthenticationResponse {
@@ -65,14 +72,13 @@ async fn login_address(
     )
 )]
 #[get("/motd")]
-async fn get_motd() -> Result<HttpResponse, KristError> {
-    let public_url = PUBLIC_URL
-        .as_ref()
-        .map_err(|_| KristError::Custom("server_config_error"))?;
+async fn get_motd(state: web::Data<AppState>) -> Result<HttpResponse, KristError> {
+    let public_url = state
+        .public_url
+        .as_deref()
+        .ok_or(KristError::Custom("server_config_error"))?;
 
-    let public_ws_url = PUBLIC_WS_URL
-        .as_ref()
-        .map_err(|_| KristError::Custom("server_config_error"))?;
+    let last_block = Block::fetch_latest(&state.pool).await?.map(Into::into);
 
     let motd = DetailedMotd {
         server_time: Utc::now().to_rfc3339(),
@@ -80,12 +86,12 @@ async fn get_motd() -> Result<HttpResponse, KristError> {
         set: None,
         motd_set: None,
         public_url: public_url.to_string(),
-        public_ws_url: public_ws_url.to_string(),
+        public_ws_url: public_ws_url(public_url),
         mining_enabled: false,
         transactions_enabled: true,
         debug_mode: true,
-        work: 500,
-        last_block: None,
+        work: MOTD_WORK,
+        last_block,
         package: PackageInfo {
             name: "Kromer".to_string(),
             version: "0.2.0".to_string(),
@@ -94,15 +100,7 @@ async fn get_motd() -> Result<HttpResponse, KristError> {
             repository: "https://github.com/ReconnectedCC/kromer2/".to_string(),
             git_hash: crate::build_info::GIT_COMMIT_HASH.map(|s| s.to_string()),
         },
-        constants: Constants {
-            wallet_version: 3,
-            nonce_max_size: 500,
-            name_cost: 500,
-            min_work: 50,
-            max_work: 500,
-            work_factor: 500.0,
-            seconds_per_block: 5000,
-        },
+        constants: MOTD_CONSTANTS,
         currency: CurrencyInfo {
             address_prefix: "k".to_string(),
             name_suffix: "kro".to_string(),
@@ -134,6 +132,11 @@ async fn get_walletversion() -> HttpResponse {
     HttpResponse::Ok().json(response)
 }
 
+/// Whether deriving an address through `/v2` also creates its wallet, off by default so the
+/// endpoint can't be used to fill the wallets table.
+pub static V2_PERSIST_WALLETS: Lazy<bool> =
+    Lazy::new(|| crate::utils::env::var_or("KRIST_V2_PERSIST_WALLETS", false));
+
 #[utoipa::path(
     post,
     path = "/api/krist/v2",
@@ -143,16 +146,35 @@ async fn get_walletversion() -> HttpResponse {
     )
 )]
 #[post("/v2")]
-async fn get_v2_address(query: web::Json<LoginDetails>) -> Result<HttpResponse, KristError> {
+async fn get_v2_address(
+    state: web::Data<AppState>,
+    query: Json<LoginDetails>,
+) -> Result<HttpResponse, KristError> {
     let query = query.into_inner();
-    let key = query.private_key;
 
-    let address = crypto::make_v2_address(&key, "k");
+    let address = derive_v2_address(&state, &query.private_key, *V2_PERSIST_WALLETS).await?;
     let response = PrivateKeyAddressResponse { address, ok: true };
 
     Ok(HttpResponse::Ok().json(response))
 }
 
+/// Derive the v2 address for a private key, and make sure its wallet exists when `persist` is set.
+/// The address doesn't depend on the wallet, so maintenance mode only skips creating it.
+async fn derive_v2_address(
+    state: &AppState,
+    private_key: &str,
+    persist: bool,
+) -> Result<String, KristError> {
+    if persist && !state.is_maintenance() {
+        // Creates the wallet with this private key if it doesn't exist yet, same as a login.
+        let wallet = Wallet::verify_address(&state.pool, private_key).await?;
+
+        return Ok(wallet.model.address);
+    }
+
+    Ok(crypto::make_v2_address(private_key, "k"))
+}
+
 #[utoipa::path(
     get,
     path = "/api/krist/supply",
@@ -164,11 +186,7 @@ async fn get_v2_address(query: web::Json<LoginDetails>) -> Result<HttpResponse,
 async fn get_kromer_supply(state: web::Data<AppState>) -> Result<HttpResponse, KristError> {
     let pool = &state.pool;
 
-    let money_supply: Decimal = sqlx::query_scalar(
-        "SELECT COALESCE(SUM(balance), 0) FROM wallets WHERE address != 'serverwelf'",
-    )
-    .fetch_one(pool)
-    .await?;
+    let money_supply = Wallet::money_supply(pool, &WELFARE_ADDRESS).await?;
 
     Ok(HttpResponse::Ok().json(MoneySupplyResponse {
         ok: true,
@@ -185,3 +203,164 @@ pub fn config(cfg: &mut web::ServiceConfig) {
             .service(get_v2_address),
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        App,
+        http::StatusCode,
+        test::{TestRequest, call_service, init_service, read_body_json},
+    };
+    use rust_decimal::dec;
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::websockets::WebSocketServer;
+
+    #[sqlx::test]
+    async fn test_v2_wallet_persistence(pool: sqlx::PgPool) {
+        let key = "correct horse battery staple";
+        let expected = crypto::make_v2_address(key, "k");
+        let wallet = async || Wallet::fetch_by_address(&pool, &expected).await.unwrap();
+        let state = AppState::new(pool.clone());
+
+        // Derivation alone leaves the wallets table alone
+        let address = derive_v2_address(&state, key, false).await.unwrap();
+        assert_eq!(address, expected);
+        assert!(wallet().await.is_none());
+
+        // So does maintenance mode, the address is still handed out
+        state.set_maintenance(true);
+        let address = derive_v2_address(&state, key, true).await.unwrap();
+        assert_eq!(address, expected);
+        assert!(wallet().await.is_none());
+
+        state.set_maintenance(false);
+        let address = derive_v2_address(&state, key, true).await.unwrap();
+        assert_eq!(address, expected);
+        let created = wallet().await.expect("persisting should create the wallet");
+        assert_eq!(created.balance, dec!(0));
+
+        // Asking again finds the same wallet
+        derive_v2_address(&state, key, true).await.unwrap();
+        assert_eq!(wallet().await.unwrap().id, created.id);
+    }
+
+    #[sqlx::test]
+    async fn test_login_creates_a_wallet_that_can_receive(pool: sqlx::PgPool) {
+        let sender = Wallet::verify_address(&pool, "hunter2")
+            .await
+            .unwrap()
+            .model;
+        sender.update_balance(&pool, dec!(50)).await.unwrap();
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new(pool.clone())))
+                .app_data(web::Data::new(WebSocketServer::new()))
+                .configure(crate::routes::config),
+        )
+        .await;
+
+        let key = "correct horse battery staple";
+        let req = TestRequest::post()
+            .uri("/api/krist/login")
+            .set_json(json!({ "privatekey": key }))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert!(resp.status().is_success());
+        let body: Value = read_body_json(resp).await;
+        assert_eq!(body["authed"], true);
+        let address = body["address"].as_str().unwrap().to_owned();
+        assert_eq!(address, crypto::make_v2_address(key, "k"));
+
+        let wallet = Wallet::fetch_by_address(&pool, &address)
+            .await
+            .unwrap()
+            .expect("logging in should create the wallet");
+        assert_eq!(wallet.balance, dec!(0));
+
+        let req = TestRequest::post()
+            .uri("/api/krist/transactions")
+            .set_json(json!({ "privatekey": "hunter2", "to": address, "amount": 10 }))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert!(resp.status().is_success(), "{}", resp.status());
+
+        let wallet = Wallet::fetch_by_address(&pool, &address)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(wallet.balance, dec!(10));
+    }
+
+    #[sqlx::test]
+    async fn test_login_during_maintenance(pool: sqlx::PgPool) {
+        let existing = Wallet::verify_address(&pool, "hunter2")
+            .await
+            .unwrap()
+            .model;
+        let state = web::Data::new(AppState::new(pool.clone()));
+        state.set_maintenance(true);
+        let app = init_service(
+            App::new()
+                .app_data(state.clone())
+                .configure(crate::routes::config),
+        )
+        .await;
+        let login = |key: &str| {
+            TestRequest::post()
+                .uri("/api/krist/login")
+                .set_json(json!({ "privatekey": key }))
+                .to_request()
+        };
+
+        let resp = call_service(&app, login("hunter2")).await;
+        assert!(resp.status().is_success());
+        let body: Value = read_body_json(resp).await;
+        assert_eq!(body["address"], existing.address);
+
+        let key = "correct horse battery staple";
+        let resp = call_service(&app, login(key)).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body: Value = read_body_json(resp).await;
+        assert_eq!(body["error"], "maintenance");
+        let address = crypto::make_v2_address(key, "k");
+        assert!(
+            Wallet::fetch_by_address(&pool, &address)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_motd_last_block(pool: sqlx::PgPool) {
+        let mut state = AppState::new(pool.clone());
+        state.public_url = Some("localhost:8080".to_owned());
+        let app = init_service(App::new().app_data(web::Data::new(state)).configure(config)).await;
+        let motd = async || -> Value {
+            let resp = call_service(&app, TestRequest::get().uri("/motd").to_request()).await;
+            assert!(resp.status().is_success());
+            read_body_json(resp).await
+        };
+
+        // Nothing was imported yet
+        let body = motd().await;
+        assert_eq!(body["ok"], true);
+        assert_eq!(body["public_url"], "localhost:8080");
+        assert!(body["last_block"].is_null());
+
+        for height in [6, 7] {
+            sqlx::query("INSERT INTO blocks(height, address, hash, value, time, difficulty) VALUES ($1, 'kalice0000', repeat('ab', 32), 25.00, NOW(), 400)")
+                .bind(height)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        let body = motd().await;
+        assert_eq!(body["last_block"]["height"], 7.0);
+        assert_eq!(body["last_block"]["address"], "kalice0000");
+        assert_eq!(body["last_block"]["short_hash"], "abababababab");
+    }
+}
@@ -1,3 +1,4 @@
+pub mod json;
 pub mod lookup;
 pub mod misc;
 pub mod names;
@@ -7,6 +8,24 @@ pub mod ws;
 
 use actix_web::{HttpResponse, get, web};
 
+use crate::database::ledger::SYSTEM_ADDRESSES;
+use crate::errors::krist::{KristError, address::AddressError};
+use crate::utils::validation;
+
+/// Reject anything that can't be an address before it reaches the database. System addresses
+/// like the welfare address don't follow the address format but are still real wallets.
+pub(crate) fn check_address(address: &str) -> Result<(), KristError> {
+    if validation::is_valid_kromer_address(address)
+        || SYSTEM_ADDRESSES.iter().any(|system| system == address)
+    {
+        return Ok(());
+    }
+
+    Err(KristError::Address(AddressError::InvalidAddress(
+        address.to_owned(),
+    )))
+}
+
 #[get("")]
 pub async fn index_get() -> HttpResponse {
     HttpResponse::Ok().body("Hello! This is the Krist compatible API provided by Kromer, some functionality might be slightly different but most software made for krist should work")
@@ -2,6 +2,7 @@ use actix_web::{HttpResponse, get, post, web};
 use rust_decimal::Decimal;
 
 use crate::database::ModelExt;
+use crate::database::ledger::WELFARE_ADDRESS;
 use crate::database::name::Model as Name;
 use crate::database::transaction::{Model as Transaction, TransactionCreateData, TransactionType};
 use crate::database::wallet::Model as Wallet;
@@ -13,12 +14,17 @@ use crate::errors::krist::transaction::TransactionError;
 use crate::models::krist::motd::MINING_CONSTANTS;
 use crate::models::krist::names::{
     NameAvailablityResponse, NameBonusResponse, NameCostResponse, NameDataUpdateBody, NameJson,
-    NameListResponse, NameResponse, RegisterNameRequest, TransferNameRequest,
+    NameListResponse, NameResponse, NameStatsResponse, RegisterNameRequest, TransferNameRequest,
 };
 use crate::models::krist::websockets::{WebSocketEvent, WebSocketMessage};
+use crate::routes::krist::{check_address, json::Json};
 use crate::utils::validation;
 use crate::websockets::WebSocketServer;
-use crate::{AppState, errors::krist::KristError, routes::PaginationParams};
+use crate::{
+    AppState,
+    errors::krist::KristError,
+    routes::{DEFAULT_PAGE_LIMIT, PaginationParams},
+};
 
 #[utoipa::path(
     get,
@@ -36,7 +42,7 @@ async fn name_list(
     let params = query.into_inner();
     let pool = &state.pool;
 
-    let limit = params.limit.unwrap_or(50);
+    let limit = params.limit.unwrap_or(*DEFAULT_PAGE_LIMIT);
     let offset = params.offset.unwrap_or(0);
 
     let mut tx = pool.begin().await?;
@@ -81,7 +87,8 @@ async fn name_cost() -> Result<HttpResponse, KristError> {
         ("name", description = "Name to check")
     ),
     responses(
-        (status = 200, description = "Check Name Availability", body = NameAvailablityResponse)
+        (status = 200, description = "Check Name Availability", body = NameAvailablityResponse),
+        (status = 400, description = "Invalid name format")
     )
 )]
 #[get("/check/{name}")]
@@ -92,21 +99,27 @@ async fn name_check(
     let name = name.into_inner();
     let pool = &state.pool;
 
-    if !validation::is_valid_name(&name, true) {
-        return Err(KristError::Generic(GenericError::InvalidParameter(
-            "name".to_string(),
-        )));
-    }
-    let name = name.trim().to_lowercase();
+    check_name_format(&name)?;
 
     let name = Name::fetch_by_name(pool, name).await?;
 
-    let response = NameAvailablityResponse {
-        ok: true,
-        available: name.is_none(),
-    };
+    Ok(HttpResponse::Ok().json(name_availability(name.as_ref())))
+}
 
-    Ok(HttpResponse::Ok().json(response))
+/// Names are checked exactly as given, anything that could never be registered is an `invalid_name`.
+fn check_name_format(name: &str) -> Result<(), KristError> {
+    if name != name.to_lowercase() || !validation::is_valid_name(name, true) {
+        return Err(KristError::Name(NameError::InvalidName(name.to_owned())));
+    }
+
+    Ok(())
+}
+
+fn name_availability(existing: Option<&Name>) -> NameAvailablityResponse {
+    NameAvailablityResponse {
+        ok: true,
+        available: existing.is_none(),
+    }
 }
 
 #[utoipa::path(
@@ -129,6 +142,41 @@ async fn name_bonus(state: web::Data<AppState>) -> Result<HttpResponse, KristErr
     Ok(HttpResponse::Ok().json(response))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/krist/names/stats",
+    params(PaginationParams),
+    responses(
+        (status = 200, description = "Names registered per day", body = NameStatsResponse)
+    )
+)]
+#[get("/stats")]
+async fn name_stats(
+    state: web::Data<AppState>,
+    query: web::Query<PaginationParams>,
+) -> Result<HttpResponse, KristError> {
+    let params = query.into_inner();
+    let pool = &state.pool;
+
+    let mut tx = pool.begin().await?;
+
+    let total = Name::total_count(&mut *tx).await?;
+    let total_unpaid = Name::count_unpaid(&mut *tx).await?;
+    let days = Name::registrations_per_day(&mut *tx, &params).await?;
+
+    tx.commit().await?;
+
+    let response = NameStatsResponse {
+        ok: true,
+        total,
+        total_unpaid,
+        count: days.len(),
+        days,
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
 #[utoipa::path(
     get,
     path = "/api/krist/names/new",
@@ -211,12 +259,14 @@ async fn name_register(
     state: web::Data<AppState>,
     websocket_server: web::Data<WebSocketServer>,
     name: web::Path<String>,
-    details: Option<web::Json<RegisterNameRequest>>,
+    details: Option<Json<RegisterNameRequest>>,
 ) -> Result<HttpResponse, KristError> {
+    state.ensure_writable()?;
+
     let pool = &state.pool;
     let websocket_server = websocket_server.into_inner();
 
-    let name = name.into_inner().trim().to_lowercase();
+    let name = validation::normalize_name(&name.into_inner());
     let new_name_cost = Decimal::new(MINING_CONSTANTS.name_cost, 0);
 
     let private_key = details.map(|request| request.0.private_key);
@@ -259,7 +309,7 @@ async fn name_register(
     // Create the transaction
     let creation_data = TransactionCreateData {
         from: verify_addr_resp.model.address.clone(),
-        to: "serverwelf".to_string(),
+        to: WELFARE_ADDRESS.clone(),
         amount: new_name_cost,
         transaction_type: TransactionType::NamePurchase,
         ..Default::default()
@@ -286,6 +336,10 @@ async fn name_register(
 
     tx.commit().await?;
 
+    websocket_server
+        .broadcast_balances(pool, &[&response.name.owner])
+        .await;
+
     Ok(HttpResponse::Ok().json(response))
 }
 
@@ -302,14 +356,24 @@ async fn name_register(
 )]
 async fn name_update_data(
     state: web::Data<AppState>,
+    websocket_server: web::Data<WebSocketServer>,
     name: web::Path<String>,
-    body: web::Json<NameDataUpdateBody>,
+    body: Json<NameDataUpdateBody>,
 ) -> Result<HttpResponse, KristError> {
+    state.ensure_writable()?;
+
     let pool = &state.pool;
     let name = name.into_inner();
     let body = body.into_inner();
 
-    let model = Name::ctrl_update_metadata(pool, name, body).await?;
+    let (model, changed) = Name::ctrl_update_metadata(pool, name, body).await?;
+
+    if changed {
+        let event = WebSocketMessage::new_event(WebSocketEvent::Name {
+            name: model.clone().into(),
+        });
+        websocket_server.broadcast_event(event).await;
+    }
 
     let name: NameJson = model.into();
     let resp = NameResponse { ok: true, name };
@@ -325,7 +389,8 @@ async fn name_update_data(
     ),
     request_body = TransferNameRequest,
     responses(
-        (status = 200, description = "Transfer Name", body = NameResponse)
+        (status = 200, description = "Transfer Name", body = NameResponse),
+        (status = 404, description = "The name or the recipient address does not exist")
     )
 )]
 #[post("/{name}/transfer")]
@@ -333,8 +398,10 @@ async fn name_transfer(
     state: web::Data<AppState>,
     websocket_server: web::Data<WebSocketServer>,
     name: web::Path<String>,
-    details: web::Json<TransferNameRequest>,
+    details: Json<TransferNameRequest>,
 ) -> Result<HttpResponse, KristError> {
+    state.ensure_writable()?;
+
     let pool = &state.pool;
     let server = websocket_server.into_inner();
     let details = details.into_inner();
@@ -346,6 +413,8 @@ async fn name_transfer(
         )));
     }
 
+    check_address(&details.address)?;
+
     let name = name.trim().to_lowercase();
 
     // Start transaction for all operations to prevent race conditions
@@ -375,6 +444,12 @@ async fn name_transfer(
         return Ok(HttpResponse::Ok().json(response));
     }
 
+    // Unlike Krist the recipient is not created on the fly, a typo in the address would otherwise
+    // strand the name on a wallet nobody has the key to.
+    Wallet::fetch_by_address(&mut *tx, &details.address)
+        .await?
+        .ok_or_else(|| KristError::Address(AddressError::NotFound(details.address.clone())))?;
+
     // Update the name ownership using the database layer method
     let updated_name = name_model
         .update_ownership_in_transaction(&mut tx, &details.address)
@@ -415,6 +490,7 @@ pub fn config(cfg: &mut web::ServiceConfig) {
             .service(name_check)
             .service(name_bonus)
             .service(name_new)
+            .service(name_stats)
             .service(name_get)
             .service(name_register)
             .service(name_transfer)
@@ -425,3 +501,202 @@ pub fn config(cfg: &mut web::ServiceConfig) {
             ),
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::{TestRequest, call_service, init_service, read_body_json};
+    use actix_web::{App, http::StatusCode};
+    use chrono::Utc;
+    use sqlx::postgres::PgPoolOptions;
+
+    use super::*;
+    use crate::models::krist::websockets::WebSocketMessageInner;
+
+    #[actix_web::test]
+    async fn test_check_invalid_name() {
+        let pool = PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/kromer")
+            .expect("lazy pool should not connect");
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new(pool)))
+                .configure(config),
+        )
+        .await;
+
+        let long = "a".repeat(65);
+        for name in ["Reconnected", long.as_str(), "shop.kro", "sh%20op"] {
+            let req = TestRequest::get()
+                .uri(&format!("/names/check/{name}"))
+                .to_request();
+            let resp = call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{name}");
+
+            let body: serde_json::Value = read_body_json(resp).await;
+            assert_eq!(body["error"], "invalid_name");
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_transfer_to_malformed_address() {
+        let pool = PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/kromer")
+            .expect("lazy pool should not connect");
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new(pool)))
+                .app_data(web::Data::new(WebSocketServer::new()))
+                .configure(config),
+        )
+        .await;
+
+        let req = TestRequest::post()
+            .uri("/names/reconnected/transfer")
+            .set_json(serde_json::json!({ "address": "kTOOLONG12345", "privatekey": "hunter2" }))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let body: serde_json::Value = read_body_json(resp).await;
+        assert_eq!(body["error"], "invalid_address");
+    }
+
+    #[sqlx::test]
+    async fn test_transfer_recipient_must_exist(pool: sqlx::PgPool) {
+        let owner = Wallet::verify_address(&pool, "hunter2")
+            .await
+            .unwrap()
+            .model;
+        Name::create(&pool, "shop".into(), owner.address.clone())
+            .await
+            .unwrap();
+        Wallet::create_wallet(&pool, "kre3w0i79j", "hash", None)
+            .await
+            .unwrap();
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new(pool.clone())))
+                .app_data(web::Data::new(WebSocketServer::new()))
+                .configure(config),
+        )
+        .await;
+        let transfer = |address: &str| {
+            TestRequest::post()
+                .uri("/names/shop/transfer")
+                .set_json(serde_json::json!({ "address": address, "privatekey": "hunter2" }))
+                .to_request()
+        };
+        let owner_of_shop = async || {
+            Name::fetch_by_name(&pool, "shop")
+                .await
+                .unwrap()
+                .unwrap()
+                .owner
+        };
+
+        let resp = call_service(&app, transfer("kqxhx5yn9v")).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let body: serde_json::Value = read_body_json(resp).await;
+        assert_eq!(body["error"], "address_not_found");
+        assert_eq!(owner_of_shop().await, owner.address);
+
+        let resp = call_service(&app, transfer("kre3w0i79j")).await;
+        assert!(resp.status().is_success(), "{}", resp.status());
+        assert_eq!(owner_of_shop().await, "kre3w0i79j");
+    }
+
+    #[sqlx::test]
+    async fn test_update_broadcasts_only_changes(pool: sqlx::PgPool) {
+        let owner = Wallet::verify_address(&pool, "hunter2")
+            .await
+            .unwrap()
+            .model;
+        Name::create(&pool, "shop".into(), owner.address)
+            .await
+            .unwrap();
+        let server = web::Data::new(WebSocketServer::new());
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new(pool.clone())))
+                .app_data(server.clone())
+                .configure(config),
+        )
+        .await;
+        let update = || {
+            TestRequest::post()
+                .uri("/names/shop/update")
+                .set_json(serde_json::json!({ "a": "example.com", "privatekey": "hunter2" }))
+                .to_request()
+        };
+        let name_events = || {
+            server
+                .replay_since(0)
+                .unwrap()
+                .into_iter()
+                .filter(|event| {
+                    matches!(
+                        event.r#type,
+                        WebSocketMessageInner::Event {
+                            event: WebSocketEvent::Name { .. },
+                            ..
+                        }
+                    )
+                })
+                .count()
+        };
+
+        let resp = call_service(&app, update()).await;
+        assert!(resp.status().is_success(), "{}", resp.status());
+        let name = Name::fetch_by_name(&pool, "shop").await.unwrap().unwrap();
+        assert_eq!(name.metadata.as_deref(), Some("example.com"));
+        assert_eq!(name_events(), 1);
+
+        // Setting the same metadata again isn't a change worth announcing
+        let resp = call_service(&app, update()).await;
+        assert!(resp.status().is_success(), "{}", resp.status());
+        assert_eq!(name_events(), 1);
+    }
+
+    #[test]
+    fn test_check_name_availability() {
+        assert!(check_name_format("reconnected").is_ok());
+        assert!(name_availability(None).available);
+
+        let taken = Name {
+            id: 1,
+            last_transfered: None,
+            last_updated: None,
+            name: "reconnected".into(),
+            owner: "kre3w0i79j".into(),
+            original_owner: "kre3w0i79j".into(),
+            time_registered: Utc::now(),
+            unpaid: Decimal::ZERO,
+            metadata: None,
+        };
+        assert!(!name_availability(Some(&taken)).available);
+    }
+
+    #[sqlx::test]
+    async fn test_register_mixed_case_duplicate(pool: sqlx::PgPool) {
+        Name::create(&pool, "shop".into(), "kalice0000".into())
+            .await
+            .unwrap();
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new(pool)))
+                .app_data(web::Data::new(WebSocketServer::new()))
+                .configure(config),
+        )
+        .await;
+
+        let req = TestRequest::post()
+            .uri("/names/ShOp")
+            .set_json(serde_json::json!({ "privatekey": "hunter2" }))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+        let body: serde_json::Value = read_body_json(resp).await;
+        assert_eq!(body["error"], "name_taken");
+    }
+}
@@ -3,7 +3,9 @@ use rust_decimal::dec;
 
 use crate::database::ModelExt;
 use crate::database::transaction::{
-    Model as Transaction, TransactionCreateData, TransactionNameData, TransactionType,
+    MAX_METADATA_LENGTH, MAX_TRANSACTION_AMOUNT, Model as Transaction, TransactionCreateData,
+    TransactionNameData, TransactionType, check_metadata_json, check_transfer_limits,
+    normalize_amount,
 };
 use crate::database::wallet::Model as Wallet;
 
@@ -13,18 +15,22 @@ use crate::errors::krist::generic::GenericError;
 use crate::errors::krist::name::NameError;
 use crate::errors::krist::transaction::TransactionError;
 use crate::models::krist::transactions::{
-    TransactionDetails, TransactionJson, TransactionListResponse, TransactionResponse,
+    CommonMetaJson, TransactionDetails, TransactionDirection, TransactionJson,
+    TransactionListResponse, TransactionMetaResponse, TransactionOrderBy, TransactionResponse,
+    TransactionTypeQuery, TransactionsBetweenQuery,
 };
 use crate::models::krist::websockets::{WebSocketEvent, WebSocketMessage};
+use crate::models::krist::{INTEGER_AMOUNTS, is_sendable_amount};
+use crate::routes::krist::{check_address, json::Json};
 use crate::utils::validation::NAME_META_RE;
 
 use crate::websockets::WebSocketServer;
-use crate::{AppState, errors::krist::KristError, routes::PaginationParams};
+use crate::{AppState, errors::krist::KristError, routes::PaginationParams, routes::SortOrder};
 
 #[utoipa::path(
     get,
     path = "/api/krist/transactions",
-    params(PaginationParams),
+    params(PaginationParams, TransactionTypeQuery),
     responses(
         (status = 200, description = "List transactions", body = TransactionListResponse)
     )
@@ -33,14 +39,17 @@ use crate::{AppState, errors::krist::KristError, routes::PaginationParams};
 pub async fn transaction_list(
     state: web::Data<AppState>,
     query: web::Query<PaginationParams>,
+    type_query: Result<web::Query<TransactionTypeQuery>, actix_web::Error>,
 ) -> Result<HttpResponse, KristError> {
     let params = query.into_inner();
     let pool = &state.pool;
+    let transaction_type = type_filter(type_query)?;
 
     let mut tx = pool.begin().await?;
 
-    let total_transaction = Transaction::total_count_no_mined(&mut *tx, &params).await?;
-    let transactions = Transaction::fetch_all_no_mined(&mut *tx, &params).await?;
+    let total_transaction =
+        Transaction::total_count_no_mined(&mut *tx, &params, transaction_type.clone()).await?;
+    let transactions = Transaction::fetch_all_no_mined(&mut *tx, &params, transaction_type).await?;
 
     tx.commit().await?;
 
@@ -69,11 +78,13 @@ pub async fn transaction_list(
 async fn transaction_create(
     state: web::Data<AppState>,
     server: web::Data<WebSocketServer>,
-    details: web::Json<TransactionDetails>,
+    details: Json<TransactionDetails>,
 ) -> Result<HttpResponse, KristError> {
+    state.ensure_writable()?;
+
     let pool = &state.pool;
     let details = details.into_inner();
-    let amount = details.amount.round_dp(2); // Do not allow more than 2 decimals after the dot.
+    let amount = normalize_amount(details.amount).map_err(TransactionError::from)?;
 
     // Check if the `to` field is not empty and must be below or equal to 64.
     // The length check is for making sure there is enough space for metaname too.
@@ -84,12 +95,28 @@ async fn transaction_create(
     }
 
     // Check on the server so DB doesnt throw.
-    if amount <= dec!(0.00) {
+    if amount <= dec!(0.00) || !is_sendable_amount(&details.amount, *INTEGER_AMOUNTS) {
         return Err(KristError::Generic(GenericError::InvalidParameter(
             "amount".to_string(),
         )));
     }
 
+    if details
+        .metadata_json
+        .as_ref()
+        .is_some_and(|metadata| !metadata.is_object())
+    {
+        return Err(KristError::Generic(GenericError::InvalidParameter(
+            "metadataJson".to_string(),
+        )));
+    }
+
+    check_transfer_limits(amount, details.metadata.as_deref(), *MAX_TRANSACTION_AMOUNT)
+        .map_err(TransactionError::from)?;
+    if let Some(metadata_json) = &details.metadata_json {
+        check_metadata_json(metadata_json).map_err(TransactionError::from)?;
+    }
+
     let mut tx = pool.begin().await?;
 
     let sender_verify_response = Wallet::verify_address(&mut *tx, details.private_key).await?;
@@ -98,6 +125,7 @@ async fn transaction_create(
     }
 
     let sender = sender_verify_response.model;
+    sender.ensure_can_send().map_err(AddressError::from)?;
 
     let is_name = NAME_META_RE.is_match(&details.to);
 
@@ -143,7 +171,9 @@ async fn transaction_create(
         sent_metaname,
         sent_name,
         metadata: details.metadata,
+        metadata_json: details.metadata_json,
         transaction_type: TransactionType::Transfer,
+        allow_duplicate: details.allow_duplicate.unwrap_or(false),
         ..Default::default()
     };
 
@@ -158,6 +188,11 @@ async fn transaction_create(
     });
     server.broadcast_event(event).await;
 
+    let from = transaction_json.from.as_deref().unwrap_or_default();
+    server
+        .broadcast_balances(pool, &[from, &transaction_json.to])
+        .await;
+
     let final_response = TransactionResponse {
         ok: true,
         transaction: transaction_json,
@@ -169,7 +204,7 @@ async fn transaction_create(
 #[utoipa::path(
     get,
     path = "/api/krist/transactions/latest",
-    params(PaginationParams),
+    params(PaginationParams, TransactionTypeQuery),
     responses(
         (status = 200, description = "Get latest transactions", body = TransactionListResponse)
     )
@@ -178,12 +213,175 @@ async fn transaction_create(
 async fn transaction_latest(
     state: web::Data<AppState>,
     query: web::Query<PaginationParams>,
+    type_query: Result<web::Query<TransactionTypeQuery>, actix_web::Error>,
+) -> Result<HttpResponse, KristError> {
+    let params = query.into_inner();
+    let pool = &state.pool;
+    let transaction_type = type_filter(type_query)?;
+
+    let total = Transaction::total_count_no_mined(pool, &params, transaction_type.clone()).await?;
+    let transactions = Transaction::sorted_by_date(pool, &params, transaction_type).await?;
+
+    let transactions: Vec<TransactionJson> =
+        transactions.into_iter().map(|trans| trans.into()).collect();
+
+    let response = TransactionListResponse {
+        ok: true,
+        count: transactions.len(),
+        total,
+        transactions,
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/krist/transactions/{address}/latest",
+    params(
+        ("address", description = "Address"),
+        PaginationParams
+    ),
+    responses(
+        (status = 200, description = "Get the latest transactions of an address", body = TransactionListResponse),
+        (status = 400, description = "Malformed address"),
+        (status = 404, description = "Wallet not found")
+    )
+)]
+#[get("/{address}/latest")]
+async fn transaction_latest_for_address(
+    state: web::Data<AppState>,
+    address: web::Path<String>,
+    query: web::Query<PaginationParams>,
 ) -> Result<HttpResponse, KristError> {
+    let address = address.into_inner();
+    check_address(&address)?;
     let params = query.into_inner();
     let pool = &state.pool;
 
-    let total = Transaction::total_count_no_mined(pool, &params).await?;
-    let transactions = Transaction::sorted_by_date(pool, &params).await?;
+    let mut tx = pool.begin().await?;
+
+    let wallet = Wallet::fetch_by_address(&mut *tx, &address)
+        .await?
+        .ok_or_else(|| KristError::Address(AddressError::NotFound(address)))?;
+
+    // Same as the address's own transaction listing, always newest first
+    let direction = TransactionDirection::All;
+    let total = wallet
+        .total_transactions(&mut *tx, params.exclude_mined.unwrap_or(false), direction)
+        .await?;
+    let transactions = wallet
+        .transactions(
+            &mut *tx,
+            &params,
+            TransactionOrderBy::Time,
+            SortOrder::Desc,
+            direction,
+        )
+        .await?;
+
+    tx.commit().await?;
+
+    let transactions: Vec<TransactionJson> =
+        transactions.into_iter().map(|trans| trans.into()).collect();
+
+    let response = TransactionListResponse {
+        ok: true,
+        count: transactions.len(),
+        total: total as usize,
+        transactions,
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/krist/transactions/between",
+    params(PaginationParams, TransactionsBetweenQuery),
+    responses(
+        (status = 200, description = "List transactions between two addresses", body = TransactionListResponse),
+        (status = 400, description = "An address or the order is invalid")
+    )
+)]
+#[get("/between")]
+async fn transaction_between(
+    state: web::Data<AppState>,
+    pagination: web::Query<PaginationParams>,
+    query: web::Query<TransactionsBetweenQuery>,
+) -> Result<HttpResponse, KristError> {
+    let pool = &state.pool;
+    let pagination = pagination.into_inner();
+    let query = query.into_inner();
+
+    check_address(&query.a)?;
+    check_address(&query.b)?;
+
+    let order = match query.order {
+        Some(value) => SortOrder::parse(&value).ok_or_else(|| {
+            KristError::Generic(GenericError::InvalidParameter("order".to_owned()))
+        })?,
+        None => SortOrder::Desc,
+    };
+
+    let mut tx = pool.begin().await?;
+
+    let total = Transaction::total_between(&mut *tx, &query.a, &query.b).await?;
+    let transactions =
+        Transaction::fetch_between(&mut *tx, &query.a, &query.b, &pagination, order).await?;
+
+    tx.commit().await?;
+
+    let transactions: Vec<TransactionJson> =
+        transactions.into_iter().map(|trans| trans.into()).collect();
+
+    let response = TransactionListResponse {
+        ok: true,
+        count: transactions.len(),
+        total,
+        transactions,
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/krist/transactions/by-ref/{ref}",
+    params(
+        ("ref", description = "The CommonMeta `ref` value to look for"),
+        PaginationParams
+    ),
+    responses(
+        (status = 200, description = "List transactions with a matching ref", body = TransactionListResponse),
+        (status = 400, description = "The ref is empty, too long or contains a `;`")
+    )
+)]
+#[get("/by-ref/{ref}")]
+async fn transaction_by_ref(
+    state: web::Data<AppState>,
+    reference: web::Path<String>,
+    pagination: web::Query<PaginationParams>,
+) -> Result<HttpResponse, KristError> {
+    let pool = &state.pool;
+    let reference = reference.into_inner();
+    let pagination = pagination.into_inner();
+
+    if reference.is_empty()
+        || reference.contains(';')
+        || reference.chars().count() > MAX_METADATA_LENGTH
+    {
+        return Err(KristError::Generic(GenericError::InvalidParameter(
+            "ref".to_owned(),
+        )));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    let total = Transaction::total_by_ref(&mut *tx, &reference).await?;
+    let transactions = Transaction::fetch_by_ref(&mut *tx, &reference, &pagination).await?;
+
+    tx.commit().await?;
 
     let transactions: Vec<TransactionJson> =
         transactions.into_iter().map(|trans| trans.into()).collect();
@@ -206,6 +404,7 @@ async fn transaction_latest(
     ),
     responses(
         (status = 200, description = "Get transaction by ID", body = TransactionResponse),
+        (status = 400, description = "The ID is not a number or is negative"),
         (status = 404, description = "Transaction not found")
     )
 )]
@@ -217,6 +416,12 @@ async fn transaction_get(
     let id = id.into_inner();
     let pool = &state.pool;
 
+    if id < 0 {
+        return Err(KristError::Generic(GenericError::InvalidParameter(
+            "id".to_owned(),
+        )));
+    }
+
     let slim = Transaction::fetch_by_id(pool, id).await?;
 
     slim.map(|trans| TransactionResponse {
@@ -227,12 +432,435 @@ async fn transaction_get(
     .ok_or_else(|| KristError::Transaction(TransactionError::NotFound))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/krist/transactions/{id}/meta",
+    params(
+        ("id", description = "Transaction ID")
+    ),
+    responses(
+        (status = 200, description = "Get the CommonMeta of a transaction", body = TransactionMetaResponse),
+        (status = 400, description = "The ID is not a number or is negative"),
+        (status = 404, description = "Transaction not found")
+    )
+)]
+#[get("/{id}/meta")]
+async fn transaction_get_meta(
+    state: web::Data<AppState>,
+    id: web::Path<i64>,
+) -> Result<HttpResponse, KristError> {
+    let id = id.into_inner();
+    let pool = &state.pool;
+
+    if id < 0 {
+        return Err(KristError::Generic(GenericError::InvalidParameter(
+            "id".to_owned(),
+        )));
+    }
+
+    let transaction = Transaction::fetch_by_id(pool, id)
+        .await?
+        .ok_or(KristError::Transaction(TransactionError::NotFound))?;
+
+    let meta = transaction
+        .metadata
+        .as_deref()
+        .map(CommonMetaJson::parse)
+        .unwrap_or_default();
+
+    Ok(HttpResponse::Ok().json(TransactionMetaResponse { ok: true, meta }))
+}
+
+/// `type` is the only field of [`TransactionTypeQuery`], so failing to extract it means it didn't
+/// name a transaction type.
+fn type_filter(
+    query: Result<web::Query<TransactionTypeQuery>, actix_web::Error>,
+) -> Result<Option<TransactionType>, KristError> {
+    query
+        .map(|query| query.into_inner().transaction_type)
+        .map_err(|_| KristError::Generic(GenericError::InvalidParameter("type".to_owned())))
+}
+
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/transactions")
             .service(transaction_create)
             .service(transaction_latest)
+            .service(transaction_between)
+            .service(transaction_by_ref)
+            .service(transaction_latest_for_address)
+            .service(transaction_get_meta)
             .service(transaction_get)
             .service(transaction_list),
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        App,
+        http::StatusCode,
+        test::{TestRequest, call_service, init_service, read_body_json},
+    };
+    use serde_json::Value;
+    use sqlx::postgres::PgPoolOptions;
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_between_rejects_invalid_addresses() {
+        let pool = PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/kromer")
+            .expect("lazy pool should not connect");
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new(pool)))
+                .configure(config),
+        )
+        .await;
+
+        for uri in [
+            "/transactions/between?a=nope&b=kre3w0i79j",
+            "/transactions/between?a=kre3w0i79j&b=",
+        ] {
+            let resp = call_service(&app, TestRequest::get().uri(uri).to_request()).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{uri}");
+
+            let body: Value = read_body_json(resp).await;
+            assert_eq!(body["error"], "invalid_address", "{uri}");
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_type_filter(pool: sqlx::PgPool) {
+        Wallet::create_wallet(&pool, "kalice0000", "alice", Some(dec!(100)))
+            .await
+            .unwrap();
+        for transaction_type in [
+            TransactionType::Transfer,
+            TransactionType::NamePurchase,
+            TransactionType::Transfer,
+        ] {
+            let data = TransactionCreateData {
+                from: "kalice0000".into(),
+                to: "kbob000000".into(),
+                amount: dec!(1.00),
+                transaction_type,
+                ..Default::default()
+            };
+            Transaction::create_no_update(&pool, data).await.unwrap();
+        }
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new(pool)))
+                .configure(config),
+        )
+        .await;
+
+        for uri in [
+            "/transactions?type=bogus",
+            "/transactions/latest?type=bogus",
+        ] {
+            let resp = call_service(&app, TestRequest::get().uri(uri).to_request()).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+            let body: Value = read_body_json(resp).await;
+            assert_eq!(body["error"], "invalid_parameter");
+            assert_eq!(body["parameter"], "type");
+        }
+
+        for uri in [
+            "/transactions?type=transfer",
+            "/transactions/latest?type=transfer",
+        ] {
+            let resp = call_service(&app, TestRequest::get().uri(uri).to_request()).await;
+            assert!(resp.status().is_success());
+
+            let body: Value = read_body_json(resp).await;
+            assert_eq!(body["total"], 2);
+            assert_eq!(body["count"], 2);
+            for transaction in body["transactions"].as_array().unwrap() {
+                assert_eq!(transaction["type"], "transfer");
+            }
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_between_only_lists_the_pair(pool: sqlx::PgPool) {
+        let mut ids = Vec::new();
+        for (from, to) in [
+            ("kalice0000", "kbob000000"),
+            ("kalice0000", "kcarol0000"),
+            ("kbob000000", "kalice0000"),
+            ("kcarol0000", "kbob000000"),
+            ("serverwelf", "kalice0000"),
+        ] {
+            let data = TransactionCreateData {
+                from: from.into(),
+                to: to.into(),
+                amount: dec!(1.00),
+                transaction_type: TransactionType::Transfer,
+                ..Default::default()
+            };
+            ids.push(Transaction::create_no_update(&pool, data).await.unwrap().id);
+        }
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new(pool)))
+                .configure(config),
+        )
+        .await;
+
+        let req = TestRequest::get()
+            .uri("/transactions/between?a=kbob000000&b=kalice0000&order=ASC")
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert!(resp.status().is_success());
+
+        let body: Value = read_body_json(resp).await;
+        assert_eq!(body["total"], 2);
+        let listed: Vec<_> = body["transactions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|transaction| transaction["id"].as_i64().unwrap() as i32)
+            .collect();
+        assert_eq!(listed, vec![ids[0], ids[2]]);
+
+        // System addresses are valid parties too, like everywhere else an address is taken.
+        let req = TestRequest::get()
+            .uri("/transactions/between?a=serverwelf&b=kalice0000")
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert!(resp.status().is_success());
+
+        let body: Value = read_body_json(resp).await;
+        assert_eq!(body["total"], 1);
+        assert_eq!(body["transactions"][0]["id"].as_i64(), Some(ids[4] as i64));
+    }
+
+    #[sqlx::test]
+    async fn test_latest_for_address_is_newest_first(pool: sqlx::PgPool) {
+        Wallet::create_wallet(&pool, "kalice0000", "alice", None)
+            .await
+            .unwrap();
+        let mut ids = Vec::new();
+        for (from, to) in [
+            ("kalice0000", "kbob000000"),
+            ("kbob000000", "kalice0000"),
+            ("kalice0000", "kcarol0000"),
+            ("kbob000000", "kcarol0000"),
+        ] {
+            let data = TransactionCreateData {
+                from: from.into(),
+                to: to.into(),
+                amount: dec!(1.00),
+                transaction_type: TransactionType::Transfer,
+                ..Default::default()
+            };
+            ids.push(Transaction::create_no_update(&pool, data).await.unwrap().id);
+        }
+        // Date the rows so their age disagrees with their id: the first insert is the newest
+        sqlx::query(
+            "UPDATE transactions SET date = NOW() - make_interval(hours => id) WHERE id <> $1",
+        )
+        .bind(ids[0])
+        .execute(&pool)
+        .await
+        .unwrap();
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new(pool)))
+                .configure(config),
+        )
+        .await;
+
+        let req = TestRequest::get()
+            .uri("/transactions/kalice0000/latest")
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert!(resp.status().is_success());
+
+        let body: Value = read_body_json(resp).await;
+        assert_eq!(body["total"], 3);
+        let listed: Vec<_> = body["transactions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|transaction| transaction["id"].as_i64().unwrap() as i32)
+            .collect();
+        assert_eq!(listed, vec![ids[0], ids[1], ids[2]]);
+    }
+
+    #[sqlx::test]
+    async fn test_by_ref_matches_exact_ref(pool: sqlx::PgPool) {
+        let mut ids = Vec::new();
+        for metadata in [
+            "ref=order-42",
+            "reference=order-42",
+            "msg=ref=order-42",
+            "ref=;ref=order-42",
+            "msg=thanks;ref=order-42;ref=other",
+            "ref=order-420",
+        ] {
+            let data = TransactionCreateData {
+                from: "kalice0000".into(),
+                to: "kbob000000".into(),
+                amount: dec!(1.00),
+                metadata: Some(metadata.into()),
+                transaction_type: TransactionType::Transfer,
+                ..Default::default()
+            };
+            ids.push(Transaction::create_no_update(&pool, data).await.unwrap().id);
+        }
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new(pool)))
+                .configure(config),
+        )
+        .await;
+        let by_ref = async |reference: &str| -> (Value, Vec<i32>) {
+            let req = TestRequest::get()
+                .uri(&format!("/transactions/by-ref/{reference}"))
+                .to_request();
+            let resp = call_service(&app, req).await;
+            assert!(resp.status().is_success(), "{reference}");
+            let body: Value = read_body_json(resp).await;
+            let listed = body["transactions"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|transaction| transaction["id"].as_i64().unwrap() as i32)
+                .collect();
+            (body, listed)
+        };
+
+        // Newest first, and only where `ref` is its own key with a value
+        let (body, listed) = by_ref("order-42").await;
+        assert_eq!(body["total"], 3);
+        assert_eq!(listed, vec![ids[4], ids[3], ids[0]]);
+
+        // Only the first ref counts
+        let (body, listed) = by_ref("other").await;
+        assert_eq!(body["total"], 0);
+        assert!(listed.is_empty());
+
+        let (body, listed) = by_ref("order-4").await;
+        assert_eq!(body["total"], 0);
+        assert!(listed.is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_by_ref_rejects_invalid_refs() {
+        let pool = PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/kromer")
+            .expect("lazy pool should not connect");
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new(pool)))
+                .configure(config),
+        )
+        .await;
+
+        let too_long = format!(
+            "/transactions/by-ref/{}",
+            "a".repeat(MAX_METADATA_LENGTH + 1)
+        );
+        for uri in ["/transactions/by-ref/a%3Bb", too_long.as_str()] {
+            let resp = call_service(&app, TestRequest::get().uri(uri).to_request()).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+            let body: Value = read_body_json(resp).await;
+            assert_eq!(body["parameter"], "ref");
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_latest_for_address_rejects_malformed_address() {
+        let pool = PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/kromer")
+            .expect("lazy pool should not connect");
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new(pool)))
+                .configure(config),
+        )
+        .await;
+
+        let req = TestRequest::get()
+            .uri("/transactions/kTOOLONG12345/latest")
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let body: Value = read_body_json(resp).await;
+        assert_eq!(body["error"], "invalid_address");
+    }
+
+    #[actix_web::test]
+    async fn test_maintenance_rejects_writes() {
+        let pool = PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/kromer")
+            .expect("lazy pool should not connect");
+        let state = web::Data::new(AppState::new(pool));
+        let app = init_service(
+            App::new()
+                .app_data(state.clone())
+                .app_data(web::Data::new(WebSocketServer::new()))
+                .configure(config),
+        )
+        .await;
+
+        let write = || {
+            TestRequest::post()
+                .uri("/transactions")
+                .set_json(serde_json::json!({
+                    "privatekey": "hunter2",
+                    "to": "",
+                    "amount": 1,
+                }))
+                .to_request()
+        };
+        // Fails validation before the database, so the status shows which check turned it away.
+        let read = || {
+            TestRequest::get()
+                .uri("/transactions/between?a=nope&b=kre3w0i79j")
+                .to_request()
+        };
+
+        state.set_maintenance(true);
+        let resp = call_service(&app, write()).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body: Value = read_body_json(resp).await;
+        assert_eq!(body["error"], "maintenance");
+
+        let resp = call_service(&app, read()).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        state.set_maintenance(false);
+        let resp = call_service(&app, write()).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn test_transaction_id_must_be_a_valid_number() {
+        let pool = PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/kromer")
+            .expect("lazy pool should not connect");
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new(pool)))
+                .configure(crate::routes::config),
+        )
+        .await;
+
+        for uri in ["/api/krist/transactions/abc", "/api/krist/transactions/-1"] {
+            let resp = call_service(&app, TestRequest::get().uri(uri).to_request()).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{uri}");
+
+            let body: Value = read_body_json(resp).await;
+            assert_eq!(body["error"], "invalid_parameter", "{uri}");
+            assert_eq!(body["parameter"], "id", "{uri}");
+        }
+    }
+}
@@ -5,17 +5,23 @@ use crate::AppState;
 use crate::database::{ModelExt, wallet::Model as Wallet};
 use crate::errors::krist::KristError;
 use crate::errors::krist::address::AddressError;
+use crate::errors::krist::generic::GenericError;
 use crate::models::krist::addresses::{
-    AddressGetQuery, AddressJson, AddressListResponse, AddressResponse,
+    AddressGetQuery, AddressJson, AddressListQuery, AddressListResponse, AddressOrderBy,
+    AddressResponse,
 };
 use crate::models::krist::names::{NameJson, NameListResponse};
-use crate::models::krist::transactions::{TransactionJson, TransactionListResponse};
-use crate::routes::PaginationParams;
+use crate::models::krist::transactions::{
+    TransactionDirection, TransactionDirectionQuery, TransactionJson, TransactionListResponse,
+    TransactionOrderBy, TransactionOrderQuery,
+};
+use crate::routes::krist::check_address;
+use crate::routes::{DEFAULT_PAGE_LIMIT, PaginationParams, SortOrder};
 
 #[utoipa::path(
     get,
     path = "/api/krist/addresses",
-    params(PaginationParams),
+    params(PaginationParams, AddressListQuery),
     responses(
         (status = 200, description = "List wallets", body = AddressListResponse)
     )
@@ -24,17 +30,32 @@ use crate::routes::PaginationParams;
 async fn wallet_list(
     state: web::Data<AppState>,
     pagination: web::Query<PaginationParams>,
+    query: web::Query<AddressListQuery>,
 ) -> Result<HttpResponse, KristError> {
     let pool = &state.pool;
 
     let pagination = pagination.into_inner();
-    let limit = pagination.limit.unwrap_or(50);
+    let limit = pagination.limit.unwrap_or(*DEFAULT_PAGE_LIMIT);
     let offset = pagination.offset.unwrap_or(0);
 
+    let query = query.into_inner();
+    let order_by = match query.order_by {
+        Some(value) => AddressOrderBy::parse(&value).ok_or_else(|| {
+            KristError::Generic(GenericError::InvalidParameter("orderBy".to_owned()))
+        })?,
+        None => AddressOrderBy::default(),
+    };
+    let order = match query.order {
+        Some(value) => SortOrder::parse(&value).ok_or_else(|| {
+            KristError::Generic(GenericError::InvalidParameter("order".to_owned()))
+        })?,
+        None => SortOrder::default(),
+    };
+
     let mut tx = pool.begin().await?;
 
     let total = Wallet::total_count(&mut *tx).await?;
-    let wallets = Wallet::fetch_all(&mut *tx, limit, offset).await?;
+    let wallets = Wallet::fetch_all_ordered(&mut *tx, limit, offset, order_by, order).await?;
 
     tx.commit().await?;
 
@@ -59,6 +80,7 @@ async fn wallet_list(
     ),
     responses(
         (status = 200, description = "Get Wallet", body = AddressResponse),
+        (status = 400, description = "Malformed address"),
         (status = 404, description = "Wallet not found")
     )
 )]
@@ -69,6 +91,7 @@ async fn wallet_get(
     query: web::Query<AddressGetQuery>,
 ) -> Result<HttpResponse, KristError> {
     let address = address.into_inner();
+    check_address(&address)?;
 
     let wallet = match query.0.fetch_names {
         Some(true) => Wallet::fetch_by_address_names(&state.pool, &address).await?,
@@ -98,7 +121,7 @@ async fn wallet_richest(
     pagination: web::Query<PaginationParams>,
 ) -> Result<HttpResponse, KristError> {
     let pagination = pagination.into_inner();
-    let limit = pagination.limit.unwrap_or(50);
+    let limit = pagination.limit.unwrap_or(*DEFAULT_PAGE_LIMIT);
     let offset = pagination.offset.unwrap_or(0);
 
     let total = Wallet::total_count(&state.pool).await?;
@@ -123,10 +146,13 @@ async fn wallet_richest(
     path = "/api/krist/addresses/{address}/transactions",
     params(
         ("address", description = "Address"),
-        PaginationParams
+        PaginationParams,
+        TransactionOrderQuery,
+        TransactionDirectionQuery
     ),
     responses(
         (status = 200, description = "Get Wallet Transactions", body = TransactionListResponse),
+        (status = 400, description = "Malformed address, or invalid orderBy, order or direction"),
         (status = 404, description = "Wallet not found")
     )
 )]
@@ -135,11 +161,35 @@ async fn wallet_get_transactions(
     state: web::Data<AppState>,
     address: web::Path<String>,
     params: web::Query<PaginationParams>,
+    query: web::Query<TransactionOrderQuery>,
+    direction: web::Query<TransactionDirectionQuery>,
 ) -> Result<HttpResponse, KristError> {
     let address = address.into_inner();
+    check_address(&address)?;
     let params = params.into_inner();
     let pool = &state.pool;
 
+    let query = query.into_inner();
+    let order_by = match query.order_by {
+        Some(value) => TransactionOrderBy::parse(&value).ok_or_else(|| {
+            KristError::Generic(GenericError::InvalidParameter("orderBy".to_owned()))
+        })?,
+        None => TransactionOrderBy::default(),
+    };
+    // Newest first unless asked otherwise
+    let order = match query.order {
+        Some(value) => SortOrder::parse(&value).ok_or_else(|| {
+            KristError::Generic(GenericError::InvalidParameter("order".to_owned()))
+        })?,
+        None => SortOrder::Desc,
+    };
+    let direction = match direction.into_inner().direction {
+        Some(value) => TransactionDirection::parse(&value).ok_or_else(|| {
+            KristError::Generic(GenericError::InvalidParameter("direction".to_owned()))
+        })?,
+        None => TransactionDirection::default(),
+    };
+
     let mut tx = pool.begin().await?;
 
     let wallet = Wallet::fetch_by_address(&mut *tx, &address)
@@ -147,10 +197,12 @@ async fn wallet_get_transactions(
         .ok_or_else(|| KristError::Address(AddressError::NotFound(address)))?;
 
     let total_transactions = wallet
-        .total_transactions(&mut *tx, params.exclude_mined.unwrap_or(false))
+        .total_transactions(&mut *tx, params.exclude_mined.unwrap_or(false), direction)
         .await?;
 
-    let transactions = wallet.transactions(&mut *tx, &params).await?;
+    let transactions = wallet
+        .transactions(&mut *tx, &params, order_by, order, direction)
+        .await?;
 
     tx.commit().await?;
 
@@ -176,6 +228,7 @@ async fn wallet_get_transactions(
     ),
     responses(
         (status = 200, description = "Get Wallet Names", body = NameListResponse),
+        (status = 400, description = "Malformed address"),
         (status = 404, description = "Wallet not found")
     )
 )]
@@ -186,6 +239,7 @@ async fn wallet_get_names(
     query: web::Query<PaginationParams>,
 ) -> Result<HttpResponse, KristError> {
     let address = address.into_inner();
+    check_address(&address)?;
     let query = query.into_inner();
     let pool = &state.pool;
 
@@ -222,3 +276,42 @@ pub fn config(cfg: &mut web::ServiceConfig) {
             .service(wallet_list),
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        App,
+        http::StatusCode,
+        test::{TestRequest, call_service, init_service, read_body_json},
+    };
+    use serde_json::Value;
+    use sqlx::postgres::PgPoolOptions;
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_malformed_address_is_rejected() {
+        // Nothing listens here, a request that reached the database would fail with a 500.
+        let pool = PgPoolOptions::new()
+            .connect_lazy("postgres://127.0.0.1:1/kromer")
+            .expect("lazy pool should not connect");
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new(pool)))
+                .configure(config),
+        )
+        .await;
+
+        for uri in [
+            "/addresses/notanaddress!!",
+            "/addresses/kTOOLONG12345/transactions",
+            "/addresses/KRE3W0I79J/names",
+        ] {
+            let resp = call_service(&app, TestRequest::get().uri(uri).to_request()).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{uri}");
+
+            let body: Value = read_body_json(resp).await;
+            assert_eq!(body["error"], "invalid_address", "{uri}");
+        }
+    }
+}
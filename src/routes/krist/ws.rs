@@ -1,13 +1,13 @@
+use std::net::IpAddr;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Instant;
 
 use actix_web::rt::time;
-use actix_web::{HttpRequest, get, post};
+use actix_web::{HttpMessage, HttpRequest, get, post};
 use actix_web::{HttpResponse, web};
 use actix_ws::AggregatedMessage;
-use chrono::Utc;
 use serde_json::json;
 use std::sync::Mutex;
 use uuid::Uuid;
@@ -15,10 +15,10 @@ use uuid::Uuid;
 use crate::AppState;
 use crate::database::wallet::Model as Wallet;
 use crate::errors::krist::{KristError, address::AddressError, websockets::WebSocketError};
-use crate::models::krist::websockets::{WebSocketMessage, WebSocketMessageInner};
-use crate::websockets::types::common::WebSocketTokenData;
-use crate::websockets::types::convert_to_iso_string;
-use crate::websockets::{CLIENT_TIMEOUT, HEARTBEAT_INTERVAL, WebSocketServer, handler, utils};
+use crate::routes::krist::json::Json;
+use crate::routes::request_id::RequestId;
+use crate::websockets::types::common::{WebSocketSessionOrigin, WebSocketTokenData};
+use crate::websockets::{WebSocketServer, handler, utils};
 
 #[derive(serde::Deserialize)]
 struct WsConnDetails {
@@ -31,7 +31,7 @@ pub async fn setup_ws(
     req: HttpRequest,
     state: web::Data<AppState>,
     server: web::Data<WebSocketServer>,
-    details: Option<web::Json<WsConnDetails>>,
+    details: Option<Json<WsConnDetails>>,
 ) -> Result<HttpResponse, KristError> {
     let pool = &state.pool;
 
@@ -56,6 +56,10 @@ pub async fn setup_ws(
 
             server.obtain_token(token_data)
         }
+        None if !server.config.allow_guests => {
+            tracing::debug!("Rejecting guest websocket, guests are disabled");
+            return Err(KristError::Address(AddressError::AuthFailed));
+        }
         None => {
             let token_data = WebSocketTokenData::new("guest".into(), None, computer_id);
 
@@ -64,15 +68,16 @@ pub async fn setup_ws(
     };
 
     // Make the URL and return it to the user.
-    let url = match utils::make_url::make_url(uuid) {
-        Ok(value) => value,
-        Err(_) => return Err(KristError::Custom("server_config_error")),
-    };
+    let public_url = state
+        .public_url
+        .as_deref()
+        .ok_or(KristError::Custom("server_config_error"))?;
+    let url = utils::make_url::make_url(public_url, uuid);
 
     Ok(HttpResponse::Ok().json(json!({
         "ok": true,
         "url": url,
-        "expires": 30
+        "expires": server.config.token_expiration.as_secs()
     })))
 }
 
@@ -106,7 +111,22 @@ pub async fn gateway(
         .aggregate_continuations()
         .max_continuation_size(2 * 1024 * 1024);
 
-    server.insert_session(uuid, session.clone(), data); // Not a big fan of cloning but here it is needed.
+    // Skipped rather than refused in maintenance mode, the grant is still owed on a later connect.
+    let faucet = server
+        .faucet_amount_for(&data)
+        .filter(|_| !state.is_maintenance())
+        .map(|amount| (data.address.clone(), amount));
+
+    // Not a big fan of cloning but here it is needed.
+    let origin = WebSocketSessionOrigin {
+        ip: client_ip(&req, &server.config.trusted_proxies),
+        request_id: req.extensions().get::<RequestId>().map(|id| id.0.clone()),
+    };
+    if let Err(err) = server.insert_session(uuid, session.clone(), data, origin) {
+        actix_web::rt::spawn(handler::reject_session(session, err));
+
+        return Ok(response);
+    }
 
     let alive = Arc::new(Mutex::new(Instant::now()));
     let session_closed = Arc::new(AtomicBool::new(false));
@@ -117,7 +137,12 @@ pub async fn gateway(
     let alive2 = alive.clone();
     let session_closed2 = session_closed.clone();
 
-    handler::send_hello_message(&mut session).await;
+    handler::send_hello_message(&state.pool, &mut session).await;
+
+    // After the hello, so the client already knows the server when its balance update arrives.
+    if let Some((address, amount)) = faucet {
+        server.grant_faucet(&state.pool, &address, amount).await;
+    }
 
     let cleanup_session =
         |server: Arc<WebSocketServer>, uuid: Uuid, session_closed: Arc<AtomicBool>| {
@@ -131,7 +156,7 @@ pub async fn gateway(
 
     // Heartbeat handling
     let heartbeat_handle = actix_web::rt::spawn(async move {
-        let mut interval = time::interval(HEARTBEAT_INTERVAL);
+        let mut interval = time::interval(server2.config.heartbeat_interval);
 
         loop {
             interval.tick().await;
@@ -142,7 +167,7 @@ pub async fn gateway(
             }
 
             let last_heartbeat = *alive2.lock().expect("alive mutex poisoned");
-            if Instant::now().duration_since(last_heartbeat) > CLIENT_TIMEOUT {
+            if Instant::now().duration_since(last_heartbeat) > server2.config.client_timeout {
                 tracing::info!("Session timed out");
 
                 // Don't call close() - it hangs when client is unresponsive.
@@ -162,13 +187,9 @@ pub async fn gateway(
                 break;
             }
 
-            let cur_time = convert_to_iso_string(Utc::now());
-            let message = WebSocketMessage {
-                ok: None,
-                id: None,
-                r#type: WebSocketMessageInner::Keepalive {
-                    server_time: Some(cur_time),
-                },
+            // In ping-only mode the ping above is all the client gets.
+            let Some(message) = server2.keepalive_message() else {
+                continue;
             };
 
             if let Ok(msg) = serde_json::to_string(&message) {
@@ -189,39 +210,27 @@ pub async fn gateway(
             while let Some(Ok(msg)) = stream.recv().await {
                 match msg {
                     AggregatedMessage::Ping(bytes) => {
-                        if session.pong(&bytes).await.is_err() {
-                            tracing::error!("Failed to send pong back to session");
+                        if let Err(err) = session.pong(&bytes).await {
+                            tracing::error!("Failed to send pong back to session: {err}");
                             return;
                         }
                     }
 
                     AggregatedMessage::Text(string) => {
-                        if string.chars().count() > 512 {
-                            // TODO: Possibly use error message struct in models
-                            // This isn't super necessary though and this shortcut saves some unnecessary error handling...
-                            let error_msg = json!({
-                                "ok": "false",
-                                "error": "message_too_long",
-                                "message": "Message larger than 512 characters",
-                                "type": "error"
-                            })
-                            .to_string();
-                            tracing::info!("Message received was larger than 512 characters");
-
-                            let _ = session.text(error_msg).await;
-                        } else {
-                            tracing::debug!("Message received: {string}");
-
-                            let process_result =
-                                handler::process_text_msg(&state.pool, &server, &uuid, &string)
-                                    .await;
-
-                            if let Ok(message) = process_result {
-                                let msg = serde_json::to_string(&message)
-                                    .expect("Failed to serialize message into string");
-                                let _ = session.text(msg).await;
-                            } else {
-                                tracing::error!("Error in processing message")
+                        handle_text_frame(&mut session, &state, &server, &uuid, &string).await;
+                    }
+
+                    AggregatedMessage::Binary(bytes) => {
+                        match handler::decode_binary_msg(server.config.accept_binary, &bytes) {
+                            Ok(text) => {
+                                handle_text_frame(&mut session, &state, &server, &uuid, text).await;
+                            }
+                            Err(error_msg) => {
+                                tracing::debug!("Rejected binary frame");
+
+                                if let Ok(msg) = serde_json::to_string(&error_msg) {
+                                    let _ = session.text(msg).await;
+                                }
                             }
                         }
                     }
@@ -239,8 +248,6 @@ pub async fn gateway(
                         tracing::trace!("Received a pong back! :D");
                         *alive.lock().expect("alive mutex poisoned") = Instant::now();
                     }
-
-                    _ => (), // Binary data is just ignored
                 }
             }
 
@@ -256,6 +263,191 @@ pub async fn gateway(
     Ok(response)
 }
 
+/// The IP of the client. `X-Forwarded-For` is only read when the peer is one of `trusted_proxies`,
+/// and then the right-most hop that isn't a trusted proxy is taken, since anything left of it could
+/// have been made up by the client.
+fn client_ip(req: &HttpRequest, trusted_proxies: &[IpAddr]) -> Option<IpAddr> {
+    let mut client = req.peer_addr()?.ip();
+    if !trusted_proxies.contains(&client) {
+        return Some(client);
+    }
+
+    let forwarded = req
+        .headers()
+        .get("X-Forwarded-For")
+        .and_then(|header| header.to_str().ok())
+        .unwrap_or_default();
+
+    for hop in forwarded.rsplit(',') {
+        match hop.trim().parse() {
+            Ok(ip) => client = ip,
+            // Garbage isn't an address to count against, keep the last hop that made sense.
+            Err(_) => break,
+        }
+
+        if !trusted_proxies.contains(&client) {
+            break;
+        }
+    }
+
+    Some(client)
+}
+
+async fn handle_text_frame(
+    session: &mut actix_ws::Session,
+    state: &AppState,
+    server: &WebSocketServer,
+    uuid: &Uuid,
+    string: &str,
+) {
+    if let Err(error_msg) = handler::check_msg_size(string) {
+        tracing::info!(
+            "Message received was larger than {} characters",
+            handler::MAX_MESSAGE_CHARS
+        );
+
+        if let Ok(msg) = serde_json::to_string(&error_msg) {
+            let _ = session.text(msg).await;
+        }
+    } else {
+        tracing::debug!("Message received: {string}");
+
+        let process_result = handler::process_text_msg(state, server, uuid, string).await;
+
+        if let Ok(message) = process_result {
+            let msg =
+                serde_json::to_string(&message).expect("Failed to serialize message into string");
+            let _ = session.text(msg).await;
+        } else {
+            tracing::error!("Error in processing message")
+        }
+    }
+}
+
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(web::scope("/ws").service(setup_ws).service(gateway));
 }
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{App, http::StatusCode, test};
+    use sqlx::postgres::PgPoolOptions;
+
+    use super::*;
+    use crate::websockets::WebSocketServerConfig;
+
+    async fn start_guest(allow_guests: bool) -> StatusCode {
+        let pool = PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/kromer")
+            .expect("lazy pool should not connect");
+        let mut state = AppState::new(pool);
+        state.public_url = Some("localhost:8080".to_owned());
+        let server = WebSocketServer::with_config(WebSocketServerConfig {
+            allow_guests,
+            ..Default::default()
+        });
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .app_data(web::Data::new(server))
+                .configure(config),
+        )
+        .await;
+
+        let req = test::TestRequest::post().uri("/ws/start").to_request();
+        test::call_service(&app, req).await.status()
+    }
+
+    #[actix_web::test]
+    async fn test_client_ip() {
+        let proxy: IpAddr = "192.168.1.20".parse().unwrap();
+        let request = |forwarded: Option<&str>| {
+            let req = test::TestRequest::default().peer_addr("192.168.1.20:50000".parse().unwrap());
+            match forwarded {
+                Some(forwarded) => req.insert_header(("X-Forwarded-For", forwarded)),
+                None => req,
+            }
+            .to_http_request()
+        };
+
+        assert_eq!(client_ip(&request(None), &[]), Some(proxy));
+        assert_eq!(client_ip(&request(None), &[proxy]), Some(proxy));
+
+        // A spoofed header from a peer that isn't a trusted proxy still counts against the peer.
+        let spoofed = request(Some("10.0.0.7"));
+        assert_eq!(client_ip(&spoofed, &[]), Some(proxy));
+
+        // Behind the proxy, the client can only prepend made up hops, the right-most untrusted one
+        // is what the proxy actually saw.
+        let forwarded = request(Some("1.2.3.4, 10.0.0.7"));
+        assert_eq!(client_ip(&forwarded, &[proxy]), "10.0.0.7".parse().ok());
+
+        let chained = request(Some("1.2.3.4, 10.0.0.7, 192.168.1.21"));
+        let proxies = [proxy, "192.168.1.21".parse().unwrap()];
+        assert_eq!(client_ip(&chained, &proxies), "10.0.0.7".parse().ok());
+
+        let garbage = request(Some("10.0.0.7, not-an-ip"));
+        assert_eq!(client_ip(&garbage, &[proxy]), Some(proxy));
+    }
+
+    #[actix_web::test]
+    async fn test_guest_start() {
+        assert_eq!(start_guest(false).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(start_guest(true).await, StatusCode::OK);
+    }
+
+    #[sqlx::test]
+    async fn test_faucet_waits_out_maintenance(pool: sqlx::PgPool) {
+        // The gateway spawns its session tasks onto actix's local executor
+        let local = tokio::task::LocalSet::new();
+        local
+            .run_until(async move {
+                use actix_web::http::header;
+                use rust_decimal::dec;
+
+                Wallet::create_wallet(&pool, "kalice0000", "alice", None)
+                    .await
+                    .unwrap();
+                let state = web::Data::new(AppState::new(pool.clone()));
+                let server = web::Data::new(WebSocketServer::with_config(WebSocketServerConfig {
+                    faucet_amount: Some(dec!(5)),
+                    ..Default::default()
+                }));
+                let app = test::init_service(
+                    App::new()
+                        .app_data(state.clone())
+                        .app_data(server.clone())
+                        .configure(config),
+                )
+                .await;
+
+                let connect = async || {
+                    let token_data = WebSocketTokenData::new("kalice0000".into(), None, None);
+                    let uuid = server.obtain_token(token_data);
+                    let req = test::TestRequest::get()
+                        .uri(&format!("/ws/gateway/{uuid}"))
+                        .insert_header((header::UPGRADE, "websocket"))
+                        .insert_header((header::CONNECTION, "upgrade"))
+                        .insert_header((header::SEC_WEBSOCKET_VERSION, "13"))
+                        .insert_header((header::SEC_WEBSOCKET_KEY, "dGhlIHNhbXBsZSBub25jZQ=="))
+                        .to_request();
+                    let resp = test::call_service(&app, req).await;
+                    assert_eq!(resp.status(), StatusCode::SWITCHING_PROTOCOLS);
+
+                    Wallet::fetch_by_address(&pool, "kalice0000")
+                        .await
+                        .unwrap()
+                        .unwrap()
+                        .balance
+                };
+
+                state.set_maintenance(true);
+                assert_eq!(connect().await, dec!(0));
+
+                state.set_maintenance(false);
+                assert_eq!(connect().await, dec!(5));
+            })
+            .await;
+    }
+}
@@ -1,12 +1,75 @@
 mod internal;
 pub mod krist;
 pub mod not_found;
+pub mod request_id;
 pub mod v1;
 
+use actix_cors::Cors;
 use actix_web::{HttpResponse, get, middleware, web};
+use once_cell::sync::Lazy;
 use utoipa::{IntoParams, ToSchema};
 
-use crate::{errors::krist::KristError, guards};
+use crate::{
+    errors::{
+        KromerError,
+        krist::{KristError, generic::GenericError},
+    },
+    guards,
+    utils::env,
+};
+
+/// Page size for HTTP listings when no `limit` is given
+pub static DEFAULT_PAGE_LIMIT: Lazy<i64> =
+    Lazy::new(|| default_limit(std::env::var("DEFAULT_PAGE_LIMIT").ok().as_deref(), 50));
+
+/// Largest page any listing returns, whatever `limit` asks for. A bad value is caught by
+/// [`validate_limits`] at startup rather than replaced with the default.
+pub static MAX_PAGE_LIMIT: Lazy<i64> = Lazy::new(|| {
+    max_page_limit(std::env::var("MAX_PAGE_LIMIT").ok().as_deref())
+        .unwrap_or_else(|err| panic!("{err}"))
+});
+
+fn max_page_limit(value: Option<&str>) -> Result<i64, String> {
+    match env::parse_opt::<i64>("MAX_PAGE_LIMIT", value)? {
+        Some(limit) if limit < 1 => Err(format!("MAX_PAGE_LIMIT ({limit}) must be at least 1")),
+        limit => Ok(limit.unwrap_or(1000)),
+    }
+}
+
+/// Check the page limits read from the environment, meant to be called once at startup.
+pub fn validate_limits() -> Result<(), String> {
+    max_page_limit(std::env::var("MAX_PAGE_LIMIT").ok().as_deref())?;
+    Ok(())
+}
+
+/// Clamp a requested page size to `1..=MAX_PAGE_LIMIT`. A `limit` of 0 is kept as is, it asks for
+/// just the total, so listings skip fetching rows altogether.
+pub fn page_limit(limit: i64) -> i64 {
+    clamp_page_limit(limit, *MAX_PAGE_LIMIT)
+}
+
+fn clamp_page_limit(limit: i64, max: i64) -> i64 {
+    match limit {
+        0 => 0,
+        limit => limit.clamp(1, max),
+    }
+}
+
+/// Page size for websocket listings when no `limit` is given, socket clients tend to want more at once
+pub static DEFAULT_WS_PAGE_LIMIT: Lazy<i64> =
+    Lazy::new(|| default_limit(std::env::var("DEFAULT_WS_PAGE_LIMIT").ok().as_deref(), 100));
+
+/// Parse a configured default page size, `fallback` when it is unset or not a positive number.
+fn default_limit(value: Option<&str>, fallback: i64) -> i64 {
+    match value.map(|value| value.trim().parse::<i64>()) {
+        Some(Ok(limit)) if limit > 0 => limit,
+        Some(_) => {
+            tracing::warn!("Ignoring invalid default page limit {value:?}, using {fallback}");
+            fallback
+        }
+        None => fallback,
+    }
+}
 
 #[get("/")]
 pub async fn index_get() -> Result<HttpResponse, KristError> {
@@ -14,16 +77,25 @@ pub async fn index_get() -> Result<HttpResponse, KristError> {
 }
 
 pub fn config(cfg: &mut web::ServiceConfig) {
+    let v1_json_cfg =
+        web::JsonConfig::default().error_handler(|err, _req| KromerError::JsonPayload(err).into());
     let krist_json_cfg =
         web::JsonConfig::default().error_handler(|err, _req| KristError::JsonPayload(err).into());
 
+    // A path segment that doesn't parse is reported as an invalid parameter, named after the
+    // route's first path parameter.
     let krist_path_config =
-        web::PathConfig::default().error_handler(|err, _req| KristError::Path(err).into());
+        web::PathConfig::default().error_handler(|err, req| match req.match_info().iter().next() {
+            Some((parameter, _)) => {
+                KristError::Generic(GenericError::InvalidParameter(parameter.to_owned())).into()
+            }
+            None => KristError::Path(err).into(),
+        });
 
     cfg.service(
         web::scope("/api/v1")
             .wrap(middleware::NormalizePath::trim())
-            .app_data(krist_json_cfg.clone()) // TODO: Custom.
+            .app_data(v1_json_cfg)
             .app_data(krist_path_config.clone())
             .configure(v1::config),
     );
@@ -43,6 +115,33 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(web::scope("").service(index_get));
 }
 
+/// Origins allowed to make cross-origin requests, read from the comma separated `CORS_ALLOWED_ORIGINS`.
+/// When unset any origin is allowed.
+pub static CORS_ALLOWED_ORIGINS: Lazy<Option<Vec<String>>> = Lazy::new(|| {
+    std::env::var("CORS_ALLOWED_ORIGINS").ok().map(|origins| {
+        origins
+            .split(',')
+            .map(str::trim)
+            .filter(|origin| !origin.is_empty())
+            .map(str::to_owned)
+            .collect()
+    })
+});
+
+/// Build the CORS middleware, restricted to `allowed_origins` when given.
+pub fn cors(allowed_origins: Option<&[String]>) -> Cors {
+    let cors = match allowed_origins {
+        Some(origins) => origins
+            .iter()
+            .fold(Cors::default(), |cors, origin| cors.allowed_origin(origin)),
+        None => Cors::default().allow_any_origin(),
+    };
+
+    cors.allowed_methods(["GET", "POST", "PUT", "PATCH", "DELETE"])
+        .allow_any_header()
+        .max_age(3600)
+}
+
 #[derive(Debug, serde::Deserialize, serde::Serialize, ToSchema, IntoParams)]
 pub struct PaginationParams {
     #[serde(alias = "excludeMined")]
@@ -56,8 +155,325 @@ impl Default for PaginationParams {
     fn default() -> Self {
         Self {
             exclude_mined: None,
-            limit: Some(50),
+            limit: Some(*DEFAULT_PAGE_LIMIT),
             offset: Some(0),
         }
     }
 }
+
+/// Direction of a sorted listing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_uppercase().as_str() {
+            "ASC" => Some(Self::Asc),
+            "DESC" => Some(Self::Desc),
+            _ => None,
+        }
+    }
+
+    /// The SQL keyword for this direction
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            Self::Asc => "ASC",
+            Self::Desc => "DESC",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        App,
+        http::header,
+        test::{TestRequest, call_service, init_service, read_body_json},
+    };
+
+    use rust_decimal::dec;
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::database::transaction::{
+        Model as Transaction, TransactionCreateData, TransactionType,
+    };
+    use crate::database::wallet::Model as Wallet;
+
+    #[test]
+    fn test_default_pagination_uses_configured_limit() {
+        assert_eq!(PaginationParams::default().limit, Some(*DEFAULT_PAGE_LIMIT));
+    }
+
+    #[test]
+    fn test_default_limit() {
+        assert_eq!(default_limit(None, 50), 50);
+        assert_eq!(default_limit(Some("250"), 50), 250);
+        assert_eq!(default_limit(Some(" 250 "), 100), 250);
+        assert_eq!(default_limit(Some("lots"), 50), 50);
+        assert_eq!(default_limit(Some("0"), 100), 100);
+    }
+
+    #[test]
+    fn test_configured_max_page_limit() {
+        assert_eq!(clamp_page_limit(5000, 200), 200);
+        assert_eq!(clamp_page_limit(150, 200), 150);
+        assert_eq!(clamp_page_limit(0, 200), 0);
+    }
+
+    #[test]
+    fn test_max_page_limit() {
+        assert_eq!(max_page_limit(None), Ok(1000));
+        assert_eq!(max_page_limit(Some("200")), Ok(200));
+        assert!(max_page_limit(Some("0")).is_err());
+        assert!(max_page_limit(Some("-5")).is_err());
+        let err = max_page_limit(Some("lots")).unwrap_err();
+        assert!(err.contains("MAX_PAGE_LIMIT"), "{err}");
+    }
+
+    #[test]
+    fn test_page_limit() {
+        assert_eq!(page_limit(0), 0);
+        assert_eq!(page_limit(-5), 1);
+        assert_eq!(page_limit(5000), *MAX_PAGE_LIMIT);
+    }
+
+    #[sqlx::test]
+    async fn test_zero_limit_skips_rows(pool: sqlx::PgPool) {
+        for address in ["kalice0000", "kbob000000"] {
+            Wallet::create_wallet(&pool, address, "hash", Some(dec!(10)))
+                .await
+                .unwrap();
+        }
+        for _ in 0..3 {
+            let data = TransactionCreateData {
+                from: "kalice0000".into(),
+                to: "kbob000000".into(),
+                amount: dec!(1.00),
+                transaction_type: TransactionType::Transfer,
+                ..Default::default()
+            };
+            Transaction::create_no_update(&pool, data).await.unwrap();
+        }
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(crate::AppState::new(pool)))
+                .configure(config),
+        )
+        .await;
+
+        // The page is empty but the total is still counted, wallets include `serverwelf`
+        for (uri, list, total) in [
+            ("/api/krist/transactions?limit=0", "transactions", 3),
+            ("/api/krist/addresses/rich?limit=0", "addresses", 3),
+        ] {
+            let resp = call_service(&app, TestRequest::get().uri(uri).to_request()).await;
+            assert!(resp.status().is_success(), "{uri}");
+            let body: Value = read_body_json(resp).await;
+            assert_eq!(body["count"], 0, "{uri}");
+            assert_eq!(body["total"], total, "{uri}");
+            assert_eq!(body[list], json!([]), "{uri}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_unknown_v1_path_is_not_found() {
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/kromer")
+            .expect("lazy pool should not connect");
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(crate::AppState::new(pool)))
+                .configure(config)
+                .default_service(web::route().to(not_found::not_found)),
+        )
+        .await;
+
+        let req = TestRequest::get().uri("/api/v1/nothing/here").to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::NOT_FOUND);
+
+        let body: serde_json::Value = read_body_json(resp).await;
+        assert_eq!(body["ok"], false);
+        assert_eq!(body["error"]["code"], "not_found");
+        assert_eq!(body["error"]["message"], "Resource not found");
+    }
+
+    #[actix_web::test]
+    async fn test_malformed_body_names_field() {
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/kromer")
+            .expect("lazy pool should not connect");
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(crate::AppState::new(pool)))
+                .app_data(web::Data::new(crate::websockets::WebSocketServer::new()))
+                .configure(config),
+        )
+        .await;
+
+        let req = TestRequest::post()
+            .uri("/api/krist/login")
+            .set_json(serde_json::json!({}))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+
+        let body: serde_json::Value = read_body_json(resp).await;
+        assert_eq!(body["error"], "missing_parameter");
+        assert_eq!(body["parameter"], "privatekey");
+        assert_eq!(body["message"], "Missing parameter privatekey");
+
+        let req = TestRequest::post()
+            .uri("/api/krist/transactions")
+            .set_json(
+                serde_json::json!({ "privatekey": "a", "to": "kabcdefghi", "amount": "lots" }),
+            )
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+
+        let body: serde_json::Value = read_body_json(resp).await;
+        assert_eq!(body["error"], "invalid_parameter");
+        assert_eq!(body["parameter"], "amount");
+        assert_eq!(body["message"], "Invalid parameter amount");
+        assert!(
+            body["info"]
+                .as_str()
+                .is_some_and(|info| info.contains("lots"))
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_responses_are_compressed() {
+        let app = init_service(
+            App::new()
+                .wrap(middleware::Compress::default())
+                .route(
+                    "/",
+                    web::get().to(|| async { HttpResponse::Ok().json(vec![0; 512]) }),
+                )
+                .route(
+                    "/identity",
+                    web::get().to(|| async {
+                        HttpResponse::Ok()
+                            .insert_header(header::ContentEncoding::Identity)
+                            .body("streamed")
+                    }),
+                ),
+        )
+        .await;
+
+        let request = |uri| {
+            TestRequest::get()
+                .uri(uri)
+                .insert_header((header::ACCEPT_ENCODING, "gzip"))
+                .to_request()
+        };
+
+        let resp = call_service(&app, request("/")).await;
+        assert_eq!(
+            resp.headers().get(header::CONTENT_ENCODING).unwrap(),
+            "gzip"
+        );
+
+        let resp = call_service(&app, request("/identity")).await;
+        assert_eq!(
+            resp.headers().get(header::CONTENT_ENCODING).unwrap(),
+            "identity"
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_cors_preflight_allows_patch_and_delete() {
+        let app = init_service(
+            App::new()
+                .wrap(cors(None))
+                .route("/{id}", web::patch().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        for method in ["PATCH", "DELETE"] {
+            let req = TestRequest::default()
+                .method(actix_web::http::Method::OPTIONS)
+                .uri("/1")
+                .insert_header((header::ORIGIN, "https://example.com"))
+                .insert_header((header::ACCESS_CONTROL_REQUEST_METHOD, method))
+                .to_request();
+            let resp = call_service(&app, req).await;
+
+            assert!(
+                resp.status().is_success(),
+                "{method} preflight was rejected"
+            );
+            let allowed = resp
+                .headers()
+                .get(header::ACCESS_CONTROL_ALLOW_METHODS)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default();
+            assert!(allowed.contains(method), "{method} missing from {allowed}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_cors_restricted_origins() {
+        let origins = ["https://kromer.example".to_owned()];
+        let app = init_service(
+            App::new()
+                .wrap(cors(Some(&origins)))
+                .route("/{id}", web::patch().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let preflight = |origin: &str, method: &str| {
+            TestRequest::default()
+                .method(actix_web::http::Method::OPTIONS)
+                .uri("/1")
+                .insert_header((header::ORIGIN, origin))
+                .insert_header((header::ACCESS_CONTROL_REQUEST_METHOD, method))
+                .to_request()
+        };
+
+        for method in ["PATCH", "DELETE"] {
+            let resp = call_service(&app, preflight("https://kromer.example", method)).await;
+            assert!(
+                resp.status().is_success(),
+                "{method} preflight was rejected"
+            );
+            assert_eq!(
+                resp.headers()
+                    .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+                    .unwrap(),
+                "https://kromer.example"
+            );
+
+            let resp = call_service(&app, preflight("https://evil.example", method)).await;
+            assert!(
+                resp.status().is_client_error(),
+                "{method} preflight from an unlisted origin was allowed"
+            );
+            assert!(
+                resp.headers()
+                    .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+                    .is_none()
+            );
+        }
+
+        // Plain requests are still served for non-browser clients, but without the header a
+        // browser won't hand the response to the unlisted origin
+        let req = TestRequest::patch()
+            .uri("/1")
+            .insert_header((header::ORIGIN, "https://evil.example"))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert!(
+            resp.headers()
+                .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,159 @@
+use actix_web::{
+    Error, HttpMessage,
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderName, HeaderValue},
+    middleware::{Logger, Next},
+};
+use tracing::Instrument;
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Longest `X-Request-Id` taken from a client, anything longer is replaced with a fresh id.
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+/// The id of the current request, available from the request extensions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+/// The access log, with the request id of each request. It has to be registered after (so it wraps)
+/// [`request_id`], otherwise it reads the response before the id is added to it.
+pub fn access_log() -> Logger {
+    Logger::new(
+        r#"%a "%r" %s %b "%{Referer}i" "%{User-Agent}i" "%{X-CC-ID}i" "%{X-Request-Id}o" %T"#,
+    )
+}
+
+/// Take the request id from `X-Request-Id` or make one up, run the request inside a span carrying
+/// it and echo it back on the response.
+pub async fn request_id(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty() && value.len() <= MAX_REQUEST_ID_LENGTH)
+        .map(str::to_owned)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    req.extensions_mut().insert(RequestId(id.clone()));
+
+    let span = tracing::info_span!("request", request_id = %id);
+    let mut res = next.call(req).instrument(span).await?;
+
+    // Either came in as a valid header value or is a uuid, so this can't fail.
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        res.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        App, HttpResponse,
+        middleware::from_fn,
+        test::{TestRequest, call_service, init_service, read_body},
+        web,
+    };
+
+    use std::sync::{Mutex, OnceLock};
+
+    use super::*;
+
+    /// Keeps the access log lines, the logger is process wide so it is only ever installed once.
+    struct AccessLogCapture(Mutex<Vec<String>>);
+
+    impl log::Log for AccessLogCapture {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata
+                .target()
+                .starts_with("actix_web::middleware::logger")
+        }
+
+        fn log(&self, record: &log::Record) {
+            if self.enabled(record.metadata()) {
+                self.0.lock().unwrap().push(record.args().to_string());
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    fn access_log_lines() -> &'static Mutex<Vec<String>> {
+        static CAPTURE: OnceLock<&'static AccessLogCapture> = OnceLock::new();
+
+        let capture = CAPTURE.get_or_init(|| {
+            let capture = Box::leak(Box::new(AccessLogCapture(Mutex::new(Vec::new()))));
+            log::set_logger(capture).expect("another logger was installed");
+            log::set_max_level(log::LevelFilter::Info);
+            capture
+        });
+        &capture.0
+    }
+
+    #[actix_web::test]
+    async fn test_access_log_has_request_id() {
+        let lines = access_log_lines();
+        let app = init_service(
+            App::new()
+                .wrap(from_fn(request_id))
+                .wrap(access_log())
+                .route("/logged", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let resp = call_service(&app, TestRequest::get().uri("/logged").to_request()).await;
+        let generated = resp
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .expect("missing request id")
+            .to_str()
+            .expect("request id is not a string")
+            .to_owned();
+        // The line is written once the body has been sent
+        read_body(resp).await;
+
+        let lines = lines.lock().unwrap();
+        let line = lines
+            .iter()
+            .find(|line| line.contains("GET /logged"))
+            .expect("request was not logged");
+        assert!(line.contains(&format!("\"{generated}\"")), "{line}");
+    }
+
+    #[actix_web::test]
+    async fn test_request_id_header() {
+        let app = init_service(
+            App::new()
+                .wrap(from_fn(request_id))
+                .route("/", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let req = TestRequest::get()
+            .uri("/")
+            .insert_header((REQUEST_ID_HEADER, "shop-order-42"))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(
+            resp.headers()
+                .get(REQUEST_ID_HEADER)
+                .expect("missing request id"),
+            "shop-order-42"
+        );
+
+        let resp = call_service(&app, TestRequest::get().uri("/").to_request()).await;
+        let generated = resp
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .expect("missing request id")
+            .to_str()
+            .expect("request id is not a string");
+        assert!(Uuid::parse_str(generated).is_ok());
+    }
+}
@@ -1,3 +1,5 @@
+pub mod stats;
+pub mod transactions;
 pub mod wallet;
 pub mod ws;
 
@@ -8,6 +10,7 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     // cfg.service(version_get);
     cfg.configure(wallet::config);
     cfg.configure(ws::config);
-    // cfg.configure(transaction::config);
+    cfg.configure(transactions::config);
+    cfg.configure(stats::config);
     // cfg.configure(name::config);
 }
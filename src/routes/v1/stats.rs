@@ -0,0 +1,176 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use actix_web::{HttpResponse, get, web};
+
+use crate::database::ModelExt;
+use crate::database::ledger::WELFARE_ADDRESS;
+use crate::database::name::Model as Name;
+use crate::database::transaction::Model as Transaction;
+use crate::database::wallet::Model as Wallet;
+use crate::models::kromer::{responses::ApiResponse, stats::Stats};
+use crate::websockets::WebSocketServer;
+use crate::{AppState, errors::KromerError};
+
+/// How long computed stats are served before the database is asked again
+const STATS_CACHE_TTL: Duration = Duration::from_secs(5);
+
+/// The last computed [`Stats`] and when they were computed.
+#[derive(Debug)]
+pub struct StatsCache {
+    ttl: Duration,
+    entry: Mutex<Option<(Instant, Stats)>>,
+}
+
+impl StatsCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entry: Mutex::new(None),
+        }
+    }
+
+    /// The cached stats, unless they are older than the TTL.
+    fn get(&self) -> Option<Stats> {
+        let entry = self.entry.lock().expect("stats cache poisoned");
+
+        entry
+            .as_ref()
+            .filter(|(computed_at, _)| computed_at.elapsed() < self.ttl)
+            .map(|(_, stats)| stats.clone())
+    }
+
+    fn store(&self, stats: Stats) {
+        *self.entry.lock().expect("stats cache poisoned") = Some((Instant::now(), stats));
+    }
+}
+
+impl Default for StatsCache {
+    fn default() -> Self {
+        Self::new(STATS_CACHE_TTL)
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/stats",
+    responses(
+        (status = 200, description = "Totals for the status page, cached for a few seconds", body = ApiResponse<Stats>),
+    )
+)]
+#[get("/stats")]
+async fn stats_get(
+    state: web::Data<AppState>,
+    server: web::Data<WebSocketServer>,
+) -> Result<HttpResponse, KromerError> {
+    let mut stats = match state.stats_cache.get() {
+        Some(stats) => stats,
+        None => {
+            let pool = &state.pool;
+
+            let (transactions, names, wallets, money_supply) = tokio::join!(
+                Transaction::total_count(pool),
+                Name::total_count(pool),
+                Wallet::total_count(pool),
+                Wallet::money_supply(pool, &WELFARE_ADDRESS),
+            );
+            let stats = Stats {
+                transactions: transactions?,
+                names: names?,
+                wallets: wallets?,
+                money_supply: money_supply?,
+                sessions: 0,
+            };
+
+            state.stats_cache.store(stats.clone());
+
+            stats
+        }
+    };
+
+    // Counting sessions is cheap, so that one is always live.
+    stats.sessions = server.sessions.len();
+
+    let response = ApiResponse {
+        data: Some(stats),
+        ..Default::default()
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(stats_get);
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        App,
+        test::{TestRequest, call_service, init_service, read_body_json},
+    };
+    use rust_decimal::{Decimal, dec};
+    use serde_json::Value;
+    use sqlx::postgres::PgPoolOptions;
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_stats_fields() {
+        let pool = PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/kromer")
+            .expect("lazy pool should not connect");
+
+        // Fresh stats are served from the cache, so the lazy pool is never used.
+        let stats = Stats {
+            transactions: 120,
+            names: 4,
+            wallets: 9,
+            money_supply: dec!(1500.25),
+            sessions: 0,
+        };
+        let state = AppState::new(pool);
+        state.stats_cache.store(stats);
+
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .app_data(web::Data::new(WebSocketServer::new()))
+                .configure(config),
+        )
+        .await;
+
+        let resp = call_service(&app, TestRequest::get().uri("/stats").to_request()).await;
+        assert!(resp.status().is_success());
+
+        let body: Value = read_body_json(resp).await;
+        for field in ["transactions", "names", "wallets", "sessions"] {
+            let value = body["data"][field].as_u64();
+            assert!(value.is_some(), "{field} should be a non-negative count");
+        }
+        let money_supply: Decimal = serde_json::from_value(body["data"]["money_supply"].clone())
+            .expect("money_supply should be a number");
+        assert!(money_supply >= Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_stats_cache_ttl() {
+        let stats = Stats {
+            transactions: 1,
+            names: 0,
+            wallets: 1,
+            money_supply: dec!(10),
+            sessions: 0,
+        };
+
+        let cache = StatsCache::new(STATS_CACHE_TTL);
+        assert!(cache.get().is_none());
+        cache.store(stats.clone());
+        assert_eq!(cache.get().map(|stats| stats.transactions), Some(1));
+
+        // Nothing is fresh with a zero TTL, so every request goes to the database
+        let expired = StatsCache::new(Duration::ZERO);
+        expired.store(stats);
+        assert!(expired.get().is_none());
+    }
+}
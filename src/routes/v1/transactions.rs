@@ -0,0 +1,227 @@
+use actix_web::{HttpResponse, get, http::header::ContentEncoding, web};
+use futures_util::{StreamExt, stream};
+use tokio::sync::mpsc;
+
+use crate::database::transaction::Model as Transaction;
+use crate::models::krist::transactions::TransactionJson;
+use crate::models::kromer::responses::ApiResponse;
+use crate::models::kromer::transactions::{ExportFormat, ExportQuery, ExportRow, MetadataQuery};
+use crate::routes::DEFAULT_PAGE_LIMIT;
+use crate::utils::validation;
+use crate::{AppState, errors::KromerError};
+
+/// How many formatted rows may be waiting on a slow client before we stop reading from the cursor
+const EXPORT_BUFFER_ROWS: usize = 64;
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/transactions/export",
+    params(ExportQuery),
+    responses(
+        (status = 200, description = "Transactions touching the given addresses, as CSV or NDJSON"),
+        (status = 400, description = "Invalid address list")
+    )
+)]
+#[get("/export")]
+async fn transaction_export(
+    state: web::Data<AppState>,
+    query: web::Query<ExportQuery>,
+) -> Result<HttpResponse, KromerError> {
+    let query = query.into_inner();
+    let format = query.format.unwrap_or_default();
+
+    if !validation::is_valid_kromer_address_list(&query.addresses) {
+        return Err(KromerError::Validation("Invalid address list".to_owned()));
+    }
+    let addresses: Vec<String> = query.addresses.split(',').map(str::to_owned).collect();
+
+    // The cursor borrows the pool, so it is driven from its own task and handed over a bounded channel.
+    let pool = state.pool.clone();
+    let (sender, receiver) = mpsc::channel::<Result<web::Bytes, KromerError>>(EXPORT_BUFFER_ROWS);
+
+    actix_web::rt::spawn(async move {
+        if format == ExportFormat::Csv
+            && sender
+                .send(Ok(web::Bytes::from_static(
+                    ExportRow::CSV_HEADER.as_bytes(),
+                )))
+                .await
+                .is_err()
+        {
+            return;
+        }
+
+        let mut rows = Transaction::stream_by_addresses(&pool, &addresses);
+        while let Some(row) = rows.next().await {
+            let chunk = row.map(|transaction| {
+                let row = ExportRow::from(transaction);
+                let line = match format {
+                    ExportFormat::Csv => row.to_csv_line(),
+                    ExportFormat::Ndjson => row.to_ndjson_line(),
+                };
+
+                web::Bytes::from(line)
+            });
+
+            let failed = chunk.is_err();
+            if let Err(err) = &chunk {
+                tracing::error!("Failed to export transactions: {err}");
+            }
+
+            if sender.send(chunk.map_err(KromerError::from)).await.is_err() || failed {
+                break;
+            }
+        }
+    });
+
+    let body = stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|chunk| (chunk, receiver))
+    });
+
+    let content_type = match format {
+        ExportFormat::Csv => "text/csv",
+        ExportFormat::Ndjson => "application/x-ndjson",
+    };
+
+    // Keep the export out of the compression middleware, it is sent as it is produced.
+    Ok(HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header(ContentEncoding::Identity)
+        .streaming(body))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/transactions/by-metadata",
+    params(MetadataQuery),
+    responses(
+        (status = 200, description = "Transactions whose structured metadata contains the given object", body = ApiResponse<Vec<TransactionJson>>),
+        (status = 400, description = "`contains` is not a JSON object")
+    )
+)]
+#[get("/by-metadata")]
+async fn transaction_by_metadata(
+    state: web::Data<AppState>,
+    query: web::Query<MetadataQuery>,
+) -> Result<HttpResponse, KromerError> {
+    let query = query.into_inner();
+    let contains = query
+        .contains_object()
+        .ok_or_else(|| KromerError::Validation("contains must be a JSON object".to_owned()))?;
+
+    let transactions = Transaction::fetch_by_metadata_json(
+        &state.pool,
+        &contains,
+        query.limit.unwrap_or(*DEFAULT_PAGE_LIMIT),
+        query.offset.unwrap_or(0),
+    )
+    .await?;
+
+    let response = ApiResponse {
+        data: Some(
+            transactions
+                .into_iter()
+                .map(TransactionJson::from)
+                .collect::<Vec<_>>(),
+        ),
+        ..Default::default()
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/transactions")
+            .service(transaction_export)
+            .service(transaction_by_metadata),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        App,
+        http::StatusCode,
+        test::{TestRequest, call_service, init_service, read_body_json},
+    };
+    use rust_decimal::dec;
+    use serde_json::{Value, json};
+
+    use crate::database::transaction::MAX_METADATA_JSON_LENGTH;
+    use crate::database::wallet::Model as Wallet;
+    use crate::websockets::WebSocketServer;
+    use crate::{AppState, routes};
+
+    #[sqlx::test]
+    async fn test_find_by_metadata_json(pool: sqlx::PgPool) {
+        let sender = Wallet::verify_address(&pool, "hunter2")
+            .await
+            .unwrap()
+            .model;
+        sender.update_balance(&pool, dec!(100)).await.unwrap();
+        Wallet::create_wallet(&pool, "kbob000000", "bob", None)
+            .await
+            .unwrap();
+        let app = init_service(
+            App::new()
+                .app_data(actix_web::web::Data::new(AppState::new(pool)))
+                .app_data(actix_web::web::Data::new(WebSocketServer::new()))
+                .configure(routes::config),
+        )
+        .await;
+
+        let send = |amount: i64, metadata_json: Value| {
+            TestRequest::post()
+                .uri("/api/krist/transactions")
+                .set_json(json!({
+                    "privatekey": "hunter2",
+                    "to": "kbob000000",
+                    "amount": amount,
+                    "metadataJson": metadata_json,
+                }))
+                .to_request()
+        };
+        let mut ids = Vec::new();
+        for (amount, metadata_json) in [
+            (5, json!({ "order": { "id": 42 }, "shop": "kiosk" })),
+            (6, json!({ "order": { "id": 43 }, "shop": "kiosk" })),
+        ] {
+            let resp = call_service(&app, send(amount, metadata_json)).await;
+            assert!(resp.status().is_success(), "{}", resp.status());
+            let body: Value = read_body_json(resp).await;
+            ids.push(body["transaction"]["id"].as_i64().unwrap());
+        }
+
+        let find = |contains: &str| {
+            let encoded: String = contains.bytes().map(|b| format!("%{b:02X}")).collect();
+            TestRequest::get()
+                .uri(&format!(
+                    "/api/v1/transactions/by-metadata?contains={encoded}"
+                ))
+                .to_request()
+        };
+        let found = |body: Value| -> Vec<i64> {
+            body["data"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|transaction| transaction["id"].as_i64().unwrap())
+                .collect()
+        };
+
+        let resp = call_service(&app, find(r#"{"order":{"id":42}}"#)).await;
+        assert!(resp.status().is_success());
+        assert_eq!(found(read_body_json(resp).await), vec![ids[0]]);
+
+        let resp = call_service(&app, find(r#"{"shop":"kiosk"}"#)).await;
+        assert_eq!(found(read_body_json(resp).await), vec![ids[1], ids[0]]);
+
+        // Structured metadata has its own cap, the 2 MB body limit isn't one
+        let oversized = json!({ "note": "a".repeat(MAX_METADATA_JSON_LENGTH) });
+        let resp = call_service(&app, send(7, oversized)).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: Value = read_body_json(resp).await;
+        assert_eq!(body["error"], "metadata_too_long");
+    }
+}
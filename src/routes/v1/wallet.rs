@@ -5,6 +5,7 @@ use crate::database::ModelExt;
 use crate::database::player::Model as Player;
 
 use crate::errors::player::PlayerError;
+use crate::models::kromer::players::Player as PlayerResponse;
 use crate::models::kromer::responses::ApiResponse;
 use crate::models::kromer::wallets::Wallet as WalletResponse;
 use crate::{AppState, errors::KromerError};
@@ -91,10 +92,94 @@ async fn wallet_get_by_name(
     Ok(HttpResponse::Ok().json(response))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/wallet/by-address/{address}/owner",
+    params(
+        ("address", description = "Wallet address")
+    ),
+    responses(
+        (status = 200, description = "Player owning the wallet", body = ApiResponse<PlayerResponse>),
+        (status = 404, description = "The address does not exist or nobody owns it")
+    )
+)]
+#[get("/by-address/{address}/owner")]
+async fn wallet_get_owner(
+    state: web::Data<AppState>,
+    address: web::Path<String>,
+) -> Result<HttpResponse, KromerError> {
+    let address = address.into_inner();
+    let pool = &state.pool;
+
+    let player = Player::fetch_by_wallet_address(pool, &address)
+        .await?
+        .ok_or_else(|| KromerError::Player(PlayerError::NotFound))?;
+
+    let response = ApiResponse {
+        data: Some(PlayerResponse::from(player)),
+        ..Default::default()
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/wallet")
+            .service(wallet_get_owner)
             .service(wallet_get_by_name)
             .service(wallet_get_by_uuid),
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        App,
+        http::StatusCode,
+        test::{TestRequest, call_service, init_service, read_body_json},
+    };
+    use serde_json::Value;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::database::wallet::Model as Wallet;
+
+    #[sqlx::test]
+    async fn test_wallet_owner(pool: sqlx::PgPool) {
+        let owned = Wallet::create_wallet(&pool, "kalice0000", "alice", None)
+            .await
+            .unwrap();
+        Wallet::create_wallet(&pool, "kbob000000", "bob", None)
+            .await
+            .unwrap();
+        let alice = Player::create(&pool, Uuid::new_v4(), "alice".to_owned())
+            .await
+            .unwrap();
+        alice.add_wallet_to_owned(&pool, &owned).await.unwrap();
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new(pool)))
+                .configure(config),
+        )
+        .await;
+
+        let req = TestRequest::get()
+            .uri("/wallet/by-address/kalice0000/owner")
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert!(resp.status().is_success());
+        let body: Value = read_body_json(resp).await;
+        assert_eq!(body["data"]["uuid"], alice.id.to_string());
+        assert_eq!(body["data"]["name"], "alice");
+
+        // Nobody owns the wallet, or there is no wallet at all
+        for address in ["kbob000000", "knobody000"] {
+            let req = TestRequest::get()
+                .uri(&format!("/wallet/by-address/{address}/owner"))
+                .to_request();
+            let resp = call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::NOT_FOUND, "{address}");
+        }
+    }
+}
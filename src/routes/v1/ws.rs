@@ -1,7 +1,10 @@
 use actix_web::{HttpResponse, get, web};
 
+use std::collections::BTreeMap;
+
 use crate::{
     errors::KromerError,
+    guards,
     models::kromer::{responses::ApiResponse, websockets::SessionCountResponse},
     websockets::WebSocketServer,
 };
@@ -29,6 +32,29 @@ async fn ws_session_get_count(
     Ok(HttpResponse::Ok().json(response))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/ws/subscriptions",
+    responses(
+        (status = 200, description = "Sessions subscribed to each event type, requires the internal key", body = ApiResponse<BTreeMap<String, usize>>),
+    )
+)]
+#[get("/subscriptions", guard = "guards::internal_key_guard")]
+async fn ws_subscriptions_get_counts(
+    server: web::Data<WebSocketServer>,
+) -> Result<HttpResponse, KromerError> {
+    let response = ApiResponse {
+        data: Some(server.subscription_counts()),
+        ..Default::default()
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
 pub fn config(cfg: &mut web::ServiceConfig) {
-    cfg.service(web::scope("/ws").service(ws_session_get_count));
+    cfg.service(
+        web::scope("/ws")
+            .service(ws_session_get_count)
+            .service(ws_subscriptions_get_counts),
+    );
 }
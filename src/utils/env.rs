@@ -0,0 +1,75 @@
+use std::{env, fmt::Display, str::FromStr};
+
+/// Read and parse an environment variable, falling back to `default` when it is unset or invalid.
+pub fn var_or<T>(key: &str, default: T) -> T
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    parse_or(key, env::var(key).ok().as_deref(), default)
+}
+
+/// Read and parse an environment variable that has no default. Unset is `Ok(None)`, but a value
+/// that doesn't parse is an error, for settings where guessing would be worse than not starting.
+pub fn var_opt<T>(key: &str) -> Result<Option<T>, String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    parse_opt(key, env::var(key).ok().as_deref())
+}
+
+/// [`var_or`] for a `value` that was already looked up, `key` only names it in the warning.
+pub fn parse_or<T>(key: &str, value: Option<&str>, default: T) -> T
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match value {
+        Some(value) => value.trim().parse().unwrap_or_else(|err| {
+            tracing::warn!("Ignoring invalid value for {key} ({err}), using the default");
+            default
+        }),
+        None => default,
+    }
+}
+
+/// [`var_opt`] for a `value` that was already looked up, `key` only names it in the error.
+pub fn parse_opt<T>(key: &str, value: Option<&str>) -> Result<Option<T>, String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    value
+        .map(|value| {
+            value
+                .trim()
+                .parse()
+                .map_err(|err| format!("Invalid value for {key}: {err}"))
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_var_or() {
+        assert_eq!(var_or("KROMER_TEST_UNSET_VAR", 50i64), 50);
+
+        assert_eq!(parse_or("PAGE_LIMIT", Some("250"), 50i64), 250);
+        assert_eq!(parse_or("PAGE_LIMIT", Some("lots"), 50i64), 50);
+        assert_eq!(parse_or("PAGE_LIMIT", None, 50i64), 50);
+    }
+
+    #[test]
+    fn test_var_opt() {
+        assert_eq!(var_opt::<i64>("KROMER_TEST_UNSET_VAR"), Ok(None));
+
+        assert_eq!(parse_opt::<i64>("CAP", Some(" 250 ")), Ok(Some(250)));
+        assert_eq!(parse_opt::<i64>("CAP", None), Ok(None));
+        let err = parse_opt::<i64>("BAD_CAP", Some("lots")).unwrap_err();
+        assert!(err.contains("BAD_CAP"), "{err}");
+    }
+}
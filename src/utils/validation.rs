@@ -23,6 +23,12 @@ pub fn is_valid_name(name: &str, fetching: bool) -> bool {
     }
 }
 
+/// Normalize a name to the form it is stored in, trimmed and lowercase.
+#[inline(always)]
+pub fn normalize_name(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
 #[inline(always)]
 pub fn is_valid_kromer_address(address: &str) -> bool {
     ADDRESS_RE_V2.is_match(address)
@@ -42,3 +48,20 @@ pub fn is_valid_a_record(a: &str) -> bool {
 pub fn strip_name_suffix(name: &str) -> String {
     name.replace(".kro", "")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mixed_case_names_collide() {
+        let registered = normalize_name("Foo");
+
+        for attempt in ["foo", "FOO", " fOo ", "foo\n"] {
+            assert_eq!(normalize_name(attempt), registered);
+        }
+
+        assert_ne!(normalize_name("foo2"), registered);
+        assert!(is_valid_name(&registered, false));
+    }
+}
@@ -0,0 +1,248 @@
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use sqlx::{Pool, Postgres};
+use tokio::sync::mpsc;
+use tokio_retry2::strategy::ExponentialBackoff;
+use tokio_retry2::{Retry, RetryError};
+
+use crate::database::webhook::Model as Webhook;
+use crate::models::krist::websockets::{WebSocketEvent, WebSocketMessage, WebSocketMessageInner};
+
+/// The events a webhook can be registered for
+pub const WEBHOOK_EVENTS: [&str; 2] = ["transactions", "names"];
+
+/// Header carrying `sha256=<hex HMAC-SHA256 of the body>`, keyed with the webhook's secret
+pub const SIGNATURE_HEADER: &str = "X-Kromer-Signature";
+
+/// How many times a failed delivery is retried before it is given up on
+const MAX_RETRIES: usize = 4;
+
+/// How long a single delivery attempt may take
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The webhook event `event` is delivered as, `None` for events webhooks can't subscribe to.
+pub fn event_kind(event: &WebSocketEvent) -> Option<&'static str> {
+    match event {
+        WebSocketEvent::Transaction { .. } => Some("transactions"),
+        WebSocketEvent::Name { .. } => Some("names"),
+        WebSocketEvent::Block { .. } | WebSocketEvent::Balance { .. } => None,
+    }
+}
+
+/// The value of the [`SIGNATURE_HEADER`] for `body`.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Hands broadcast events to the background task delivering them, so broadcasting never waits
+/// on a webhook.
+#[derive(Debug, Clone)]
+pub struct WebhookDispatcher {
+    sender: mpsc::UnboundedSender<(&'static str, String)>,
+}
+
+impl WebhookDispatcher {
+    /// Start the delivery task, looking up the registered webhooks in `pool`.
+    pub fn spawn(pool: Pool<Postgres>) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        actix_web::rt::spawn(run(pool, receiver));
+
+        Self { sender }
+    }
+
+    /// Queue `event` for delivery if it is one webhooks can subscribe to.
+    pub fn dispatch(&self, event: &WebSocketMessage) {
+        let WebSocketMessageInner::Event { event: inner, .. } = &event.r#type else {
+            return;
+        };
+        let Some(kind) = event_kind(inner) else {
+            return;
+        };
+
+        let body = match serde_json::to_string(event) {
+            Ok(body) => body,
+            Err(err) => {
+                tracing::error!("Failed to serialize webhook payload: {err}");
+                return;
+            }
+        };
+
+        if self.sender.send((kind, body)).is_err() {
+            tracing::error!("Webhook delivery task is gone, dropping {kind} event");
+        }
+    }
+}
+
+async fn run(pool: Pool<Postgres>, mut receiver: mpsc::UnboundedReceiver<(&'static str, String)>) {
+    let client = reqwest::Client::builder()
+        .timeout(DELIVERY_TIMEOUT)
+        .build()
+        .expect("Failed to build webhook HTTP client");
+
+    while let Some((kind, body)) = receiver.recv().await {
+        let webhooks = match Webhook::fetch_by_event(&pool, kind).await {
+            Ok(webhooks) => webhooks,
+            Err(err) => {
+                tracing::error!("Failed to fetch webhooks for {kind} event: {err}");
+                continue;
+            }
+        };
+
+        for webhook in webhooks {
+            let client = client.clone();
+            let body = body.clone();
+
+            // Each webhook retries on its own, a slow one must not hold up the rest.
+            actix_web::rt::spawn(async move {
+                if let Err(err) = deliver(&client, &webhook, body).await {
+                    tracing::warn!(
+                        "Giving up on delivering {kind} event to webhook {}: {err}",
+                        webhook.id
+                    );
+                }
+            });
+        }
+    }
+}
+
+/// POST `body` to `webhook`, retrying with backoff on connection errors, 429s and 5xx responses.
+pub async fn deliver(
+    client: &reqwest::Client,
+    webhook: &Webhook,
+    body: String,
+) -> Result<(), reqwest::Error> {
+    let signature = sign(&webhook.secret, body.as_bytes());
+    let strategy = ExponentialBackoff::from_millis(2)
+        .factor(250)
+        .take(MAX_RETRIES);
+
+    Retry::spawn(strategy, || async {
+        let result = client
+            .post(&webhook.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, &signature)
+            .body(body.clone())
+            .send()
+            .await
+            .and_then(|res| res.error_for_status());
+
+        match result {
+            Ok(_) => Ok(()),
+            // The endpoint rejected the payload, sending it again won't change that.
+            Err(err)
+                if err
+                    .status()
+                    .is_some_and(|status| status.is_client_error() && status.as_u16() != 429) =>
+            {
+                Err(RetryError::permanent(err))
+            }
+            Err(err) => {
+                tracing::debug!("Webhook {} delivery failed, retrying: {err}", webhook.id);
+                Err(RetryError::transient(err))
+            }
+        }
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use actix_web::{App, HttpRequest, HttpResponse, HttpServer, web};
+    use chrono::Utc;
+    use rust_decimal::dec;
+
+    use super::*;
+    use crate::database::transaction::TransactionType;
+    use crate::models::krist::transactions::TransactionJson;
+
+    #[derive(Default)]
+    struct Received(Mutex<Vec<(Option<String>, String)>>);
+
+    async fn receive(
+        req: HttpRequest,
+        body: String,
+        received: web::Data<Received>,
+    ) -> HttpResponse {
+        let signature = req
+            .headers()
+            .get(SIGNATURE_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned);
+        received.0.lock().unwrap().push((signature, body));
+
+        HttpResponse::NoContent().finish()
+    }
+
+    #[actix_web::test]
+    async fn test_transaction_webhook_delivery() {
+        let received = web::Data::new(Received::default());
+        let app_received = received.clone();
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(app_received.clone())
+                .route("/hook", web::post().to(receive))
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .expect("mock server should bind");
+        let addr = server.addrs()[0];
+        let handle = server.run();
+        let server_handle = handle.handle();
+        actix_web::rt::spawn(handle);
+
+        let webhook = Webhook {
+            id: 1,
+            url: format!("http://{addr}/hook"),
+            events: vec!["transactions".into()],
+            secret: "shop-secret".into(),
+            created_at: Utc::now(),
+        };
+        let event = WebSocketMessage::new_event(WebSocketEvent::Transaction {
+            transaction: TransactionJson {
+                id: 42,
+                from: Some("kre3w0i79j".into()),
+                to: "kqxhx5yn9v".into(),
+                value: dec!(10),
+                time: Utc::now().to_rfc3339(),
+                name: None,
+                metadata: Some("ref=order-42".into()),
+                metadata_json: None,
+                sent_metaname: None,
+                sent_name: None,
+                transaction_type: TransactionType::Transfer,
+            },
+        });
+        let WebSocketMessageInner::Event { event: inner, .. } = &event.r#type else {
+            unreachable!()
+        };
+        assert_eq!(event_kind(inner), Some("transactions"));
+
+        let body = serde_json::to_string(&event).unwrap();
+        deliver(&reqwest::Client::new(), &webhook, body.clone())
+            .await
+            .expect("delivery should succeed");
+
+        let received = received.0.lock().unwrap().clone();
+        assert_eq!(received.len(), 1);
+        let (signature, received_body) = &received[0];
+        assert_eq!(received_body, &body);
+        assert_eq!(
+            signature.as_deref(),
+            Some(sign("shop-secret", body.as_bytes()).as_str())
+        );
+
+        let payload: serde_json::Value = serde_json::from_str(received_body).unwrap();
+        assert_eq!(payload["event"], "transaction");
+        assert_eq!(payload["transaction"]["id"], 42);
+
+        server_handle.stop(false).await;
+    }
+}
@@ -1,17 +1,25 @@
 use thiserror::Error;
 
-use crate::errors::krist::KristErrorExt;
+use crate::errors::krist::{KristErrorExt, catalog};
 
 #[derive(Debug, Error)]
 pub enum WebSocketServerError {
     #[error("WebSocket token was not found in cache")]
     TokenNotFound,
+
+    #[error("Too many guest sessions are connected, try again later or connect with a private key")]
+    GuestLimitReached,
+
+    #[error("Too many sessions are connected from this address, close some before opening more")]
+    IpLimitReached,
 }
 
 impl KristErrorExt for WebSocketServerError {
-    fn error_type(&self) -> &'static str {
+    fn catalog_entry(&self) -> catalog::KristErrorCode {
         match self {
-            WebSocketServerError::TokenNotFound => "token_not_found",
+            WebSocketServerError::TokenNotFound => catalog::TOKEN_NOT_FOUND,
+            WebSocketServerError::GuestLimitReached => catalog::GUEST_LIMIT_REACHED,
+            WebSocketServerError::IpLimitReached => catalog::IP_LIMIT_REACHED,
         }
     }
 }
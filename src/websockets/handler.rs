@@ -1,36 +1,35 @@
 use chrono::Utc;
-use sqlx::{Pool, Postgres};
+use sqlx::PgPool;
 use uuid::Uuid;
 
-use super::{WebSocketServer, types::convert_to_iso_string};
+use super::{WebSocketServer, errors::WebSocketServerError, types::convert_to_iso_string};
 use crate::{
-    errors::{KromerError, websocket::WebSocketError},
+    AppState,
+    database::block::Model as Block,
+    errors::{
+        KromerError,
+        krist::{KristErrorExt, catalog},
+    },
     models::krist::{
-        motd::{Constants, CurrencyInfo, DetailedMotd, PackageInfo},
-        websockets::{WebSocketMessage, WebSocketMessageInner},
+        blocks::BlockJson,
+        motd::{CurrencyInfo, DetailedMotd, MOTD_CONSTANTS, MOTD_WORK, PackageInfo},
+        websockets::{WebSocketMessage, WebSocketMessageInner, WebSocketMessageResponse},
     },
+    routes::{DEFAULT_WS_PAGE_LIMIT, PaginationParams},
     websockets::routes,
 };
 
 #[tracing::instrument(skip_all, fields(uuid = ?uuid))]
 pub async fn process_text_msg(
-    pool: &Pool<Postgres>,
+    state: &AppState,
     server: &WebSocketServer,
     uuid: &Uuid,
     text: &str,
 ) -> Result<WebSocketMessage, KromerError> {
-    // strip leading and trailing whitespace (spaces, newlines, etc.)
-    let msg = text.trim();
-
-    // TODO: potentially change how this serialization is handled, so that we can properly extract "Invalid Parameter" errors.
-    let parsed_msg_result: Result<WebSocketMessage, serde_json::Error> = serde_json::from_str(msg);
-
-    let parsed_msg = match parsed_msg_result {
+    let pool = &state.pool;
+    let parsed_msg = match parse_text_msg(text) {
         Ok(value) => value,
-        Err(err) => {
-            tracing::error!("Could not parse JSON: {err}");
-            return Err(KromerError::WebSocket(WebSocketError::JsonParseRead));
-        }
+        Err(error_msg) => return Ok(*error_msg),
     };
 
     let msg_type = parsed_msg.r#type;
@@ -62,12 +61,27 @@ pub async fn process_text_msg(
         WebSocketMessageInner::Unsubscribe { event } => {
             routes::subscriptions::unsubscribe(server, uuid, event, msg_id)
         }
+        WebSocketMessageInner::Watch { addresses } => {
+            routes::subscriptions::watch(server, uuid, addresses, msg_id)
+        }
         WebSocketMessageInner::MakeTransaction {
             private_key,
             to,
             amount,
             metadata,
+            allow_duplicate,
         } => {
+            if let Err(error) = state.ensure_writable() {
+                return Ok(WebSocketMessage {
+                    ok: Some(false),
+                    id: msg_id,
+                    r#type: WebSocketMessageInner::Error {
+                        error: error.error_type().to_owned(),
+                        message: error.to_string(),
+                    },
+                });
+            }
+
             let private_key = match private_key {
                 Some(key) => key,
                 None => {
@@ -82,7 +96,7 @@ pub async fn process_text_msg(
                             ok: Some(false),
                             id: msg_id,
                             r#type: WebSocketMessageInner::Error {
-                                error: "unauthorized".into(),
+                                error: catalog::UNAUTHORIZED.code.to_owned(),
                                 message: "You are not logged in.".into(),
                             },
                         });
@@ -96,24 +110,43 @@ pub async fn process_text_msg(
                 to,
                 amount,
                 metadata,
+                allow_duplicate.unwrap_or(false),
                 msg_id,
                 server,
             )
             .await
         }
+        WebSocketMessageInner::Transactions {
+            limit,
+            offset,
+            exclude_mined,
+        } => {
+            let params = PaginationParams {
+                exclude_mined,
+                limit: limit.or(Some(*DEFAULT_WS_PAGE_LIMIT)),
+                offset,
+            };
+            routes::transactions::get_transactions(pool, &params, msg_id).await
+        }
+        WebSocketMessageInner::Replay { since } => {
+            routes::subscriptions::replay(server, uuid, since, msg_id)
+        }
+        // Nothing is mined, but krist clients still ask for the work before doing anything else.
         WebSocketMessageInner::Work => WebSocketMessage {
             ok: Some(true),
             id: msg_id,
-            r#type: WebSocketMessageInner::Error {
-                error: "mining_disabled".to_owned(),
-                message: "Mining disabled".to_owned(),
+            r#type: WebSocketMessageInner::Response {
+                data: WebSocketMessageResponse::Work {
+                    work: MOTD_WORK,
+                    constants: MOTD_CONSTANTS,
+                },
             },
         },
         _ => WebSocketMessage {
             ok: Some(true),
             id: msg_id,
             r#type: WebSocketMessageInner::Error {
-                error: "invalid_message_type".to_owned(),
+                error: catalog::INVALID_MESSAGE_TYPE.code.to_owned(),
                 message: "Invalid message type".to_owned(),
             },
         }, // Responses not sent by client or unimplemented
@@ -122,10 +155,131 @@ pub async fn process_text_msg(
     Ok(msg)
 }
 
-pub async fn send_hello_message(session: &mut actix_ws::Session) {
+/// The longest text frame, in characters, the server is willing to process
+pub const MAX_MESSAGE_CHARS: usize = 512;
+
+/// Check that an incoming text frame is not too long to process.
+///
+/// Otherwise the error frame that should be sent back to the client is returned instead.
+pub fn check_msg_size(text: &str) -> Result<(), Box<WebSocketMessage>> {
+    if text.chars().count() <= MAX_MESSAGE_CHARS {
+        return Ok(());
+    }
+
+    Err(Box::new(WebSocketMessage {
+        ok: Some(false),
+        id: None,
+        r#type: WebSocketMessageInner::Error {
+            error: catalog::MESSAGE_TOO_LONG.code.to_owned(),
+            message: format!("Message larger than {MAX_MESSAGE_CHARS} characters"),
+        },
+    }))
+}
+
+/// Parse an incoming text frame into a message.
+///
+/// On failure, the error frame that should be sent back to the client is returned instead.
+pub fn parse_text_msg(text: &str) -> Result<WebSocketMessage, Box<WebSocketMessage>> {
+    // strip leading and trailing whitespace (spaces, newlines, etc.)
+    let msg = text.trim();
+
+    let value: serde_json::Value = serde_json::from_str(msg).map_err(|err| {
+        tracing::debug!("Could not parse JSON: {err}");
+        invalid_message(None, format!("Message is not valid JSON: {err}"))
+    })?;
+
+    let msg_id = value
+        .get("id")
+        .and_then(serde_json::Value::as_u64)
+        .map(|id| id as usize);
+
+    let msg_type = match value.get("type") {
+        Some(serde_json::Value::String(msg_type)) => msg_type,
+        Some(_) => {
+            return Err(invalid_message(
+                msg_id,
+                "Field `type` must be a string".to_owned(),
+            ));
+        }
+        None => return Err(invalid_message(msg_id, "Missing field `type`".to_owned())),
+    };
+
+    if !WebSocketMessageInner::CLIENT_MESSAGE_TYPES.contains(&msg_type.as_str()) {
+        return Err(Box::new(WebSocketMessage {
+            ok: Some(false),
+            id: msg_id,
+            r#type: WebSocketMessageInner::Error {
+                error: catalog::INVALID_MESSAGE_TYPE.code.to_owned(),
+                message: "Invalid message type".to_owned(),
+            },
+        }));
+    }
+
+    serde_json::from_value(value).map_err(|err| {
+        tracing::debug!("Could not parse message: {err}");
+        invalid_message(msg_id, format!("Invalid message: {err}"))
+    })
+}
+
+/// Get the text of a binary frame so it can be processed like a text frame.
+///
+/// Binary frames are only accepted when enabled in the config, and must contain UTF-8 encoded JSON.
+/// Otherwise the error frame that should be sent back to the client is returned instead.
+pub fn decode_binary_msg(accept_binary: bool, bytes: &[u8]) -> Result<&str, Box<WebSocketMessage>> {
+    if !accept_binary {
+        return Err(Box::new(WebSocketMessage {
+            ok: Some(false),
+            id: None,
+            r#type: WebSocketMessageInner::Error {
+                error: catalog::BINARY_NOT_SUPPORTED.code.to_owned(),
+                message: "Binary frames are not supported, send messages as text".to_owned(),
+            },
+        }));
+    }
+
+    std::str::from_utf8(bytes)
+        .map_err(|_| invalid_message(None, "Binary frame is not valid UTF-8".to_owned()))
+}
+
+fn invalid_message(msg_id: Option<usize>, message: String) -> Box<WebSocketMessage> {
+    Box::new(WebSocketMessage {
+        ok: Some(false),
+        id: msg_id,
+        r#type: WebSocketMessageInner::Error {
+            error: catalog::INVALID_MESSAGE.code.to_owned(),
+            message,
+        },
+    })
+}
+
+/// Tell a session why it can not be served, then close it.
+pub async fn reject_session(mut session: actix_ws::Session, error: WebSocketServerError) {
+    let error_message = WebSocketMessage {
+        ok: Some(false),
+        id: None,
+        r#type: WebSocketMessageInner::Error {
+            error: error.error_type().to_owned(),
+            message: error.to_string(),
+        },
+    };
+
+    if let Ok(msg) = serde_json::to_string(&error_message) {
+        let _ = session.text(msg).await;
+    }
+
+    let reason = actix_ws::CloseReason {
+        code: actix_ws::CloseCode::Policy,
+        description: Some(error.error_type().to_owned()),
+    };
+    let _ = session.close(Some(reason)).await;
+}
+
+/// The hello every session gets on connect. It carries the package version, the currency
+/// constants and the latest block, so clients can feature-detect without a separate MOTD call.
+pub fn hello_message(last_block: Option<BlockJson>) -> WebSocketMessage {
     let cur_time = convert_to_iso_string(Utc::now());
 
-    let hello_message = WebSocketMessage {
+    WebSocketMessage {
         ok: Some(true),
         id: None,
         r#type: WebSocketMessageInner::Hello {
@@ -139,8 +293,8 @@ pub async fn send_hello_message(session: &mut actix_ws::Session) {
                 mining_enabled: false,
                 transactions_enabled: true,
                 debug_mode: true,
-                work: 500,
-                last_block: None,
+                work: MOTD_WORK,
+                last_block,
                 package: PackageInfo {
                     name: crate::build_info::PKG_NAME.to_string(),
                     version: crate::build_info::PKG_VERSION.to_string(),
@@ -149,15 +303,7 @@ pub async fn send_hello_message(session: &mut actix_ws::Session) {
                     repository: "https://github.com/ReconnectedCC/kromer/".to_string(),
                     git_hash: crate::build_info::GIT_COMMIT_HASH.map(|s| s.to_string()),
                 },
-                constants: Constants {
-                    wallet_version: 3,
-                    nonce_max_size: 500,
-                    name_cost: 500,
-                    min_work: 50,
-                    max_work: 500,
-                    work_factor: 500.0,
-                    seconds_per_block: 5000,
-                },
+                constants: MOTD_CONSTANTS,
                 currency: CurrencyInfo {
                     address_prefix: "k".to_string(),
                     name_suffix: "kro".to_string(),
@@ -167,7 +313,18 @@ pub async fn send_hello_message(session: &mut actix_ws::Session) {
                 notice: "Some awesome notice will go here".to_string(),
             }),
         },
-    };
+    }
+}
+
+pub async fn send_hello_message(pool: &PgPool, session: &mut actix_ws::Session) {
+    // A failed lookup shouldn't cost the client its hello, it just goes out without the block.
+    let last_block = Block::fetch_latest(pool)
+        .await
+        .inspect_err(|err| tracing::warn!("Failed to fetch the latest block for hello: {err}"))
+        .ok()
+        .flatten()
+        .map(Into::into);
+    let hello_message = hello_message(last_block);
 
     if let Ok(msg) = serde_json::to_string(&hello_message)
         && session.text(msg).await.is_err()
@@ -175,3 +332,251 @@ pub async fn send_hello_message(session: &mut actix_ws::Session) {
         tracing::debug!("Failed to send hello message (connection may have just closed)");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_of(msg: Box<WebSocketMessage>) -> (Option<bool>, Option<usize>, String, String) {
+        match msg.r#type {
+            WebSocketMessageInner::Error { error, message } => (msg.ok, msg.id, error, message),
+            other => panic!("expected an error frame, got {}", other.member_str()),
+        }
+    }
+
+    #[test]
+    fn test_garbage_message() {
+        let msg = parse_text_msg("this is not json").expect_err("garbage should not parse");
+        let (ok, id, error, _) = error_of(msg);
+
+        assert_eq!(ok, Some(false));
+        assert_eq!(id, None);
+        assert_eq!(error, "invalid_message");
+    }
+
+    #[test]
+    fn test_missing_type() {
+        let msg = parse_text_msg(r#"{"id":3,"address":"kre3w0i79j"}"#)
+            .expect_err("message without type should not parse");
+        let (ok, id, error, message) = error_of(msg);
+
+        assert_eq!(ok, Some(false));
+        assert_eq!(id, Some(3));
+        assert_eq!(error, "invalid_message");
+        assert!(message.contains("type"));
+    }
+
+    #[test]
+    fn test_unknown_type() {
+        let msg = parse_text_msg(r#"{"id":4,"type":"mine_a_block"}"#)
+            .expect_err("unknown type should not parse");
+        let (_, id, error, _) = error_of(msg);
+
+        assert_eq!(id, Some(4));
+        assert_eq!(error, "invalid_message_type");
+    }
+
+    #[test]
+    fn test_missing_field() {
+        let msg = parse_text_msg(r#"{"id":5,"type":"make_transaction","amount":5}"#)
+            .expect_err("make_transaction without recipient should not parse");
+        let (_, _, error, message) = error_of(msg);
+
+        assert_eq!(error, "invalid_message");
+        assert!(
+            message.contains("`to`"),
+            "message should name the field: {message}"
+        );
+    }
+
+    #[test]
+    fn test_valid_message() {
+        let msg = parse_text_msg(r#" {"id":6,"type":"me"} "#).expect("valid message should parse");
+
+        assert_eq!(msg.id, Some(6));
+        assert_eq!(msg.r#type.member_str(), "me");
+    }
+
+    #[test]
+    fn test_oversized_message() {
+        let text = format!(r#"{{"type":"me","padding":"{}"}}"#, "a".repeat(600));
+        let msg = check_msg_size(&text).expect_err("600 characters should be too long");
+
+        let value: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string(&msg).unwrap()).unwrap();
+        assert_eq!(value["ok"], serde_json::Value::Bool(false));
+        assert_eq!(value["type"], "error");
+        assert_eq!(value["error"], "message_too_long");
+
+        assert!(check_msg_size(r#"{"type":"me"}"#).is_ok());
+    }
+
+    #[test]
+    fn test_hello_message() {
+        let hello = serde_json::to_value(hello_message(None)).expect("failed to serialize hello");
+
+        assert_eq!(hello["type"], "hello");
+        assert_eq!(hello["package"]["version"], crate::build_info::PKG_VERSION);
+        assert_eq!(hello["currency"]["currency_symbol"], "KRO");
+        assert_eq!(hello["constants"]["max_work"], MOTD_CONSTANTS.max_work);
+    }
+
+    #[sqlx::test]
+    async fn test_hello_carries_last_block(pool: PgPool) {
+        use actix_web::{FromRequest, body::MessageBody, http::header, test::TestRequest, web};
+
+        sqlx::query("INSERT INTO blocks(height, address, hash, value, time, difficulty) VALUES (3, 'kalice0000', repeat('cd', 32), 25.00, NOW(), 400)")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let (req, mut payload) = TestRequest::default()
+            .insert_header((header::UPGRADE, "websocket"))
+            .insert_header((header::CONNECTION, "upgrade"))
+            .insert_header((header::SEC_WEBSOCKET_VERSION, "13"))
+            .insert_header((header::SEC_WEBSOCKET_KEY, "dGhlIHNhbXBsZSBub25jZQ=="))
+            .to_http_parts();
+        let body = web::Payload::from_request(&req, &mut payload)
+            .await
+            .unwrap();
+        let (response, mut session, _stream) = actix_ws::handle(&req, body).unwrap();
+
+        send_hello_message(&pool, &mut session).await;
+
+        let mut body = response.into_body();
+        let frame = std::future::poll_fn(|cx| std::pin::Pin::new(&mut body).poll_next(cx))
+            .await
+            .expect("the hello should be sent")
+            .expect("failed to read frame");
+        // Skip the websocket frame header in front of the JSON
+        let frame = String::from_utf8_lossy(&frame);
+        let hello: serde_json::Value =
+            serde_json::from_str(&frame[frame.find('{').unwrap()..]).unwrap();
+        assert_eq!(hello["type"], "hello");
+        assert_eq!(hello["last_block"]["height"], 3.0);
+        assert_eq!(hello["last_block"]["short_hash"], "cdcdcdcdcdcd");
+    }
+
+    #[test]
+    fn test_binary_frames() {
+        let frame = br#"{"id":8,"type":"me"}"#;
+
+        let (ok, _, error, _) = error_of(decode_binary_msg(false, frame).unwrap_err());
+        assert_eq!(ok, Some(false));
+        assert_eq!(error, "binary_not_supported");
+
+        let text = decode_binary_msg(true, frame).expect("binary frames should be accepted");
+        assert_eq!(parse_text_msg(text).unwrap().r#type.member_str(), "me");
+
+        let (_, _, error, _) = error_of(decode_binary_msg(true, &[0xff, 0xfe]).unwrap_err());
+        assert_eq!(error, "invalid_message");
+    }
+
+    #[test]
+    fn test_transactions_message() {
+        let msg = parse_text_msg(r#"{"id":7,"type":"transactions","limit":5,"excludeMined":true}"#)
+            .expect("transactions message should parse");
+
+        match msg.r#type {
+            WebSocketMessageInner::Transactions {
+                limit,
+                offset,
+                exclude_mined,
+            } => {
+                assert_eq!(limit, Some(5));
+                assert_eq!(offset, None);
+                assert_eq!(exclude_mined, Some(true));
+            }
+            other => panic!(
+                "expected a transactions message, got {}",
+                other.member_str()
+            ),
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_work_message() {
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/kromer")
+            .expect("lazy pool should not connect");
+        let state = AppState::new(pool);
+        let server = WebSocketServer::new();
+
+        // Work does not depend on who is asking, so a fresh guest id is enough.
+        let msg = process_text_msg(
+            &state,
+            &server,
+            &Uuid::new_v4(),
+            r#"{"id":7,"type":"work"}"#,
+        )
+        .await
+        .expect("work message failed");
+        let value = serde_json::to_value(&msg).expect("failed to serialize response");
+
+        assert_eq!(value["ok"], true);
+        assert_eq!(value["id"], 7);
+        assert_eq!(value["type"], "response");
+        assert_eq!(value["responding_to"], "work");
+        assert_eq!(value["work"], MOTD_WORK);
+        assert_eq!(value["constants"]["max_work"], MOTD_CONSTANTS.max_work);
+    }
+
+    #[actix_web::test]
+    async fn test_maintenance_rejects_socket_transfers() {
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/kromer")
+            .expect("lazy pool should not connect");
+        let state = AppState::new(pool);
+        state.set_maintenance(true);
+        let server = WebSocketServer::new();
+
+        let msg = process_text_msg(
+            &state,
+            &server,
+            &Uuid::new_v4(),
+            r#"{"id":3,"type":"make_transaction","privatekey":"a","to":"kre3w0i79j","amount":5}"#,
+        )
+        .await
+        .expect("make_transaction failed");
+        let value = serde_json::to_value(&msg).expect("failed to serialize response");
+
+        assert_eq!(value["ok"], false);
+        assert_eq!(value["id"], 3);
+        assert_eq!(value["error"], "maintenance");
+    }
+
+    #[sqlx::test]
+    async fn test_transactions_page_is_clamped(pool: sqlx::PgPool) {
+        let rows = *crate::routes::MAX_PAGE_LIMIT + 5;
+        sqlx::query(
+            r#"INSERT INTO transactions(amount, "from", "to", transaction_type, date)
+            SELECT 1.00, 'kalice0000', 'kbob000000', 'transfer', NOW() FROM generate_series(1, $1)"#,
+        )
+        .bind(rows)
+        .execute(&pool)
+        .await
+        .unwrap();
+        let state = AppState::new(pool);
+        let server = WebSocketServer::new();
+
+        let page = async |limit: &str| {
+            let text = format!(r#"{{"id":1,"type":"transactions"{limit}}}"#);
+            let msg = process_text_msg(&state, &server, &Uuid::new_v4(), &text)
+                .await
+                .expect("transactions failed");
+            let value = serde_json::to_value(&msg).expect("failed to serialize response");
+            assert_eq!(value["ok"], true);
+            assert_eq!(value["total"], rows);
+            value["count"].as_i64().unwrap()
+        };
+
+        assert_eq!(page("").await, (*DEFAULT_WS_PAGE_LIMIT).min(rows));
+        assert_eq!(page(r#","limit":3"#).await, 3);
+        assert_eq!(page(r#","limit":-5"#).await, 1);
+        assert_eq!(page(r#","limit":0"#).await, 0);
+        assert_eq!(
+            page(r#","limit":100000"#).await,
+            *crate::routes::MAX_PAGE_LIMIT
+        );
+    }
+}
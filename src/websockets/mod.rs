@@ -5,30 +5,237 @@ pub mod types;
 pub mod utils;
 
 use actix_web::rt::time;
-use actix_ws::Session;
+use actix_ws::{CloseCode, CloseReason, Session};
 use bytestring::ByteString;
 use errors::WebSocketServerError;
 use futures_util::{StreamExt, stream::FuturesUnordered};
+use rust_decimal::Decimal;
 use scc::{HashMap, HashSet};
-use std::{sync::Arc, time::Duration};
+use sqlx::{Pool, Postgres};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    net::IpAddr,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+use tracing::Instrument;
 use uuid::Uuid;
 
-use types::common::{WebSocketSessionData, WebSocketSubscriptionType, WebSocketTokenData};
+use types::common::{
+    WebSocketSessionData, WebSocketSessionOrigin, WebSocketSubscriptionType, WebSocketTokenData,
+};
 
+use crate::database::faucet;
+use crate::database::wallet::Model as Wallet;
+use crate::errors::krist::catalog;
 use crate::models::krist::websockets::{WebSocketEvent, WebSocketMessage, WebSocketMessageInner};
+use crate::utils::env;
+use crate::webhooks::{self, WebhookDispatcher};
+use types::convert_to_iso_string;
 
-pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
-pub const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
-pub const TOKEN_EXPIRATION: Duration = Duration::from_secs(30);
+type BroadcastFuture =
+    std::pin::Pin<Box<dyn std::future::Future<Output = (Uuid, SendOutcome)> + Send>>;
 
-type BroadcastFuture = std::pin::Pin<
-    Box<dyn std::future::Future<Output = (Uuid, Result<(), actix_ws::Closed>)> + Send>,
->;
+/// What happened to a message sent to a single session during a broadcast
+enum SendOutcome {
+    Sent,
+    /// The session stopped qualifying for the event before it was sent
+    Skipped,
+    Closed,
+    /// The session's outbound queue stayed full for longer than the configured timeout
+    SlowConsumer(Session),
+}
+
+/// Deployment specific settings for the websocket server.
+#[derive(Debug, Clone)]
+pub struct WebSocketServerConfig {
+    /// Whether `/ws/start` hands out tokens to clients without a private key.
+    pub allow_guests: bool,
+    /// Whether the heartbeat sends a `keepalive` message with the server time next to the ping.
+    /// Disabling this leaves a ping-only heartbeat, which still detects dead clients.
+    pub keepalive_messages: bool,
+    /// Whether binary frames are decoded as UTF-8 JSON messages instead of being rejected.
+    pub accept_binary: bool,
+    /// How long a broadcast waits on a session with a full outbound queue before dropping it as a slow consumer.
+    pub slow_consumer_timeout: Duration,
+    /// Amount granted once to an address the first time it connects, `None` disables the faucet.
+    pub faucet_amount: Option<Decimal>,
+    /// The most addresses a single session may watch with a `watch` message.
+    pub max_watched_addresses: usize,
+    /// The most guest sessions connected at once, `None` leaves them unlimited.
+    pub max_guest_sessions: Option<usize>,
+    /// The most sessions connected at once from a single client IP, `None` leaves them unlimited.
+    pub max_sessions_per_ip: Option<usize>,
+    /// Peers whose `X-Forwarded-For` header is believed, every other peer is counted by its own IP.
+    pub trusted_proxies: Vec<IpAddr>,
+    /// How long a token from `/ws/start` can be used to connect before it expires.
+    pub token_expiration: Duration,
+    /// How often the gateway pings each session.
+    pub heartbeat_interval: Duration,
+    /// How long a session may go without answering a ping before it is dropped, must be longer
+    /// than the heartbeat interval.
+    pub client_timeout: Duration,
+    /// How many of the latest transaction and name events are kept for [`WebSocketServer::replay_since`],
+    /// 0 keeps none.
+    pub replay_capacity: usize,
+}
+
+impl Default for WebSocketServerConfig {
+    fn default() -> Self {
+        Self {
+            allow_guests: true,
+            keepalive_messages: true,
+            accept_binary: false,
+            slow_consumer_timeout: Duration::from_secs(5),
+            faucet_amount: None,
+            max_watched_addresses: 50,
+            max_guest_sessions: None,
+            max_sessions_per_ip: None,
+            trusted_proxies: Vec::new(),
+            token_expiration: Duration::from_secs(30),
+            heartbeat_interval: Duration::from_secs(5),
+            client_timeout: Duration::from_secs(10),
+            replay_capacity: 100,
+        }
+    }
+}
+
+impl WebSocketServerConfig {
+    /// Read the config from the environment, falling back to the defaults. A session limit that
+    /// doesn't parse is an error rather than no limit at all.
+    pub fn from_env() -> Result<Self, String> {
+        Self::from_lookup(|key| std::env::var(key).ok())
+    }
+
+    /// [`Self::from_env`], with the variables coming from `lookup` instead.
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, String> {
+        let defaults = Self::default();
+
+        Ok(Self {
+            allow_guests: env::parse_or(
+                "WS_ALLOW_GUESTS",
+                lookup("WS_ALLOW_GUESTS").as_deref(),
+                defaults.allow_guests,
+            ),
+            keepalive_messages: env::parse_or(
+                "WS_KEEPALIVE_MESSAGES",
+                lookup("WS_KEEPALIVE_MESSAGES").as_deref(),
+                defaults.keepalive_messages,
+            ),
+            accept_binary: env::parse_or(
+                "WS_ACCEPT_BINARY",
+                lookup("WS_ACCEPT_BINARY").as_deref(),
+                defaults.accept_binary,
+            ),
+            slow_consumer_timeout: Duration::from_millis(env::parse_or(
+                "WS_SLOW_CONSUMER_TIMEOUT_MS",
+                lookup("WS_SLOW_CONSUMER_TIMEOUT_MS").as_deref(),
+                defaults.slow_consumer_timeout.as_millis() as u64,
+            )),
+            faucet_amount: Some(env::parse_or(
+                "WS_FAUCET_AMOUNT",
+                lookup("WS_FAUCET_AMOUNT").as_deref(),
+                Decimal::ZERO,
+            ))
+            .filter(|amount| *amount > Decimal::ZERO),
+            max_watched_addresses: env::parse_or(
+                "WS_MAX_WATCHED_ADDRESSES",
+                lookup("WS_MAX_WATCHED_ADDRESSES").as_deref(),
+                defaults.max_watched_addresses,
+            ),
+            max_guest_sessions: env::parse_opt(
+                "WS_MAX_GUEST_SESSIONS",
+                lookup("WS_MAX_GUEST_SESSIONS").as_deref(),
+            )?,
+            max_sessions_per_ip: env::parse_opt(
+                "WS_MAX_SESSIONS_PER_IP",
+                lookup("WS_MAX_SESSIONS_PER_IP").as_deref(),
+            )?,
+            trusted_proxies: lookup("TRUSTED_PROXIES")
+                .map(|proxies| parse_trusted_proxies(&proxies))
+                .unwrap_or_default(),
+            token_expiration: Duration::from_secs(env::parse_or(
+                "WS_TOKEN_EXPIRATION_SECS",
+                lookup("WS_TOKEN_EXPIRATION_SECS").as_deref(),
+                defaults.token_expiration.as_secs(),
+            )),
+            heartbeat_interval: Duration::from_secs(env::parse_or(
+                "WS_HEARTBEAT_INTERVAL_SECS",
+                lookup("WS_HEARTBEAT_INTERVAL_SECS").as_deref(),
+                defaults.heartbeat_interval.as_secs(),
+            )),
+            client_timeout: Duration::from_secs(env::parse_or(
+                "WS_CLIENT_TIMEOUT_SECS",
+                lookup("WS_CLIENT_TIMEOUT_SECS").as_deref(),
+                defaults.client_timeout.as_secs(),
+            )),
+            replay_capacity: env::parse_or(
+                "WS_REPLAY_CAPACITY",
+                lookup("WS_REPLAY_CAPACITY").as_deref(),
+                defaults.replay_capacity,
+            ),
+        })
+    }
+
+    /// Check the settings make sense together, meant to be called once at startup.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.heartbeat_interval.is_zero() {
+            return Err("WS_HEARTBEAT_INTERVAL_SECS must be greater than 0".to_owned());
+        }
+
+        // A timeout within one interval would drop sessions that are just waiting for their next ping.
+        if self.client_timeout <= self.heartbeat_interval {
+            return Err(format!(
+                "WS_CLIENT_TIMEOUT_SECS ({}s) must be longer than WS_HEARTBEAT_INTERVAL_SECS ({}s)",
+                self.client_timeout.as_secs(),
+                self.heartbeat_interval.as_secs()
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// The latest broadcast events, each numbered so a client can ask for the ones after the last it saw.
+#[derive(Debug, Default)]
+struct ReplayBuffer {
+    last_id: u64,
+    events: VecDeque<(u64, WebSocketMessage)>,
+}
+
+/// Parse a comma separated list of proxy IPs. An entry that isn't an IP is left out, which only
+/// ever means trusting fewer peers.
+fn parse_trusted_proxies(proxies: &str) -> Vec<IpAddr> {
+    proxies
+        .split(',')
+        .map(str::trim)
+        .filter(|proxy| !proxy.is_empty())
+        .filter_map(|proxy| match proxy.parse() {
+            Ok(ip) => Some(ip),
+            Err(_) => {
+                tracing::warn!("Ignoring invalid entry {proxy:?} in TRUSTED_PROXIES");
+                None
+            }
+        })
+        .collect()
+}
 
 #[derive(Clone)]
 pub struct WebSocketServer {
     pub sessions: Arc<HashMap<Uuid, WebSocketSessionData>>,
     pub pending_tokens: Arc<HashMap<Uuid, WebSocketTokenData>>,
+    pub config: WebSocketServerConfig,
+    /// How many sessions hold a guest slot, see [`WebSocketSessionData::guest_slot`]
+    guest_sessions: Arc<AtomicUsize>,
+    /// How many sessions are connected from each client IP
+    ip_sessions: Arc<HashMap<IpAddr, usize>>,
+    /// Where transaction and name events are handed off to registered webhooks
+    webhooks: Option<WebhookDispatcher>,
+    /// Recent transaction and name events, capped at [`WebSocketServerConfig::replay_capacity`]
+    replay: Arc<Mutex<ReplayBuffer>>,
 }
 
 impl Default for WebSocketServer {
@@ -39,16 +246,59 @@ impl Default for WebSocketServer {
 
 impl WebSocketServer {
     pub fn new() -> Self {
+        Self::with_config(WebSocketServerConfig::default())
+    }
+
+    pub fn with_config(config: WebSocketServerConfig) -> Self {
         Self {
             sessions: Arc::new(HashMap::with_capacity(100)),
             pending_tokens: Arc::new(HashMap::with_capacity(50)),
+            config,
+            guest_sessions: Arc::new(AtomicUsize::new(0)),
+            ip_sessions: Arc::new(HashMap::with_capacity(100)),
+            webhooks: None,
+            replay: Arc::new(Mutex::new(ReplayBuffer::default())),
         }
     }
 
-    #[tracing::instrument(skip_all, fields(address = data.address))]
-    pub fn insert_session(&self, uuid: Uuid, session: Session, data: WebSocketTokenData) {
+    /// Also deliver broadcast events to the webhooks registered with `dispatcher`.
+    pub fn with_webhooks(mut self, dispatcher: WebhookDispatcher) -> Self {
+        self.webhooks = Some(dispatcher);
+        self
+    }
+
+    /// Add a connected session from `origin`.
+    ///
+    /// Sessions are turned away once their IP reaches the per-IP limit, and guests also once the
+    /// guest limit is reached.
+    #[tracing::instrument(skip_all, fields(address = data.address, ip = ?origin.ip, request_id = ?origin.request_id))]
+    pub fn insert_session(
+        &self,
+        uuid: Uuid,
+        session: Session,
+        data: WebSocketTokenData,
+        origin: WebSocketSessionOrigin,
+    ) -> Result<(), WebSocketServerError> {
+        let WebSocketSessionOrigin { ip, request_id } = origin;
+        if let Some(ip) = ip
+            && !self.take_ip_slot(ip)
+        {
+            tracing::info!("Rejecting session, the per-IP limit is reached");
+            return Err(WebSocketServerError::IpLimitReached);
+        }
+
+        let guest_slot = data.address == "guest";
+        if guest_slot && !self.take_guest_slot() {
+            tracing::info!("Rejecting guest session, the guest limit is reached");
+            if let Some(ip) = ip {
+                self.release_ip_slot(ip);
+            }
+            return Err(WebSocketServerError::GuestLimitReached);
+        }
+
         let subscriptions = HashSet::from_iter([
             WebSocketSubscriptionType::OwnTransactions,
+            WebSocketSubscriptionType::OwnBalance,
             WebSocketSubscriptionType::Blocks,
         ]);
 
@@ -59,16 +309,107 @@ impl WebSocketServer {
             session,
             subscriptions,
             computer_id: data.computer_id,
+            watched_addresses: Vec::new(),
+            guest_slot,
+            ip,
+            request_id,
         };
 
-        if self.sessions.insert_sync(uuid, session_data).is_err() {
+        if let Err((_, session_data)) = self.sessions.insert_sync(uuid, session_data) {
             tracing::error!("Attempted to insert session that already exists");
+            self.release_slots(&session_data);
+        }
+
+        Ok(())
+    }
+
+    /// Claim a guest slot if there is one left.
+    fn take_guest_slot(&self) -> bool {
+        let limit = self.config.max_guest_sessions.unwrap_or(usize::MAX);
+
+        self.guest_sessions
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+                (count < limit).then_some(count + 1)
+            })
+            .is_ok()
+    }
+
+    /// The faucet amount a connecting session may get, never for guests or when the faucet is off.
+    /// Whether the address already had its grant is up to [`faucet::grant_once`].
+    pub fn faucet_amount_for(&self, data: &WebSocketTokenData) -> Option<Decimal> {
+        if data.address == "guest" {
+            return None;
+        }
+
+        self.config.faucet_amount
+    }
+
+    /// Give `address` its one-time faucet grant if it never had one, and tell it about its balance.
+    pub async fn grant_faucet(&self, pool: &Pool<Postgres>, address: &str, amount: Decimal) {
+        match faucet::grant_once(pool, address, amount).await {
+            Ok(Some((wallet, transaction))) => {
+                tracing::info!("Granted faucet amount {amount} to {}", wallet.address);
+                let event = WebSocketMessage::new_event(WebSocketEvent::Transaction {
+                    transaction: transaction.into(),
+                });
+                self.broadcast_event(event).await;
+                self.broadcast_balances(pool, &[address]).await;
+            }
+            Ok(None) => tracing::debug!("Address {address} already received its faucet grant"),
+            Err(err) => tracing::error!("Failed to grant faucet amount to {address}: {err}"),
+        }
+    }
+
+    /// Claim a slot for one more session from `ip` if it is under the limit.
+    fn take_ip_slot(&self, ip: IpAddr) -> bool {
+        let limit = self.config.max_sessions_per_ip.unwrap_or(usize::MAX);
+        if limit == 0 {
+            return false;
+        }
+
+        loop {
+            let taken = self.ip_sessions.update_sync(&ip, |_, count| {
+                let taken = *count < limit;
+                if taken {
+                    *count += 1;
+                }
+                taken
+            });
+
+            if let Some(taken) = taken {
+                return taken;
+            }
+
+            // Another connection from the same IP may have raced us to the first slot, then retry
+            if self.ip_sessions.insert_sync(ip, 1).is_ok() {
+                return true;
+            }
+        }
+    }
+
+    fn release_ip_slot(&self, ip: IpAddr) {
+        self.ip_sessions.update_sync(&ip, |_, count| {
+            *count = count.saturating_sub(1);
+        });
+        self.ip_sessions.remove_if_sync(&ip, |count| *count == 0);
+    }
+
+    /// Give back the guest and per-IP slots a session held.
+    fn release_slots(&self, data: &WebSocketSessionData) {
+        if data.guest_slot {
+            self.guest_sessions.fetch_sub(1, Ordering::SeqCst);
+        }
+
+        if let Some(ip) = data.ip {
+            self.release_ip_slot(ip);
         }
     }
 
     #[tracing::instrument(skip(self))]
     pub fn cleanup_session(&self, uuid: &Uuid) {
-        self.sessions.remove_sync(uuid);
+        if let Some((_, data)) = self.sessions.remove_sync(uuid) {
+            self.release_slots(&data);
+        }
 
         tracing::info!("Cleaned session");
     }
@@ -91,8 +432,9 @@ impl WebSocketServer {
         };
 
         let pending_tokens = self.pending_tokens.clone();
+        let expiration = self.config.token_expiration;
         actix_web::rt::spawn(async move {
-            time::sleep(TOKEN_EXPIRATION).await;
+            time::sleep(expiration).await;
 
             // I don't think that this if statement would ever fail? considering we literally just put the fucking token in the map, lol.
             if pending_tokens.remove_async(&uuid).await.is_some() {
@@ -149,6 +491,21 @@ impl WebSocketServer {
             });
     }
 
+    /// Replace the addresses a session watches, returning the list that is now in effect.
+    #[tracing::instrument(skip_all)]
+    pub fn set_watched_addresses(&self, uuid: &Uuid, addresses: Vec<String>) -> Vec<String> {
+        self.sessions
+            .update_sync(uuid, |_, v| {
+                v.watched_addresses = addresses;
+                tracing::info!(
+                    "Session now watches {} addresses",
+                    v.watched_addresses.len()
+                );
+                v.watched_addresses.clone()
+            })
+            .unwrap_or_default()
+    }
+
     pub fn get_subscription_list(&self, uuid: &Uuid) -> Vec<WebSocketSubscriptionType> {
         if let Some(data) = self.sessions.get_sync(uuid) {
             let mut subscriptions: Vec<WebSocketSubscriptionType> =
@@ -167,57 +524,178 @@ impl WebSocketServer {
 
     /// Broadcast an event to all connected clients
     #[tracing::instrument(skip_all)]
-    pub async fn broadcast_event(&self, event: WebSocketMessage) {
-        let msg =
-            serde_json::to_string(&event).expect("Failed to turn event message into a string");
+    pub async fn broadcast_event(&self, mut event: WebSocketMessage) {
+        self.record_event(&mut event);
+        if let Some(webhooks) = &self.webhooks {
+            webhooks.dispatch(&event);
+        }
+
+        let futures = self.event_futures(event);
+
+        self.finish_broadcast(futures).await;
+    }
+
+    /// Number a transaction or name event and keep it for replaying, returning the id it was given.
+    fn record_event(&self, event: &mut WebSocketMessage) -> Option<u64> {
+        let WebSocketMessageInner::Event {
+            event: inner,
+            event_id,
+        } = &mut event.r#type
+        else {
+            return None;
+        };
+        webhooks::event_kind(inner)?;
+
+        let mut replay = self.replay.lock().expect("replay buffer poisoned");
+        replay.last_id += 1;
+        let id = replay.last_id;
+        *event_id = Some(id);
+
+        if self.config.replay_capacity > 0 {
+            while replay.events.len() >= self.config.replay_capacity {
+                replay.events.pop_front();
+            }
+            replay.events.push_back((id, event.clone()));
+        }
+
+        Some(id)
+    }
+
+    /// The id of the latest transaction or name event, `None` before the first one.
+    pub fn last_event_id(&self) -> Option<u64> {
+        let replay = self.replay.lock().expect("replay buffer poisoned");
+        (replay.last_id > 0).then_some(replay.last_id)
+    }
+
+    /// The buffered events newer than `id`, oldest first. Events that already fell out of the
+    /// buffer are not returned, so a client far enough behind has to list them from the API.
+    ///
+    /// Ids restart at 1 with the process, so `None` for an id this process never handed out, the
+    /// client saw it before a restart and has to list what it missed from the API.
+    pub fn replay_since(&self, id: u64) -> Option<Vec<WebSocketMessage>> {
+        let replay = self.replay.lock().expect("replay buffer poisoned");
+        if id > replay.last_id {
+            return None;
+        }
+
+        let events = replay
+            .events
+            .iter()
+            .filter(|(event_id, _)| *event_id > id)
+            .map(|(_, event)| event.clone())
+            .collect();
+
+        Some(events)
+    }
+
+    /// [`Self::replay_since`], narrowed to the events the session would have been sent.
+    pub fn replay_for(&self, uuid: &Uuid, id: u64) -> Option<Vec<WebSocketMessage>> {
+        let Some(client_data) = self.fetch_session_data(uuid) else {
+            return Some(Vec::new());
+        };
+
+        let events = self
+            .replay_since(id)?
+            .into_iter()
+            .filter(|event| should_receive(event, &client_data))
+            .collect();
+
+        Some(events)
+    }
+
+    /// Queue an event for every session that should receive it.
+    fn event_futures(&self, event: WebSocketMessage) -> FuturesUnordered<BroadcastFuture> {
+        let msg: ByteString = serde_json::to_string(&event)
+            .expect("Failed to turn event message into a string")
+            .into();
         tracing::debug!("Broadcasting event: {msg}");
 
-        let mut futures: FuturesUnordered<BroadcastFuture> = FuturesUnordered::new();
+        let event = Arc::new(event);
+        let futures: FuturesUnordered<BroadcastFuture> = FuturesUnordered::new();
 
         self.sessions.iter_sync(|k, client_data| {
-            let id = *k;
-
-            // TODO: Somehow make this prettier...
-            if let WebSocketMessageInner::Event { ref event } = event.r#type {
-                match event {
-                    WebSocketEvent::Block { .. } => todo!(),
-                    WebSocketEvent::Transaction { transaction } => {
-                        let transaction_from = transaction.from.as_deref().unwrap_or_default();
-                        if (!client_data.is_guest()
-                            && (client_data.address == transaction.to
-                                || client_data.address == transaction_from)
-                            && client_data
-                                .is_subscribed_to(WebSocketSubscriptionType::OwnTransactions))
-                            || client_data.is_subscribed_to(WebSocketSubscriptionType::Transactions)
-                        {
-                            let mut session = client_data.session.clone();
-                            let msg = msg.clone();
-                            futures.push(Box::pin(async move { (id, session.text(msg).await) }));
-                        }
-                    }
-                    WebSocketEvent::Name { name } => {
-                        if (!client_data.is_guest()
-                            && (client_data.address == name.owner)
-                            && client_data.is_subscribed_to(WebSocketSubscriptionType::OwnNames))
-                            || client_data.is_subscribed_to(WebSocketSubscriptionType::Names)
-                        {
-                            let mut session = client_data.session.clone();
-                            let msg = msg.clone();
-                            futures.push(Box::pin(async move { (id, session.text(msg).await) }));
-                        }
-                    }
-                }
+            if should_receive(&event, client_data) {
+                let session = client_data.session.clone();
+                let span = tracing::debug_span!("send_event", session = %k, request_id = ?client_data.request_id);
+                futures.push(Box::pin(
+                    self.send_event(*k, session, msg.clone(), event.clone())
+                        .instrument(span),
+                ));
             }
 
             true
         });
 
-        while let Some((uuid, result)) = futures.next().await {
-            if result.is_err() {
-                tracing::warn!("Got an unexpected closed session");
-                self.cleanup_session(&uuid);
+        futures
+    }
+
+    /// Like [`Self::send_limited`], but checks the session is still allowed the event right before sending.
+    ///
+    /// A `logout` can land between picking the recipients and sending, and must not leak the
+    /// session's old own-transaction events to what is now a guest.
+    fn send_event(
+        &self,
+        id: Uuid,
+        session: Session,
+        msg: ByteString,
+        event: Arc<WebSocketMessage>,
+    ) -> BroadcastFuture {
+        let sessions = self.sessions.clone();
+        let send = self.send_limited(id, session, msg);
+
+        Box::pin(async move {
+            let still_wanted = sessions
+                .read_sync(&id, |_, client_data| should_receive(&event, client_data))
+                .unwrap_or(false);
+
+            match still_wanted {
+                true => send.await,
+                false => (id, SendOutcome::Skipped),
             }
+        })
+    }
+
+    /// Send the current balance of each address to its own sessions that subscribed to `ownBalance`.
+    /// Call this after the transaction that changed the balances has been committed.
+    #[tracing::instrument(skip(self, pool))]
+    pub async fn broadcast_balances(&self, pool: &Pool<Postgres>, addresses: &[&str]) {
+        // Skip the lookup entirely when nobody is listening, which is the common case.
+        let watched: Vec<&str> = addresses
+            .iter()
+            .copied()
+            .filter(|address| self.has_balance_subscriber(address))
+            .collect();
+        if watched.is_empty() {
+            return;
         }
+
+        let wallets = match Wallet::lookup_addresses(pool, watched, false).await {
+            Ok(wallets) => wallets,
+            Err(err) => {
+                tracing::warn!("Failed to fetch balances to broadcast: {err}");
+                return;
+            }
+        };
+
+        for wallet in wallets {
+            let event = WebSocketMessage::new_event(WebSocketEvent::Balance {
+                address: wallet.address,
+                balance: wallet.balance,
+            });
+            self.broadcast_event(event).await;
+        }
+    }
+
+    fn has_balance_subscriber(&self, address: &str) -> bool {
+        let mut found = false;
+
+        self.sessions.iter_sync(|_, data| {
+            found = data.address == address
+                && data.is_subscribed_to(WebSocketSubscriptionType::OwnBalance);
+            !found
+        });
+
+        found
     }
 
     /// Broadcast a message to all connected clients
@@ -226,30 +704,887 @@ impl WebSocketServer {
         let msg = msg.into();
         tracing::debug!("Sending msg: {msg}");
 
-        let mut futures: FuturesUnordered<BroadcastFuture> = FuturesUnordered::new();
+        let futures: FuturesUnordered<BroadcastFuture> = FuturesUnordered::new();
 
         self.sessions
             .iter_async(|uuid, v| {
                 let id = *uuid;
-                let mut session = v.session.clone();
-
-                let msg = msg.clone();
+                let session = v.session.clone();
 
-                futures.push(Box::pin(async move { (id, session.text(msg).await) }));
+                futures.push(self.send_limited(id, session, msg.clone()));
 
                 true
             })
             .await;
 
-        while let Some((uuid, result)) = futures.next().await {
-            if result.is_err() {
-                tracing::warn!("Got an unexpected closed session");
-                self.cleanup_session(&uuid);
+        self.finish_broadcast(futures).await;
+    }
+
+    /// Send a message to a session, giving up once its outbound queue has been full for too long.
+    ///
+    /// actix-ws buffers a small, fixed number of frames per session, so a client that does not read
+    /// would otherwise hold up every broadcast.
+    fn send_limited(&self, id: Uuid, mut session: Session, msg: ByteString) -> BroadcastFuture {
+        let timeout = self.config.slow_consumer_timeout;
+
+        Box::pin(async move {
+            match time::timeout(timeout, session.text(msg)).await {
+                Ok(Ok(())) => (id, SendOutcome::Sent),
+                Ok(Err(_)) => (id, SendOutcome::Closed),
+                Err(_) => (id, SendOutcome::SlowConsumer(session)),
+            }
+        })
+    }
+
+    async fn finish_broadcast(&self, mut futures: FuturesUnordered<BroadcastFuture>) {
+        while let Some((uuid, outcome)) = futures.next().await {
+            match outcome {
+                SendOutcome::Sent | SendOutcome::Skipped => (),
+                SendOutcome::Closed => {
+                    tracing::warn!("Got an unexpected closed session");
+                    self.cleanup_session(&uuid);
+                }
+                SendOutcome::SlowConsumer(session) => {
+                    tracing::warn!("Dropping session {uuid}, it is not reading its messages");
+                    self.cleanup_session(&uuid);
+
+                    // Closing marks the session closed straight away, the close frame itself may
+                    // never fit in the full queue so don't wait on it.
+                    let timeout = self.config.slow_consumer_timeout;
+                    actix_web::rt::spawn(async move {
+                        let reason = CloseReason {
+                            code: CloseCode::Policy,
+                            description: Some(catalog::SLOW_CONSUMER.code.to_owned()),
+                        };
+                        let _ = time::timeout(timeout, session.close(Some(reason))).await;
+                    });
+                }
             }
         }
     }
 
+    /// The keepalive message to send on every heartbeat, or `None` in ping-only mode.
+    pub fn keepalive_message(&self) -> Option<WebSocketMessage> {
+        if !self.config.keepalive_messages {
+            return None;
+        }
+
+        Some(WebSocketMessage {
+            ok: None,
+            id: None,
+            r#type: WebSocketMessageInner::Keepalive {
+                server_time: Some(convert_to_iso_string(chrono::Utc::now())),
+            },
+        })
+    }
+
+    /// How many sessions are subscribed to each subscription type, types nobody uses count as 0.
+    pub fn subscription_counts(&self) -> BTreeMap<String, usize> {
+        let mut counts: BTreeMap<String, usize> = WebSocketSubscriptionType::ALL
+            .iter()
+            .map(|subscription| (subscription.into_string(), 0))
+            .collect();
+
+        self.sessions.iter_sync(|_, data| {
+            data.subscriptions.iter_sync(|subscription| {
+                *counts.entry(subscription.into_string()).or_default() += 1;
+                true
+            });
+            true
+        });
+
+        counts
+    }
+
     pub fn fetch_session_data(&self, uuid: &Uuid) -> Option<WebSocketSessionData> {
         self.sessions.get_sync(uuid).map(|r| r.clone())
     }
 }
+
+/// Whether a session should receive an event, based on its address and subscriptions.
+fn should_receive(message: &WebSocketMessage, client_data: &WebSocketSessionData) -> bool {
+    let WebSocketMessageInner::Event { ref event, .. } = message.r#type else {
+        return false;
+    };
+
+    match event {
+        WebSocketEvent::Block { .. } => todo!(),
+        WebSocketEvent::Transaction { transaction } => {
+            let transaction_from = transaction.from.as_deref().unwrap_or_default();
+
+            (!client_data.is_guest()
+                && (client_data.address == transaction.to
+                    || client_data.address == transaction_from)
+                && client_data.is_subscribed_to(WebSocketSubscriptionType::OwnTransactions))
+                || client_data.is_subscribed_to(WebSocketSubscriptionType::Transactions)
+                || client_data.is_watching(&transaction.to)
+                || client_data.is_watching(transaction_from)
+        }
+        WebSocketEvent::Balance { address, .. } => {
+            !client_data.is_guest()
+                && client_data.address == *address
+                && client_data.is_subscribed_to(WebSocketSubscriptionType::OwnBalance)
+        }
+        WebSocketEvent::Name { name } => {
+            (!client_data.is_guest()
+                && (client_data.address == name.owner)
+                && client_data.is_subscribed_to(WebSocketSubscriptionType::OwnNames))
+                || client_data.is_subscribed_to(WebSocketSubscriptionType::Names)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        FromRequest, HttpResponse, body::MessageBody, http::header, test::TestRequest, web,
+    };
+
+    use super::*;
+    use crate::database::transaction::{
+        Model as Transaction, TransactionCreateData, TransactionType,
+    };
+
+    /// Open a websocket session without a real connection, its frames end up in the returned response body.
+    async fn open_session() -> (HttpResponse, Session) {
+        let (req, mut payload) = TestRequest::default()
+            .insert_header((header::UPGRADE, "websocket"))
+            .insert_header((header::CONNECTION, "upgrade"))
+            .insert_header((header::SEC_WEBSOCKET_VERSION, "13"))
+            .insert_header((header::SEC_WEBSOCKET_KEY, "dGhlIHNhbXBsZSBub25jZQ=="))
+            .to_http_parts();
+        let body = web::Payload::from_request(&req, &mut payload)
+            .await
+            .expect("failed to extract payload");
+
+        let (response, session, _stream) = actix_ws::handle(&req, body).expect("handshake failed");
+        (response, session)
+    }
+
+    #[test]
+    fn test_keepalive_message() {
+        let server = WebSocketServer::new();
+        let message = server
+            .keepalive_message()
+            .expect("keepalive messages are on by default");
+        assert_eq!(message.r#type.member_str(), "keepalive");
+
+        let server = WebSocketServer::with_config(WebSocketServerConfig {
+            keepalive_messages: false,
+            ..Default::default()
+        });
+        assert!(server.keepalive_message().is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_slow_consumer_is_dropped() {
+        let server = WebSocketServer::with_config(WebSocketServerConfig {
+            slow_consumer_timeout: Duration::from_millis(20),
+            ..Default::default()
+        });
+
+        let (_slow_response, slow_session) = open_session().await;
+        let (fast_response, fast_session) = open_session().await;
+
+        // The fast client reads everything it is sent, the slow one never reads at all.
+        let mut fast_body = fast_response.into_body();
+        actix_web::rt::spawn(async move {
+            while let Some(Ok(_)) =
+                std::future::poll_fn(|cx| std::pin::Pin::new(&mut fast_body).poll_next(cx)).await
+            {
+            }
+        });
+
+        let slow = Uuid::new_v4();
+        let fast = Uuid::new_v4();
+        let token = |address: &str| WebSocketTokenData {
+            address: address.to_owned(),
+            private_key: None,
+            computer_id: None,
+        };
+        server
+            .insert_session(
+                slow,
+                slow_session,
+                token("guest"),
+                WebSocketSessionOrigin::default(),
+            )
+            .expect("session should be admitted");
+        server
+            .insert_session(
+                fast,
+                fast_session,
+                token("guest"),
+                WebSocketSessionOrigin::default(),
+            )
+            .expect("session should be admitted");
+
+        for _ in 0..64 {
+            server.broadcast(r#"{"type":"keepalive"}"#).await;
+        }
+
+        assert!(!server.sessions.contains_sync(&slow));
+        assert!(server.sessions.contains_sync(&fast));
+    }
+
+    #[actix_web::test]
+    async fn test_balance_event_reaches_owner() {
+        let server = WebSocketServer::new();
+
+        let (owner_response, owner_session) = open_session().await;
+        let (other_response, other_session) = open_session().await;
+
+        let owner = Uuid::new_v4();
+        let other = Uuid::new_v4();
+        let token = |address: &str| WebSocketTokenData {
+            address: address.to_owned(),
+            private_key: None,
+            computer_id: None,
+        };
+        server
+            .insert_session(
+                owner,
+                owner_session,
+                token("kre3w0i79j"),
+                WebSocketSessionOrigin::default(),
+            )
+            .expect("session should be admitted");
+        server
+            .insert_session(
+                other,
+                other_session,
+                token("k0000000ab"),
+                WebSocketSessionOrigin::default(),
+            )
+            .expect("session should be admitted");
+        assert!(server.has_balance_subscriber("kre3w0i79j"));
+        assert!(!server.has_balance_subscriber("kunwatched"));
+
+        // As sent after a transfer credits kre3w0i79j
+        let event = WebSocketMessage::new_event(WebSocketEvent::Balance {
+            address: "kre3w0i79j".into(),
+            balance: rust_decimal::dec!(150),
+        });
+        server.broadcast_event(event).await;
+
+        let next_frame = |response: HttpResponse| async move {
+            let mut body = response.into_body();
+            let frame = std::future::poll_fn(|cx| std::pin::Pin::new(&mut body).poll_next(cx));
+            time::timeout(Duration::from_millis(50), frame).await.ok()
+        };
+
+        let frame = next_frame(owner_response)
+            .await
+            .flatten()
+            .expect("owner should receive the balance event")
+            .expect("failed to read frame");
+        let frame = String::from_utf8_lossy(&frame);
+        assert!(frame.contains(r#""event":"balance""#));
+        assert!(frame.contains(r#""address":"kre3w0i79j""#));
+
+        assert!(next_frame(other_response).await.is_none());
+    }
+
+    #[sqlx::test]
+    async fn test_transfer_balance_reaches_both_parties(pool: sqlx::PgPool) {
+        for address in ["kalice0000", "kbob000000"] {
+            Wallet::create_wallet(&pool, address, "hash", Some(rust_decimal::dec!(100)))
+                .await
+                .unwrap();
+        }
+        let server = WebSocketServer::new();
+
+        let (sender_response, sender_session) = open_session().await;
+        let (recipient_response, recipient_session) = open_session().await;
+        for (session, address) in [
+            (sender_session, "kalice0000"),
+            (recipient_session, "kbob000000"),
+        ] {
+            // No explicit subscribe, balances are on by default
+            server
+                .insert_session(
+                    Uuid::new_v4(),
+                    session,
+                    WebSocketTokenData::new(address.into(), None, None),
+                    WebSocketSessionOrigin::default(),
+                )
+                .expect("session should be admitted");
+        }
+
+        let transfer = TransactionCreateData {
+            from: "kalice0000".into(),
+            to: "kbob000000".into(),
+            amount: rust_decimal::dec!(50),
+            transaction_type: TransactionType::Transfer,
+            ..Default::default()
+        };
+        Transaction::create(&pool, transfer).await.unwrap();
+        server
+            .broadcast_balances(&pool, &["kalice0000", "kbob000000"])
+            .await;
+
+        for (response, address, balance) in [
+            (sender_response, "kalice0000", 50.0),
+            (recipient_response, "kbob000000", 150.0),
+        ] {
+            let mut body = response.into_body();
+            let frame = std::future::poll_fn(|cx| std::pin::Pin::new(&mut body).poll_next(cx));
+            let frame = time::timeout(Duration::from_millis(50), frame)
+                .await
+                .ok()
+                .flatten()
+                .expect("both parties should receive their balance")
+                .expect("failed to read frame");
+            // Skip the websocket frame header in front of the JSON
+            let frame = String::from_utf8_lossy(&frame);
+            let frame: serde_json::Value =
+                serde_json::from_str(&frame[frame.find('{').unwrap()..]).unwrap();
+            assert_eq!(frame["event"], "balance");
+            assert_eq!(frame["address"], address);
+            assert_eq!(frame["balance"], balance);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_logout_during_broadcast() {
+        let server = WebSocketServer::new();
+        let (_response, session) = open_session().await;
+
+        let uuid = Uuid::new_v4();
+        server
+            .insert_session(
+                uuid,
+                session,
+                WebSocketTokenData::new("kre3w0i79j".into(), None, None),
+                WebSocketSessionOrigin::default(),
+            )
+            .expect("session should be admitted");
+
+        let transaction = serde_json::from_value(serde_json::json!({
+            "id": 1,
+            "from": "k0000000ab",
+            "to": "kre3w0i79j",
+            "value": 5.0,
+            "time": "2025-01-01T00:00:00+00:00",
+            "type": "transfer",
+        }))
+        .expect("invalid transaction json");
+        let event = WebSocketMessage::new_event(WebSocketEvent::Transaction { transaction });
+
+        // The session is picked while logged in, then logs out before the send goes out.
+        let mut futures = server.event_futures(event);
+        assert_eq!(futures.len(), 1);
+        routes::auth::perform_logout(&server, &uuid, None);
+
+        let (_, outcome) = futures.next().await.expect("missing send");
+        assert!(matches!(outcome, SendOutcome::Skipped));
+    }
+
+    #[actix_web::test]
+    async fn test_replay_since() {
+        let server = WebSocketServer::with_config(WebSocketServerConfig {
+            replay_capacity: 3,
+            ..Default::default()
+        });
+        assert_eq!(server.last_event_id(), None);
+
+        for id in 1..=5 {
+            let transaction = serde_json::from_value(serde_json::json!({
+                "id": id,
+                "from": "k0000000ab",
+                "to": "kre3w0i79j",
+                "value": 5.0,
+                "time": "2025-01-01T00:00:00+00:00",
+                "type": "transfer",
+            }))
+            .expect("invalid transaction json");
+            server
+                .broadcast_event(WebSocketMessage::new_event(WebSocketEvent::Transaction {
+                    transaction,
+                }))
+                .await;
+        }
+        // Balances only go to their own address and are never replayed
+        server
+            .broadcast_event(WebSocketMessage::new_event(WebSocketEvent::Balance {
+                address: "kre3w0i79j".into(),
+                balance: Decimal::ONE,
+            }))
+            .await;
+        assert_eq!(server.last_event_id(), Some(5));
+
+        let transaction_ids = |events: Vec<WebSocketMessage>| -> Vec<i32> {
+            events
+                .into_iter()
+                .map(|event| match event.r#type {
+                    WebSocketMessageInner::Event {
+                        event: WebSocketEvent::Transaction { transaction },
+                        ..
+                    } => transaction.id,
+                    other => panic!("unexpected replayed message {other:?}"),
+                })
+                .collect()
+        };
+
+        let replay_since = |id| server.replay_since(id).expect("id should be known");
+        assert_eq!(transaction_ids(replay_since(3)), vec![4, 5]);
+        // Only the last three are kept
+        assert_eq!(transaction_ids(replay_since(0)), vec![3, 4, 5]);
+        assert!(replay_since(5).is_empty());
+        assert!(server.replay_since(6).is_none());
+
+        // After a restart the ids start over, so an id from before it can't be replayed from
+        let restarted = WebSocketServer::new();
+        assert!(restarted.replay_since(5).is_none());
+        assert_eq!(
+            restarted.replay_since(0).map(|events| events.len()),
+            Some(0)
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_faucet_grant_is_broadcast(pool: sqlx::PgPool) {
+        Wallet::create_wallet(&pool, "kalice0000", "alice", None)
+            .await
+            .unwrap();
+        let server = WebSocketServer::new();
+
+        server
+            .grant_faucet(&pool, "kalice0000", rust_decimal::dec!(5))
+            .await;
+        server
+            .grant_faucet(&pool, "kalice0000", rust_decimal::dec!(5))
+            .await;
+
+        // Only the one grant that was made is announced
+        let events = server.replay_since(0).unwrap();
+        assert_eq!(events.len(), 1);
+        match &events[0].r#type {
+            WebSocketMessageInner::Event {
+                event: WebSocketEvent::Transaction { transaction },
+                ..
+            } => {
+                assert_eq!(transaction.to, "kalice0000");
+                assert_eq!(transaction.value, rust_decimal::dec!(5));
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_replay_message() {
+        let server = WebSocketServer::new();
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/kromer")
+            .expect("lazy pool should not connect");
+        let state = crate::AppState::new(pool);
+
+        let (response, session) = open_session().await;
+        let (_other_response, other_session) = open_session().await;
+        let (uuid, other) = (Uuid::new_v4(), Uuid::new_v4());
+        for (uuid, session, address) in [
+            (uuid, session, "kre3w0i79j"),
+            (other, other_session, "kunrelated"),
+        ] {
+            server
+                .insert_session(
+                    uuid,
+                    session,
+                    WebSocketTokenData::new(address.into(), None, None),
+                    WebSocketSessionOrigin::default(),
+                )
+                .expect("session should be admitted");
+        }
+        server.subscribe_to_event(&uuid, WebSocketSubscriptionType::OwnTransactions);
+
+        for id in 1..=3 {
+            let transaction = serde_json::from_value(serde_json::json!({
+                "id": id,
+                "from": "k0000000ab",
+                "to": "kre3w0i79j",
+                "value": 5.0,
+                "time": "2025-01-01T00:00:00+00:00",
+                "type": "transfer",
+            }))
+            .expect("invalid transaction json");
+            server
+                .broadcast_event(WebSocketMessage::new_event(WebSocketEvent::Transaction {
+                    transaction,
+                }))
+                .await;
+        }
+
+        // Each broadcast frame carries the id to replay from
+        let mut body = response.into_body();
+        let frame = std::future::poll_fn(|cx| std::pin::Pin::new(&mut body).poll_next(cx));
+        let frame = time::timeout(Duration::from_millis(50), frame)
+            .await
+            .ok()
+            .flatten()
+            .expect("session should receive the transaction")
+            .expect("failed to read frame");
+        assert!(String::from_utf8_lossy(&frame).contains(r#""eventId":1"#));
+
+        let replay = |uuid, since: u64| {
+            let (state, server) = (&state, &server);
+            async move {
+                let msg = handler::process_text_msg(
+                    state,
+                    server,
+                    &uuid,
+                    &format!(r#"{{"id":9,"type":"replay","since":{since}}}"#),
+                )
+                .await
+                .expect("replay failed");
+                serde_json::to_value(&msg).expect("failed to serialize response")
+            }
+        };
+
+        let value = replay(uuid, 1).await;
+        assert_eq!(value["ok"], true);
+        assert_eq!(value["responding_to"], "replay");
+        let ids: Vec<_> = value["events"]
+            .as_array()
+            .expect("events should be a list")
+            .iter()
+            .map(|event| {
+                (
+                    event["eventId"].as_u64(),
+                    event["transaction"]["id"].as_i64(),
+                )
+            })
+            .collect();
+        assert_eq!(ids, vec![(Some(2), Some(2)), (Some(3), Some(3))]);
+
+        // Nothing the other session wasn't going to be sent anyway
+        let value = replay(other, 1).await;
+        assert_eq!(value["events"], serde_json::json!([]));
+
+        // An id from before a restart is refused rather than answered with nothing
+        let value = replay(uuid, 40).await;
+        assert_eq!(value["ok"], false);
+        assert_eq!(value["error"], "invalid_parameter");
+    }
+
+    #[actix_web::test]
+    async fn test_subscription_counts() {
+        let server = WebSocketServer::new();
+
+        let mut sessions = Vec::new();
+        for _ in 0..3 {
+            let (_response, session) = open_session().await;
+            let uuid = Uuid::new_v4();
+            server
+                .insert_session(
+                    uuid,
+                    session,
+                    WebSocketTokenData::new("kre3w0i79j".into(), None, None),
+                    WebSocketSessionOrigin::default(),
+                )
+                .expect("session should be admitted");
+            sessions.push(uuid);
+        }
+
+        server.subscribe_to_event(&sessions[0], WebSocketSubscriptionType::Transactions);
+        server.subscribe_to_event(&sessions[1], WebSocketSubscriptionType::Transactions);
+        server.unsubscribe_from_event(&sessions[2], &WebSocketSubscriptionType::Blocks);
+
+        let counts = server.subscription_counts();
+        assert_eq!(counts["transactions"], 2);
+        assert_eq!(counts["ownTransactions"], 3);
+        assert_eq!(counts["blocks"], 2);
+        assert_eq!(counts["names"], 0);
+        assert_eq!(counts.len(), WebSocketSubscriptionType::ALL.len());
+    }
+
+    #[actix_web::test]
+    async fn test_watch_list() {
+        let server = WebSocketServer::new();
+        let (_response, session) = open_session().await;
+
+        let uuid = Uuid::new_v4();
+        server
+            .insert_session(
+                uuid,
+                session,
+                WebSocketTokenData::new("kshop00000".into(), None, None),
+                WebSocketSessionOrigin::default(),
+            )
+            .expect("session should be admitted");
+
+        let transfer = |from: &str, to: &str| {
+            let transaction = serde_json::from_value(serde_json::json!({
+                "id": 1,
+                "from": from,
+                "to": to,
+                "value": 5.0,
+                "time": "2025-01-01T00:00:00+00:00",
+                "type": "transfer",
+            }))
+            .expect("invalid transaction json");
+            WebSocketMessage::new_event(WebSocketEvent::Transaction { transaction })
+        };
+
+        let reply = routes::subscriptions::watch(
+            &server,
+            &uuid,
+            vec![
+                "kre3w0i79j".into(),
+                "k0000000ab".into(),
+                "kre3w0i79j".into(),
+            ],
+            Some(1),
+        );
+        let reply = serde_json::to_value(&reply).expect("failed to serialize reply");
+        assert_eq!(reply["ok"], true);
+        assert_eq!(
+            reply["addresses"],
+            serde_json::json!(["k0000000ab", "kre3w0i79j"])
+        );
+
+        assert_eq!(
+            server
+                .event_futures(transfer("kbuyer0000", "kre3w0i79j"))
+                .len(),
+            1
+        );
+        assert_eq!(
+            server
+                .event_futures(transfer("k0000000ab", "kbuyer0000"))
+                .len(),
+            1
+        );
+        assert_eq!(
+            server
+                .event_futures(transfer("kbuyer0000", "kother0000"))
+                .len(),
+            0
+        );
+
+        // Invalid lists leave the current one alone
+        let reply = routes::subscriptions::watch(&server, &uuid, vec!["nope".into()], None);
+        assert_eq!(reply.ok, Some(false));
+        assert_eq!(
+            server
+                .event_futures(transfer("kbuyer0000", "kre3w0i79j"))
+                .len(),
+            1
+        );
+
+        routes::subscriptions::watch(&server, &uuid, Vec::new(), None);
+        assert_eq!(
+            server
+                .event_futures(transfer("kbuyer0000", "kre3w0i79j"))
+                .len(),
+            0
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_guest_session_limit() {
+        let server = WebSocketServer::with_config(WebSocketServerConfig {
+            max_guest_sessions: Some(2),
+            ..Default::default()
+        });
+        let guest = || WebSocketTokenData::new("guest".into(), None, None);
+
+        let mut guests = Vec::new();
+        for _ in 0..2 {
+            let (_response, session) = open_session().await;
+            let uuid = Uuid::new_v4();
+            server
+                .insert_session(uuid, session, guest(), WebSocketSessionOrigin::default())
+                .expect("guest should be admitted");
+            guests.push(uuid);
+        }
+
+        let (_response, session) = open_session().await;
+        assert!(matches!(
+            server.insert_session(
+                Uuid::new_v4(),
+                session,
+                guest(),
+                WebSocketSessionOrigin::default()
+            ),
+            Err(WebSocketServerError::GuestLimitReached)
+        ));
+
+        // Authenticated sessions are never limited
+        let (_response, session) = open_session().await;
+        let address = WebSocketTokenData::new("kre3w0i79j".into(), None, None);
+        assert!(
+            server
+                .insert_session(
+                    Uuid::new_v4(),
+                    session,
+                    address,
+                    WebSocketSessionOrigin::default()
+                )
+                .is_ok()
+        );
+
+        // A guest leaving frees its slot
+        server.cleanup_session(&guests[0]);
+        let (_response, session) = open_session().await;
+        assert!(
+            server
+                .insert_session(
+                    Uuid::new_v4(),
+                    session,
+                    guest(),
+                    WebSocketSessionOrigin::default()
+                )
+                .is_ok()
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_per_ip_session_limit() {
+        let server = WebSocketServer::with_config(WebSocketServerConfig {
+            max_sessions_per_ip: Some(2),
+            ..Default::default()
+        });
+        let lab: IpAddr = "10.0.0.7".parse().unwrap();
+        let address = || WebSocketTokenData::new("kre3w0i79j".into(), None, None);
+
+        let mut sessions = Vec::new();
+        for _ in 0..2 {
+            let (_response, session) = open_session().await;
+            let uuid = Uuid::new_v4();
+            server
+                .insert_session(
+                    uuid,
+                    session,
+                    address(),
+                    WebSocketSessionOrigin {
+                        ip: Some(lab),
+                        ..Default::default()
+                    },
+                )
+                .expect("session should be admitted");
+            sessions.push(uuid);
+        }
+
+        let (_response, session) = open_session().await;
+        assert!(matches!(
+            server.insert_session(
+                Uuid::new_v4(),
+                session,
+                address(),
+                WebSocketSessionOrigin {
+                    ip: Some(lab),
+                    ..Default::default()
+                }
+            ),
+            Err(WebSocketServerError::IpLimitReached)
+        ));
+
+        // Other clients are not affected
+        let (_response, session) = open_session().await;
+        let other = WebSocketSessionOrigin {
+            ip: "10.0.0.8".parse().ok(),
+            ..Default::default()
+        };
+        assert!(
+            server
+                .insert_session(Uuid::new_v4(), session, address(), other)
+                .is_ok()
+        );
+
+        server.cleanup_session(&sessions[0]);
+        let (_response, session) = open_session().await;
+        assert!(
+            server
+                .insert_session(
+                    Uuid::new_v4(),
+                    session,
+                    address(),
+                    WebSocketSessionOrigin {
+                        ip: Some(lab),
+                        ..Default::default()
+                    }
+                )
+                .is_ok()
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_token_expiration() {
+        let server = WebSocketServer::with_config(WebSocketServerConfig {
+            token_expiration: Duration::from_millis(20),
+            ..Default::default()
+        });
+
+        let token = server.obtain_token(WebSocketTokenData::new("guest".into(), None, None));
+        assert!(server.pending_tokens.contains_sync(&token));
+
+        time::sleep(Duration::from_millis(60)).await;
+        assert!(server.use_token(&token).is_err());
+    }
+
+    #[test]
+    fn test_heartbeat_config_validation() {
+        assert!(WebSocketServerConfig::default().validate().is_ok());
+
+        let config = |heartbeat_interval, client_timeout| WebSocketServerConfig {
+            heartbeat_interval: Duration::from_secs(heartbeat_interval),
+            client_timeout: Duration::from_secs(client_timeout),
+            ..Default::default()
+        };
+        assert!(config(15, 60).validate().is_ok());
+        assert!(config(10, 10).validate().is_err());
+        assert!(config(10, 5).validate().is_err());
+        assert!(config(0, 5).validate().is_err());
+    }
+
+    #[test]
+    fn test_from_env_rejects_bad_limits() {
+        let from_vars = |vars: &[(&str, &str)]| {
+            WebSocketServerConfig::from_lookup(|key| {
+                vars.iter()
+                    .find(|(name, _)| *name == key)
+                    .map(|(_, value)| value.to_string())
+            })
+        };
+
+        let err = from_vars(&[("WS_MAX_GUEST_SESSIONS", "lots")]).unwrap_err();
+        assert!(err.contains("WS_MAX_GUEST_SESSIONS"), "{err}");
+
+        let err = from_vars(&[
+            ("WS_MAX_GUEST_SESSIONS", "25"),
+            ("WS_MAX_SESSIONS_PER_IP", "-1"),
+        ])
+        .unwrap_err();
+        assert!(err.contains("WS_MAX_SESSIONS_PER_IP"), "{err}");
+
+        let config = from_vars(&[
+            ("WS_MAX_GUEST_SESSIONS", "25"),
+            ("WS_MAX_SESSIONS_PER_IP", "4"),
+        ])
+        .unwrap();
+        assert_eq!(config.max_guest_sessions, Some(25));
+        assert_eq!(config.max_sessions_per_ip, Some(4));
+
+        let config = from_vars(&[]).unwrap();
+        assert_eq!(config.max_guest_sessions, None);
+    }
+
+    #[test]
+    fn test_faucet_amount_for() {
+        let address = WebSocketTokenData::new("kre3w0i79j".into(), None, None);
+        let guest = WebSocketTokenData::new("guest".into(), None, None);
+
+        // Off by default
+        assert_eq!(WebSocketServer::new().faucet_amount_for(&address), None);
+
+        let server = WebSocketServer::with_config(WebSocketServerConfig {
+            faucet_amount: Some(rust_decimal::dec!(0.5)),
+            ..Default::default()
+        });
+        assert_eq!(
+            server.faucet_amount_for(&address),
+            Some(rust_decimal::dec!(0.5))
+        );
+        assert_eq!(server.faucet_amount_for(&guest), None);
+    }
+}
@@ -1,5 +1,6 @@
 use sqlx::{Pool, Postgres};
 
+use crate::errors::krist::catalog;
 use crate::models::krist::websockets::{
     WebSocketMessage, WebSocketMessageInner, WebSocketMessageResponse,
 };
@@ -22,7 +23,7 @@ pub async fn get_address(
             ok: Some(false),
             id: msg_id,
             r#type: WebSocketMessageInner::Error {
-                error: "internal_server_error".to_owned(),
+                error: catalog::INTERNAL_SERVER_ERROR.code.to_owned(),
                 message: "Something went wrong while processing your message".to_owned(),
             },
         };
@@ -36,7 +37,7 @@ pub async fn get_address(
                 ok: Some(false),
                 id: msg_id,
                 r#type: WebSocketMessageInner::Error {
-                    error: "address_not_found".to_owned(),
+                    error: catalog::ADDRESS_NOT_FOUND.code.to_owned(),
                     message: format!("Address {address} not found"),
                 },
             };
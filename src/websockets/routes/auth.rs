@@ -3,6 +3,7 @@ use uuid::Uuid;
 
 use crate::database::wallet::Model as Wallet;
 use crate::errors::KromerError;
+use crate::errors::krist::catalog;
 use crate::errors::wallet::WalletError;
 use crate::models::krist::websockets::{
     WebSocketMessage, WebSocketMessageInner, WebSocketMessageResponse,
@@ -56,7 +57,7 @@ pub async fn perform_login(
                         ok: Some(false),
                         id: msg_id,
                         r#type: WebSocketMessageInner::Error {
-                            error: "session_not_found".into(),
+                            error: catalog::SESSION_NOT_FOUND.code.to_owned(),
                             message: "Session not found".into(),
                         },
                     }
@@ -113,7 +114,7 @@ pub fn perform_logout(
             ok: Some(false),
             id: msg_id,
             r#type: WebSocketMessageInner::Error {
-                error: "session_not_found".into(),
+                error: catalog::SESSION_NOT_FOUND.code.to_owned(),
                 message: "Session not found".into(),
             },
         }
@@ -3,6 +3,7 @@ use uuid::Uuid;
 
 use crate::{
     database::wallet::Model as Wallet,
+    errors::krist::catalog,
     models::krist::{
         addresses::AddressJson,
         websockets::{WebSocketMessage, WebSocketMessageInner, WebSocketMessageResponse},
@@ -45,7 +46,7 @@ pub async fn get_myself(
             ok: Some(false),
             id: msg_id,
             r#type: WebSocketMessageInner::Error {
-                error: "internal_server_error".to_owned(),
+                error: catalog::INTERNAL_SERVER_ERROR.code.to_owned(),
                 message: "Something went wrong while processing your message".to_owned(),
             },
         };
@@ -59,7 +60,7 @@ pub async fn get_myself(
                 ok: Some(false),
                 id: msg_id,
                 r#type: WebSocketMessageInner::Error {
-                    error: "address_not_found".to_owned(),
+                    error: catalog::ADDRESS_NOT_FOUND.code.to_owned(),
                     message: format!("Address {} not found", session_data.address),
                 },
             };
@@ -3,9 +3,11 @@ use std::str::FromStr;
 use uuid::Uuid;
 
 use crate::{
+    errors::krist::catalog,
     models::krist::websockets::{
         WebSocketMessage, WebSocketMessageInner, WebSocketMessageResponse,
     },
+    utils::validation,
     websockets::{WebSocketServer, types::common::WebSocketSubscriptionType},
 };
 
@@ -42,7 +44,7 @@ pub fn subscribe(
         ok: Some(false),
         id: msg_id,
         r#type: WebSocketMessageInner::Error {
-            error: "invalid_parameter".to_owned(),
+            error: catalog::INVALID_PARAMETER.code.to_owned(),
             message: "Invalid parameter event".to_owned(),
         },
     }
@@ -81,12 +83,83 @@ pub fn unsubscribe(
         ok: Some(false),
         id: msg_id,
         r#type: WebSocketMessageInner::Error {
-            error: "invalid_parameter".to_owned(),
+            error: catalog::INVALID_PARAMETER.code.to_owned(),
             message: "Invalid parameter event".to_owned(),
         },
     }
 }
 
+pub fn watch(
+    server: &WebSocketServer,
+    uuid: &Uuid,
+    mut addresses: Vec<String>,
+    msg_id: Option<usize>,
+) -> WebSocketMessage {
+    addresses.sort();
+    addresses.dedup();
+
+    let error = if addresses.len() > server.config.max_watched_addresses {
+        Some(format!(
+            "Cannot watch more than {} addresses",
+            server.config.max_watched_addresses
+        ))
+    } else {
+        addresses
+            .iter()
+            .find(|address| !validation::is_valid_kromer_address(address))
+            .map(|address| format!("Invalid address {address}"))
+    };
+
+    if let Some(message) = error {
+        return WebSocketMessage {
+            ok: Some(false),
+            id: msg_id,
+            r#type: WebSocketMessageInner::Error {
+                error: catalog::INVALID_PARAMETER.code.to_owned(),
+                message,
+            },
+        };
+    }
+
+    let addresses = server.set_watched_addresses(uuid, addresses);
+
+    WebSocketMessage {
+        ok: Some(true),
+        id: msg_id,
+        r#type: WebSocketMessageInner::Response {
+            data: WebSocketMessageResponse::Watch { addresses },
+        },
+    }
+}
+
+/// Resend the events after `since` that the session missed, as far back as the replay buffer goes.
+/// An id the server never handed out is an error, so a client that saw it before a restart resyncs.
+pub fn replay(
+    server: &WebSocketServer,
+    uuid: &Uuid,
+    since: u64,
+    msg_id: Option<usize>,
+) -> WebSocketMessage {
+    let Some(events) = server.replay_for(uuid, since) else {
+        return WebSocketMessage {
+            ok: Some(false),
+            id: msg_id,
+            r#type: WebSocketMessageInner::Error {
+                error: catalog::INVALID_PARAMETER.code.to_owned(),
+                message: format!("Unknown event id {since}, list missed events from the API"),
+            },
+        };
+    };
+
+    WebSocketMessage {
+        ok: Some(true),
+        id: msg_id,
+        r#type: WebSocketMessageInner::Response {
+            data: WebSocketMessageResponse::Replay { events },
+        },
+    }
+}
+
 pub fn get_subscription_level(
     server: &WebSocketServer,
     uuid: &Uuid,
@@ -118,6 +191,7 @@ pub fn get_valid_subscription_levels(msg_id: Option<usize>) -> WebSocketMessage
         WebSocketSubscriptionType::Names,
         WebSocketSubscriptionType::OwnNames,
         WebSocketSubscriptionType::Motd,
+        WebSocketSubscriptionType::OwnBalance,
     ];
     let subscription_list: Vec<String> = subscription_list
         .into_iter()
@@ -2,16 +2,28 @@ use rust_decimal::{Decimal, dec};
 use sqlx::{Pool, Postgres};
 
 use crate::{
-    database::transaction::{TransactionCreateData, TransactionType},
-    models::krist::websockets::{
-        WebSocketEvent, WebSocketMessage, WebSocketMessageInner, WebSocketMessageResponse,
+    database::transaction::{
+        MAX_TRANSACTION_AMOUNT, TransactionCreateData, TransactionType, check_transfer_limits,
+        normalize_amount,
     },
+    errors::krist::{
+        KristError, KristErrorExt, catalog, transaction::TransactionError as KristTransactionError,
+    },
+    models::krist::{
+        INTEGER_AMOUNTS, is_sendable_amount,
+        transactions::TransactionJson,
+        websockets::{
+            WebSocketEvent, WebSocketMessage, WebSocketMessageInner, WebSocketMessageResponse,
+        },
+    },
+    routes::PaginationParams,
     websockets::WebSocketServer,
 };
 
 use crate::database::transaction::Model as Transaction;
 use crate::database::wallet::Model as Wallet;
 
+#[allow(clippy::too_many_arguments)]
 #[tracing::instrument(skip(pool, server, msg_id, private_key))]
 pub async fn make_transaction(
     pool: &Pool<Postgres>,
@@ -19,22 +31,62 @@ pub async fn make_transaction(
     to: String,
     amount: Decimal,
     metadata: Option<String>,
+    allow_duplicate: bool,
     msg_id: Option<usize>,
     server: &WebSocketServer,
 ) -> WebSocketMessage {
-    let amount = amount.round_dp(2); // Make sure we do not support 2 decimals after the dot.
+    if !is_sendable_amount(&amount, *INTEGER_AMOUNTS) {
+        return WebSocketMessage {
+            ok: Some(false),
+            id: msg_id,
+            r#type: WebSocketMessageInner::Error {
+                error: catalog::INVALID_PARAMETER.code.to_owned(),
+                message: "Invalid parameter amount".to_owned(),
+            },
+        };
+    }
+
+    let amount = match normalize_amount(amount) {
+        Ok(amount) => amount,
+        Err(error) => {
+            let error = KristTransactionError::from(error);
+
+            return WebSocketMessage {
+                ok: Some(false),
+                id: msg_id,
+                r#type: WebSocketMessageInner::Error {
+                    error: error.error_type().to_owned(),
+                    message: error.to_string(),
+                },
+            };
+        }
+    };
 
     if amount <= dec!(0.00) {
         return WebSocketMessage {
             ok: Some(false),
             id: msg_id,
             r#type: WebSocketMessageInner::Error {
-                error: "invalid_parameter".to_owned(),
+                error: catalog::INVALID_PARAMETER.code.to_owned(),
                 message: "Invalid parameter amount".to_owned(),
             },
         };
     }
 
+    if let Err(error) = check_transfer_limits(amount, metadata.as_deref(), *MAX_TRANSACTION_AMOUNT)
+    {
+        let error = KristTransactionError::from(error);
+
+        return WebSocketMessage {
+            ok: Some(false),
+            id: msg_id,
+            r#type: WebSocketMessageInner::Error {
+                error: error.error_type().to_owned(),
+                message: error.to_string(),
+            },
+        };
+    }
+
     let resp = match Wallet::verify_address(pool, private_key).await {
         Ok(resp) => resp,
         Err(_) => {
@@ -42,7 +94,7 @@ pub async fn make_transaction(
                 ok: Some(false),
                 id: msg_id,
                 r#type: WebSocketMessageInner::Error {
-                    error: "database_error".to_owned(),
+                    error: catalog::DATABASE_ERROR.code.to_owned(),
                     message: "An error occured in the database".to_owned(),
                 },
             };
@@ -53,7 +105,7 @@ pub async fn make_transaction(
             ok: Some(false),
             id: msg_id,
             r#type: WebSocketMessageInner::Error {
-                error: "invalid_parameter".to_owned(),
+                error: catalog::INVALID_PARAMETER.code.to_owned(),
                 message: "Invalid parameter privatekey".to_owned(),
             },
         };
@@ -61,6 +113,17 @@ pub async fn make_transaction(
 
     let sender = resp.model;
 
+    if sender.locked {
+        return WebSocketMessage {
+            ok: Some(false),
+            id: msg_id,
+            r#type: WebSocketMessageInner::Error {
+                error: catalog::ADDRESS_LOCKED.code.to_owned(),
+                message: format!("Address {} is locked", sender.address),
+            },
+        };
+    }
+
     let recipient = match Wallet::fetch_by_address(pool, to.clone()).await {
         Ok(model) => model,
         Err(_) => {
@@ -68,7 +131,7 @@ pub async fn make_transaction(
                 ok: Some(false),
                 id: msg_id,
                 r#type: WebSocketMessageInner::Error {
-                    error: "database_error".to_owned(),
+                    error: catalog::DATABASE_ERROR.code.to_owned(),
                     message: "An error occured in the database".to_owned(),
                 },
             };
@@ -82,7 +145,7 @@ pub async fn make_transaction(
                 ok: Some(false),
                 id: msg_id,
                 r#type: WebSocketMessageInner::Error {
-                    error: "address_not_found".to_owned(),
+                    error: catalog::ADDRESS_NOT_FOUND.code.to_owned(),
                     message: format!("Address {to} not found"),
                 },
             };
@@ -94,7 +157,7 @@ pub async fn make_transaction(
             ok: Some(false),
             id: msg_id,
             r#type: WebSocketMessageInner::Error {
-                error: "insufficient_funds".to_owned(),
+                error: catalog::INSUFFICIENT_FUNDS.code.to_owned(),
                 message: "Insufficient funds".to_owned(),
             },
         };
@@ -106,26 +169,36 @@ pub async fn make_transaction(
         amount,
         metadata: metadata.clone(),
         transaction_type: TransactionType::Transfer,
+        allow_duplicate,
         ..Default::default()
     };
 
-    let response = Transaction::create(pool, creation_data).await;
+    let transaction = match Transaction::create(pool, creation_data).await {
+        Ok(transaction) => transaction,
+        Err(error) => {
+            // Rejections like a duplicate transfer are passed on, anything else stays vague.
+            let (error, message) = match KristError::from(error) {
+                KristError::Database(_) => (
+                    catalog::DATABASE_ERROR.code.to_owned(),
+                    "An error occured in the database".to_owned(),
+                ),
+                error => (error.error_type().to_owned(), error.to_string()),
+            };
 
-    if response.is_err() {
-        return WebSocketMessage {
-            ok: Some(false),
-            id: msg_id,
-            r#type: WebSocketMessageInner::Error {
-                error: "database_error".to_owned(),
-                message: "An error occured in the database".to_owned(),
-            },
-        };
-    }
-    let transaction = response.unwrap();
+            return WebSocketMessage {
+                ok: Some(false),
+                id: msg_id,
+                r#type: WebSocketMessageInner::Error { error, message },
+            };
+        }
+    };
     let event = WebSocketMessage::new_event(WebSocketEvent::Transaction {
         transaction: transaction.clone().into(),
     });
     server.broadcast_event(event).await;
+    server
+        .broadcast_balances(pool, &[&sender.address, &recipient.address])
+        .await;
 
     WebSocketMessage {
         ok: Some(true),
@@ -137,3 +210,44 @@ pub async fn make_transaction(
         },
     }
 }
+
+#[tracing::instrument(skip(pool, msg_id))]
+pub async fn get_transactions(
+    pool: &Pool<Postgres>,
+    params: &PaginationParams,
+    msg_id: Option<usize>,
+) -> WebSocketMessage {
+    let total = Transaction::total_count_no_mined(pool, params, None).await;
+    let transactions = Transaction::sorted_by_date(pool, params, None).await;
+
+    let (total, transactions) = match (total, transactions) {
+        (Ok(total), Ok(transactions)) => (total, transactions),
+        (Err(err), _) | (_, Err(err)) => {
+            tracing::error!("Caught an error: {err}");
+
+            return WebSocketMessage {
+                ok: Some(false),
+                id: msg_id,
+                r#type: WebSocketMessageInner::Error {
+                    error: catalog::DATABASE_ERROR.code.to_owned(),
+                    message: "An error occured in the database".to_owned(),
+                },
+            };
+        }
+    };
+
+    let transactions: Vec<TransactionJson> =
+        transactions.into_iter().map(|trans| trans.into()).collect();
+
+    WebSocketMessage {
+        ok: Some(true),
+        id: msg_id,
+        r#type: WebSocketMessageInner::Response {
+            data: WebSocketMessageResponse::Transactions {
+                count: transactions.len(),
+                total,
+                transactions,
+            },
+        },
+    }
+}
@@ -1,3 +1,5 @@
+use std::net::IpAddr;
+
 use scc::HashSet;
 use serde::{Deserialize, Serialize};
 
@@ -16,6 +18,25 @@ pub struct WebSocketSessionData {
     pub session: actix_ws::Session,
     pub subscriptions: HashSet<WebSocketSubscriptionType>,
     pub computer_id: Option<i32>,
+    /// Addresses whose transactions the session receives regardless of its subscriptions
+    pub watched_addresses: Vec<String>,
+    /// Whether the session connected as a guest and holds one of the capped guest slots until cleanup
+    #[serde(skip)]
+    pub guest_slot: bool,
+    /// The client address the session counts against for the per-IP limit
+    #[serde(skip)]
+    pub ip: Option<IpAddr>,
+    /// The `X-Request-Id` of the gateway request that opened the session
+    pub request_id: Option<String>,
+}
+
+/// Where a websocket session connected from
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WebSocketSessionOrigin {
+    /// The client address, counted against the per-IP limit
+    pub ip: Option<IpAddr>,
+    /// The `X-Request-Id` of the gateway request, so broadcast logs can be tied back to it
+    pub request_id: Option<String>,
 }
 
 #[derive(Clone, Copy, Debug, Hash, Eq, Serialize, Deserialize, PartialEq, PartialOrd)]
@@ -28,9 +49,22 @@ pub enum WebSocketSubscriptionType {
     Names,
     OwnNames,
     Motd,
+    /// Balance changes of the session's own address
+    OwnBalance,
 }
 
 impl WebSocketSubscriptionType {
+    pub const ALL: [WebSocketSubscriptionType; 8] = [
+        WebSocketSubscriptionType::Blocks,
+        WebSocketSubscriptionType::OwnBlocks,
+        WebSocketSubscriptionType::Transactions,
+        WebSocketSubscriptionType::OwnTransactions,
+        WebSocketSubscriptionType::Names,
+        WebSocketSubscriptionType::OwnNames,
+        WebSocketSubscriptionType::Motd,
+        WebSocketSubscriptionType::OwnBalance,
+    ];
+
     pub fn is_valid(subscription_type: &str) -> bool {
         subscription_type
             .parse::<WebSocketSubscriptionType>()
@@ -46,6 +80,7 @@ impl WebSocketSubscriptionType {
             WebSocketSubscriptionType::Names => "names".to_owned(),
             WebSocketSubscriptionType::OwnNames => "ownNames".to_owned(),
             WebSocketSubscriptionType::Motd => "motd".to_owned(),
+            WebSocketSubscriptionType::OwnBalance => "ownBalance".to_owned(),
         }
     }
 }
@@ -58,6 +93,12 @@ impl WebSocketSessionData {
     pub fn is_subscribed_to(&self, event: WebSocketSubscriptionType) -> bool {
         self.subscriptions.contains_sync(&event)
     }
+
+    pub fn is_watching(&self, address: &str) -> bool {
+        self.watched_addresses
+            .iter()
+            .any(|watched| watched == address)
+    }
 }
 
 impl std::str::FromStr for WebSocketSubscriptionType {
@@ -72,6 +113,8 @@ impl std::str::FromStr for WebSocketSubscriptionType {
             "names" => Ok(Self::Names),
             "ownNames" => Ok(Self::OwnNames),
             "motd" => Ok(Self::Motd),
+            // `balance` is what this subscription was called before it was subscribed by default
+            "ownBalance" | "balance" => Ok(Self::OwnBalance),
             _ => Err(()),
         }
     }
@@ -87,6 +130,7 @@ impl std::fmt::Display for WebSocketSubscriptionType {
             Self::Names => write!(f, "names"),
             Self::OwnNames => write!(f, "ownNames"),
             Self::Motd => write!(f, "motd"),
+            Self::OwnBalance => write!(f, "ownBalance"),
         }
     }
 }
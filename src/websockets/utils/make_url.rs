@@ -2,9 +2,8 @@ use std::env;
 
 use uuid::Uuid;
 
-use crate::errors::{KromerError, websocket::WebSocketError};
-
-pub fn make_url(uuid: Uuid) -> Result<String, KromerError> {
+/// The gateway URL handed to a client for `uuid`, with `public_url` being where it reaches us.
+pub fn make_url(public_url: &str, uuid: Uuid) -> String {
     let force_insecure = env::var("FORCE_WS_INSECURE").unwrap_or("true".to_owned());
     let schema = if force_insecure == "true" {
         "ws"
@@ -12,10 +11,5 @@ pub fn make_url(uuid: Uuid) -> Result<String, KromerError> {
         "wss"
     };
 
-    let server_url = env::var("PUBLIC_URL")
-        .map_err(|_| KromerError::WebSocket(WebSocketError::ServerConfigError))?;
-
-    Ok(format!(
-        "{schema}://{server_url}/api/krist/ws/gateway/{uuid}"
-    ))
+    format!("{schema}://{public_url}/api/krist/ws/gateway/{uuid}")
 }